@@ -8,24 +8,6 @@ fn comp_ident(path: &syn::Path, name: &str) -> bool {
     path.is_ident(&Ident::new(name, Span::call_site()))
 }
 
-fn is_field_type_option(field_type: &mut syn::Type) -> bool {
-    match field_type {
-        syn::Type::Path(p) => {
-            if p.path.segments.len() == 0 {
-                return false;
-            }
-                       
-            let is_option = &p.path.segments[0].ident.to_string() == "Option";
-            if is_option {
-                p.path.segments.pop();
-            }
-
-            return is_option
-        },
-        _ => false
-    }
-}
-
 fn name_string_with_attributes(field_attrs: &Vec<syn::Attribute>, initial_name: String ) -> String {
 
     let mut name_string = initial_name;
@@ -51,6 +33,7 @@ pub fn struct_map(struct_type: proc_macro2::TokenStream, fields: Vec<&mut syn::F
     let mut serialize_body = Vec::new();
     let mut serialize_result = Vec::new();
     let mut deserialize_body = Vec::new();
+    let mut schema_fields = Vec::new();
 
     for field in fields {
 
@@ -61,11 +44,9 @@ pub fn struct_map(struct_type: proc_macro2::TokenStream, fields: Vec<&mut syn::F
              // If field has name
             Some(f) => {
 
-                let field_type = &mut field.ty;
-                let is_optional = is_field_type_option(field_type);
-
-                serialize_named_field(field, &f, &mut serialize_body, &mut serialize_result, is_optional);
-                deserialize_named_field(field, &f, &mut deserialize_body, is_optional);
+                serialize_named_field(field, &f, &mut serialize_body, &mut serialize_result);
+                deserialize_named_field(field, &f, &mut deserialize_body);
+                schema_named_field(field, &f, &mut schema_fields);
             },
 
             None => {}
@@ -73,6 +54,8 @@ pub fn struct_map(struct_type: proc_macro2::TokenStream, fields: Vec<&mut syn::F
 
     }
 
+    let struct_name = struct_type.to_string();
+
     // Construct impl
     let out = quote! {
 
@@ -110,6 +93,10 @@ pub fn struct_map(struct_type: proc_macro2::TokenStream, fields: Vec<&mut syn::F
         
                 Ok(())
             }
+
+            fn schema() -> TdfSchema {
+                TdfSchema::named_struct(#struct_name, || vec![ #( #schema_fields ),* ])
+            }
         
         }
         
@@ -121,7 +108,7 @@ pub fn struct_map(struct_type: proc_macro2::TokenStream, fields: Vec<&mut syn::F
 
 
 
-fn serialize_named_field(field: &Field, f: &Ident, serialize_body: &mut Vec<proc_macro2::TokenStream>, serialize_result: &mut Vec<proc_macro2::TokenStream>, is_optional: bool) {
+fn serialize_named_field(field: &Field, f: &Ident, serialize_body: &mut Vec<proc_macro2::TokenStream>, serialize_result: &mut Vec<proc_macro2::TokenStream>) {
 
 
     let token_type = field.ty.to_token_stream();
@@ -132,16 +119,10 @@ fn serialize_named_field(field: &Field, f: &Ident, serialize_body: &mut Vec<proc
     );
 
     
-    let ser_body_field = if is_optional {
-        quote! {
-            log::trace!("Field {}", #name_string);
-            let #f = ser.ser_field_optional::< #token_type >( TDFToken::Label( #name_string.to_uppercase() ) )?;
-        }
-    } else {
-        quote! {
-            log::trace!("Field {}", #name_string);
-            let (_, #f ) = ser.ser_field::< #token_type >()?;
-        }
+    // Absent fields (like Option) are resolved by label
+    let ser_body_field = quote! {
+        log::trace!("Field {}", #name_string);
+        let #f = ser.ser_named_field::< #token_type >( #name_string )?;
     };
     
     serialize_body.push(ser_body_field);
@@ -157,25 +138,35 @@ fn serialize_named_field(field: &Field, f: &Ident, serialize_body: &mut Vec<proc
 }
 
 
-fn deserialize_named_field(field: &Field, f: &Ident, deserialize_body: &mut Vec<proc_macro2::TokenStream>, is_optional: bool ) {
+fn deserialize_named_field(field: &Field, f: &Ident, deserialize_body: &mut Vec<proc_macro2::TokenStream>) {
 
     let name_string = name_string_with_attributes(
         &field.attrs, 
         format!("{}", f)
     );
 
-    let optional_quote = match is_optional {
-        true => quote! {
-            match &mut self.#f {
-                Some(#f) => des.des_field( #name_string , #f )?,
-                None => {}
-            }
-        },
-        false => quote! {
-            des.des_field( #name_string , &mut self.#f )?;
-        }
+    // Absent fields are skipped by des_field
+    let field_quote = quote! {
+        des.des_field( #name_string , &mut self.#f )?;
     };
 
-    deserialize_body.push(optional_quote);
+    deserialize_body.push(field_quote);
+
+}
+
+fn schema_named_field(field: &Field, f: &Ident, schema_fields: &mut Vec<proc_macro2::TokenStream>) {
+
+    let token_type = field.ty.to_token_stream();
+
+    let field_string = format!("{}", f);
 
-}
\ No newline at end of file
+    let name_string = name_string_with_attributes(
+        &field.attrs, 
+        field_string.clone()
+    );
+
+    schema_fields.push(quote! {
+        FieldSchema::new( #field_string, #name_string, < #token_type as Deserialize >::schema() )
+    });
+
+}
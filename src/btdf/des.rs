@@ -11,6 +11,55 @@ use num_traits::FromPrimitive;
 pub const VARSIZE_NEGATIVE: u8 = 0x40;
 pub const VARSIZE_MORE: u8 = 0x80;
 
+/// Decode 3 bytes of label into 4 chars string
+pub fn decode_label(tag_bytes: &[u8; 3]) -> String {
+    decode_label_bytes(tag_bytes).iter().map(|b| char::from(*b)).collect()
+}
+
+/// Decode 3 bytes of label into 4 ASCII chars
+pub fn decode_label_bytes(tag_bytes: &[u8; 3]) -> [u8; 4] {
+
+    fn converter(m: u8, c: u8) -> u8 {
+        if m | c == 0x00 {
+            // Space
+            32
+        } else if m & 0x40 == 0 {
+            0x30 | c
+        } else {
+            m | c
+        }
+    }
+
+    [
+        converter(
+            (tag_bytes[0] & 0x80) >> 1,
+            (tag_bytes[0] & 0x7C) >> 2
+        ),
+        converter(
+            (tag_bytes[0] & 2) << 5,
+            ((tag_bytes[0] & 1) << 4) | ((tag_bytes[1] & 0xF0) >> 4)
+        ),
+        converter(
+            (tag_bytes[1] & 8) << 3,
+            ((tag_bytes[1] & 7) << 2) | ((tag_bytes[2] & 0xC0) >> 6)
+        ),
+        converter(
+            (tag_bytes[2] & 0x20) << 1,
+            tag_bytes[2] & 0x1F
+        ),
+    ]
+}
+
+/// Apparently EA tdf has a bug, where pair list of pair list
+/// has value type encoded as a map in some specific fields
+pub fn pair_list_value_override(label: &str) -> Option<TDFToken> {
+    match label {
+        "GBRA" | "MSID" => Some(TDFToken::PairListType),
+        "PELM" => Some(TDFToken::ListType),
+        _ => None,
+    }
+}
+
 pub struct BTDFDeserializer {
     pub stream: TDFTokenStream,
 }
@@ -40,6 +89,7 @@ impl BTDFDeserializer {
             TDFToken::ObjectTypeType => self.des_object_type(reader),
             TDFToken::ObjectIdType   => self.des_object_id(reader),
             TDFToken::FloatType      => self.des_float(reader),
+            TDFToken::TimeType       => self.des_int(reader),
             TDFToken::GenericType    => self.des_generic(reader),
             _ => bail!("Expected token, found {:?}!", tdf_type)
         };
@@ -65,40 +115,7 @@ impl BTDFDeserializer {
             has_heat1_bug = true;
         }
 
-        let mut label_bytes = String::new(); 
-
-        fn converter(m: u8, c: u8) -> char {
-            if m | c == 0x00 {
-                // Space
-                return char::from(32);
-            } else if m & 0x40 == 0 {
-                return char::from(0x30 | c)
-            } else {
-                return char::from(m | c)
-            }
-        }
-
-        label_bytes.push(converter(
-            (tag_bytes[0] & 0x80) >> 1,
-            (tag_bytes[0] & 0x7C) >> 2
-        ));
-
-        label_bytes.push(converter(
-            (tag_bytes[0] & 2) << 5,
-            ((tag_bytes[0] & 1) << 4) | ((tag_bytes[1] & 0xF0) >> 4)
-        ));
-    
-        label_bytes.push(converter(
-            (tag_bytes[1] & 8) << 3, 
-            ((tag_bytes[1] & 7) << 2) | ((tag_bytes[2] & 0xC0) >> 6)
-        ));
-    
-        label_bytes.push(converter(
-            (tag_bytes[2] & 0x20) << 1,
-            tag_bytes[2] & 0x1F
-        ));
-
-        
+        let label_bytes = decode_label(&label_tag_bytes);
 
         self.stream.push(TDFToken::Label(label_bytes));
 
@@ -263,10 +280,10 @@ impl BTDFDeserializer {
 
         let field_type = self.stream.get(self.stream.len() - 2)?;
         
-        if field_type == TDFToken::Label("GBRA".into()) || field_type == TDFToken::Label("MSID".into()) {
-            tdf_value = TDFToken::PairListType;
-        } else if field_type == TDFToken::Label("PELM".into()) {
-            tdf_value = TDFToken::ListType;
+        if let TDFToken::Label(label) = field_type {
+            if let Some(value_override) = pair_list_value_override(&label) {
+                tdf_value = value_override;
+            }
         }
 
         let size = self.read_number(reader)? as usize;
@@ -0,0 +1,231 @@
+/*
+    Annotated hex dump of TDF binary
+
+    000000  9e 99 00 00                 GID  [9e 99 00] IntType
+    000004  a4 01                         varint (36 + 1<<6) = 100
+    000006  ba 1b 65 01                 NAME [ba 1b 65] StringType
+    00000a  05 67 61 6d 65 00             string "game"
+
+    Every element is printed with its offset and raw bytes, indented by nesting.
+    Bytes after the first decoding error are marked with !!
+*/
+
+use crate::token::*;
+use crate::hex::hex;
+use anyhow::{Result, bail};
+use std::fmt::Write;
+
+use super::Scanner;
+
+/// Raw bytes shown on a single line
+const ROW_BYTES: usize = 8;
+
+
+/// Annotated dump of the whole buffer
+pub fn hex_dump(buf: &[u8]) -> String {
+    let mut dumper = Dumper {
+        scanner: Scanner::new(buf),
+        out: String::new(),
+        depth: 0,
+        printed: 0,
+    };
+    if let Err(err) = dumper.dump_map(true) {
+        dumper.dump_undecodable(&err.to_string());
+    }
+    dumper.out
+}
+
+struct Dumper<'a> {
+    scanner: Scanner<'a>,
+    out: String,
+    depth: usize,
+    /// End of bytes already printed, start of element being decoded
+    printed: usize,
+}
+
+/// How varint bytes make up the number
+fn varint_breakdown(bytes: &[u8], value: i64) -> String {
+    let mut parts = Vec::new();
+    for (i, b) in bytes.iter().enumerate() {
+        if i == 0 {
+            parts.push(format!("{}", b & 0x3F));
+        } else {
+            parts.push(format!("{}<<{}", b & 0x7F, 6 + 7 * (i - 1)));
+        }
+    }
+    let sign = if bytes.first().is_some_and(|b| b & 0x40 != 0) { "-" } else { "" };
+    if parts.len() > 1 {
+        format!("varint {}({}) = {}", sign, parts.join(" + "), value)
+    } else {
+        format!("varint {}{} = {}", sign, parts[0], value)
+    }
+}
+
+impl<'a> Dumper<'a> {
+
+    /// Print line for bytes from start to current position
+    fn line(&mut self, start: usize, text: &str) {
+        let bytes = &self.scanner.buf()[start..self.scanner.pos];
+        let mut raw = hex(&bytes[..bytes.len().min(ROW_BYTES)], " ");
+        if bytes.len() > ROW_BYTES {
+            raw.push_str(" ..");
+        }
+        let _ = writeln!(self.out, "{:06x}  {:<26}  {}{}", start, raw, "  ".repeat(self.depth), text);
+        self.printed = self.scanner.pos;
+    }
+
+    /// Bytes from start of the element that failed to decode
+    fn dump_undecodable(&mut self, reason: &str) {
+        let buf = self.scanner.buf();
+        let start = self.printed.min(buf.len());
+        let _ = writeln!(self.out, "!! undecodable at {:06x}: {}", start, reason);
+        for (i, row) in buf[start..].chunks(16).enumerate() {
+            let _ = writeln!(self.out, "{:06x}  {:<47}  !!", start + i * 16, hex(row, " "));
+        }
+    }
+
+    fn dump_number(&mut self, name: &str) -> Result<i64> {
+        let start = self.scanner.pos;
+        let value = self.scanner.read_number()?;
+        let breakdown = varint_breakdown(&self.scanner.buf()[start..self.scanner.pos], value);
+        self.line(start, &format!("{}{}", name, breakdown));
+        Ok(value)
+    }
+
+    fn dump_type(&mut self, name: &str) -> Result<TDFToken> {
+        let start = self.scanner.pos;
+        let value_type = self.scanner.read_type()?;
+        self.line(start, &format!("{}{:?}", name, value_type));
+        Ok(value_type)
+    }
+
+    /// Label, its raw tag and type of the field
+    fn dump_header(&mut self, kind: &str) -> Result<(String, TDFToken)> {
+        let start = self.scanner.pos;
+        let label = self.scanner.read_label()?;
+        let tag = hex(&self.scanner.buf()[start..self.scanner.pos], " ");
+        let value_type = self.scanner.read_type()?;
+        self.line(start, &format!("{}{} [{}] {:?}", kind, label, tag, value_type));
+        Ok((label.to_string(), value_type))
+    }
+
+    fn dump_map(&mut self, is_root: bool) -> Result<()> {
+        loop {
+            let start = self.scanner.pos;
+            match self.scanner.peek_u8() {
+                None if is_root => return Ok(()),
+                Some(0) => {
+                    self.scanner.read_u8()?;
+                    self.line(start, "end of map");
+                    return Ok(());
+                },
+                Some(b) if b <= 2 => {
+                    self.scanner.read_u8()?;
+                    self.line(start, "union map marker");
+                },
+                _ => {},
+            }
+            let (label, value_type) = self.dump_header("")?;
+            self.depth += 1;
+            self.dump_value(&value_type, Some(&label))?;
+            self.depth -= 1;
+        }
+    }
+
+    fn dump_value(&mut self, value_type: &TDFToken, label: Option<&str>) -> Result<()> {
+        let start = self.scanner.pos;
+        match value_type {
+            TDFToken::IntType | TDFToken::TimeType => {
+                self.dump_number("")?;
+            },
+            TDFToken::StringType => {
+                let value = self.scanner.read_string()?;
+                self.line(start, &format!("string {:?}", String::from_utf8_lossy(value)));
+            },
+            TDFToken::BlobType => {
+                let value = self.scanner.read_blob()?;
+                self.line(start, &format!("blob of {} bytes: {}", value.len(), hex(value, " ")));
+            },
+            TDFToken::MapType => self.dump_map(false)?,
+            TDFToken::ListType => {
+                let item_type = self.dump_type("element type ")?;
+                let size = self.dump_number("size ")?;
+                for i in 0..size {
+                    self.line(self.scanner.pos, &format!("[{}]", i));
+                    self.depth += 1;
+                    self.dump_value(&item_type, None)?;
+                    self.depth -= 1;
+                }
+            },
+            TDFToken::PairListType => {
+                let key_type = self.dump_type("key type ")?;
+                let mut item_type = self.dump_type("value type ")?;
+                if let Some(value_override) = label.and_then(super::pair_list_value_override) {
+                    self.line(self.scanner.pos, &format!("value type treated as {:?}", value_override));
+                    item_type = value_override;
+                }
+                let size = self.dump_number("size ")?;
+                for i in 0..size {
+                    self.line(self.scanner.pos, &format!("key [{}]", i));
+                    self.depth += 1;
+                    self.dump_value(&key_type, None)?;
+                    self.depth -= 1;
+                    self.line(self.scanner.pos, &format!("value [{}]", i));
+                    self.depth += 1;
+                    self.dump_value(&item_type, None)?;
+                    self.depth -= 1;
+                }
+            },
+            TDFToken::UnionType => {
+                let union_type = UnionType::from_tag(self.scanner.read_u8()?);
+                self.line(start, &format!("union type {:?}", union_type));
+                if union_type != UnionType::Unset {
+                    self.dump_member()?;
+                }
+            },
+            TDFToken::IntListType => {
+                let size = self.dump_number("size ")?;
+                for i in 0..size {
+                    self.dump_number(&format!("[{}] ", i))?;
+                }
+            },
+            TDFToken::ObjectTypeType => {
+                self.dump_number("component ")?;
+                self.dump_number("type ")?;
+            },
+            TDFToken::ObjectIdType => {
+                self.dump_number("component ")?;
+                self.dump_number("type ")?;
+                self.dump_number("id ")?;
+            },
+            TDFToken::FloatType => {
+                let bytes = self.scanner.read_bytes(4)?;
+                let value = f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                self.line(start, &format!("float {}", value));
+            },
+            TDFToken::GenericType => {
+                let exists = self.scanner.read_u8()? != 0;
+                self.line(start, &format!("generic {}", if exists { "valid" } else { "invalid" }));
+                if exists {
+                    self.dump_number("tdf id ")?;
+                    if self.scanner.peek_u8() != Some(0) {
+                        self.dump_member()?;
+                    }
+                    let end = self.scanner.pos;
+                    self.scanner.read_u8()?;
+                    self.line(end, "end of generic");
+                }
+            },
+            _ => bail!("Expected type token, found {:?}", value_type),
+        }
+        Ok(())
+    }
+
+    fn dump_member(&mut self) -> Result<()> {
+        let (label, value_type) = self.dump_header("member ")?;
+        self.depth += 1;
+        self.dump_value(&value_type, Some(&label))?;
+        self.depth -= 1;
+        Ok(())
+    }
+}
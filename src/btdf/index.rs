@@ -0,0 +1,251 @@
+/*
+    Lazy random-access index of TDF binary
+
+    One pass over the buffer builds offset table of every value,
+    values are decoded only when accessed.
+*/
+
+use crate::token::*;
+use crate::rtdf::Serialize;
+use crate::value::{PathSegment, TdfPath, TdfValue};
+use anyhow::{Result, bail};
+use std::io::Cursor;
+use std::ops::Range;
+
+use super::{Scanner, decode_value};
+
+
+/// Single row of offset table
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    /// Label of map field or union member
+    pub label: Option<String>,
+    /// Encoded key of pair list entry
+    pub key: Option<(TDFToken, Range<usize>)>,
+    pub value_type: TDFToken,
+    /// Value bytes, without label and type byte
+    pub range: Range<usize>,
+    /// Rows of direct children
+    pub children: Vec<usize>,
+}
+
+/// Offset table over encoded buffer, first row is the root map
+pub struct TdfIndex<'a> {
+    buf: &'a [u8],
+    entries: Vec<IndexEntry>,
+}
+
+/// Value of the index, decoded on access
+#[derive(Clone, Copy)]
+pub struct IndexNode<'i, 'a> {
+    index: &'i TdfIndex<'a>,
+    row: usize,
+}
+
+impl<'a> TdfIndex<'a> {
+
+    pub fn build(buf: &'a [u8]) -> Result<Self> {
+        let mut index = Self {
+            buf,
+            entries: Vec::new(),
+        };
+        let mut scanner = Scanner::new(buf);
+        index.scan(&mut scanner, TDFToken::MapType, None, None)?;
+        Ok(index)
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn root(&self) -> IndexNode<'_, 'a> {
+        IndexNode {
+            index: self,
+            row: 0,
+        }
+    }
+
+    /// Node at concrete path, like GAME.PROS[2].PNAM
+    pub fn pointer(&self, path: &str) -> Result<Option<IndexNode<'_, 'a>>> {
+        let path = TdfPath::parse(path)?;
+        if !path.is_concrete() {
+            bail!("Path {} must not contain wildcards", path);
+        }
+        let mut node = self.root();
+        for segment in &path.0 {
+            node = match node.child(segment)? {
+                Some(child) => child,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(node))
+    }
+
+    fn push(&mut self, label: Option<String>, key: Option<(TDFToken, Range<usize>)>, value_type: TDFToken, start: usize) -> usize {
+        self.entries.push(IndexEntry {
+            label,
+            key,
+            value_type,
+            range: start..start,
+            children: Vec::new(),
+        });
+        self.entries.len() - 1
+    }
+
+    fn scan(&mut self, scanner: &mut Scanner, value_type: TDFToken, label: Option<String>, key: Option<(TDFToken, Range<usize>)>) -> Result<usize> {
+
+        let row = self.push(label.clone(), key, value_type.clone(), scanner.pos);
+        let mut children = Vec::new();
+
+        match &value_type {
+            TDFToken::MapType => {
+                while let Some(field) = scanner.next_field()? {
+                    children.push(self.scan(scanner, field.value_type, Some(field.label.to_string()), None)?);
+                }
+            },
+            TDFToken::ListType => {
+                let (item_type, size) = scanner.read_list_header()?;
+                for _ in 0..size {
+                    children.push(self.scan(scanner, item_type.clone(), None, None)?);
+                }
+            },
+            TDFToken::IntListType => {
+                let size = scanner.read_size()?;
+                for _ in 0..size {
+                    children.push(self.scan(scanner, TDFToken::IntType, None, None)?);
+                }
+            },
+            TDFToken::PairListType => {
+                let (key_type, item_type, size) = scanner.read_pair_list_header(label.as_deref())?;
+                for _ in 0..size {
+                    let key_start = scanner.pos;
+                    scanner.skip_value(&key_type, None)?;
+                    let key = Some((key_type.clone(), key_start..scanner.pos));
+                    children.push(self.scan(scanner, item_type.clone(), None, key)?);
+                }
+            },
+            TDFToken::UnionType => {
+                if UnionType::from_tag(scanner.read_u8()?) != UnionType::Unset {
+                    children.push(self.scan_member(scanner)?);
+                }
+            },
+            TDFToken::GenericType => {
+                if scanner.read_u8()? != 0 {
+                    scanner.read_number()?;
+                    if scanner.peek_u8() == Some(0) {
+                        scanner.read_u8()?;
+                    } else {
+                        children.push(self.scan_member(scanner)?);
+                        scanner.read_u8()?;
+                    }
+                }
+            },
+            _ => scanner.skip_value(&value_type, label.as_deref())?,
+        }
+
+        let entry = &mut self.entries[row];
+        entry.range.end = scanner.pos;
+        entry.children = children;
+        Ok(row)
+    }
+
+    fn scan_member(&mut self, scanner: &mut Scanner) -> Result<usize> {
+        let member = scanner.read_label()?;
+        let member_type = scanner.read_type()?;
+        self.scan(scanner, member_type, Some(member.to_string()), None)
+    }
+}
+
+impl<'i, 'a> IndexNode<'i, 'a> {
+
+    pub fn entry(&self) -> &'i IndexEntry {
+        &self.index.entries[self.row]
+    }
+
+    pub fn label(&self) -> Option<&'i str> {
+        self.entry().label.as_deref()
+    }
+
+    pub fn value_type(&self) -> &'i TDFToken {
+        &self.entry().value_type
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.entry().range.clone()
+    }
+
+    /// Encoded value, can be decoded on its own
+    pub fn bytes(&self) -> &'a [u8] {
+        &self.index.buf[self.range()]
+    }
+
+    pub fn child_count(&self) -> usize {
+        self.entry().children.len()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = IndexNode<'i, 'a>> + '_ {
+        let index = self.index;
+        self.entry().children.iter().map(move |row| IndexNode { index, row: *row })
+    }
+
+    /// Key of pair list entry
+    pub fn key(&self) -> Result<Option<TdfValue>> {
+        match &self.entry().key {
+            Some((key_type, range)) => Ok(Some(decode_value(&self.index.buf[range.clone()], key_type.clone(), None)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Map field or union member by label
+    pub fn get(&self, label: &str) -> Option<IndexNode<'i, 'a>> {
+        let label = normalize_label(label);
+        self.children().find(|child| child.label().map(normalize_label).as_ref() == Some(&label))
+    }
+
+    /// List element by position
+    pub fn at(&self, index: usize) -> Option<IndexNode<'i, 'a>> {
+        match self.value_type() {
+            TDFToken::ListType | TDFToken::IntListType => self.children().nth(index),
+            _ => None,
+        }
+    }
+
+    /// Child addressed by path segment
+    pub fn child(&self, segment: &PathSegment) -> Result<Option<IndexNode<'i, 'a>>> {
+        Ok(match (self.value_type(), segment) {
+            (_, PathSegment::Label(label)) => self.get(label),
+            (TDFToken::PairListType, PathSegment::Index(wanted)) => {
+                let wanted = TdfValue::Int(*wanted);
+                self.find_key(&wanted)?
+            },
+            (TDFToken::PairListType, PathSegment::Key(wanted)) => {
+                let wanted = TdfValue::from(wanted.as_str());
+                self.find_key(&wanted)?
+            },
+            (_, PathSegment::Index(index)) if *index >= 0 => self.at(*index as usize),
+            _ => None,
+        })
+    }
+
+    fn find_key(&self, wanted: &TdfValue) -> Result<Option<IndexNode<'i, 'a>>> {
+        for child in self.children() {
+            if child.key()?.as_ref() == Some(wanted) {
+                return Ok(Some(child));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decode the value
+    pub fn value(&self) -> Result<TdfValue> {
+        decode_value(self.bytes(), self.value_type().clone(), self.label())
+    }
+
+    /// Decode map into the struct
+    pub fn decode<T: Serialize>(&self) -> Result<T> {
+        if *self.value_type() != TDFToken::MapType {
+            bail!("Only maps can be decoded into struct, found {:?}", self.value_type());
+        }
+        crate::bin_to_struct(&mut Cursor::new(self.bytes()))
+    }
+}
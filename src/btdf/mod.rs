@@ -8,4 +8,19 @@ mod ser;
 pub use ser::*;
 
 mod des;
-pub use des::*;
\ No newline at end of file
+pub use des::*;
+
+mod scan;
+pub use scan::*;
+
+mod splice;
+pub use splice::*;
+
+mod project;
+pub use project::*;
+
+mod index;
+pub use index::*;
+
+mod dump;
+pub use dump::*;
@@ -0,0 +1,192 @@
+/*
+    Projection decoding of TDF binary
+
+    Only values at selected paths are decoded,
+    everything else is skipped by sizes and counts without allocations.
+    Paths of children are only built when they lead to a selected value.
+*/
+
+use crate::token::*;
+use crate::value::{PathSegment, QueryMatch, TdfPath, TdfValue, ValueSerializer, join, label_step, key_step};
+use anyhow::{Result, bail};
+use peekread::SeekPeekReader;
+use std::io::Cursor;
+
+use super::{BTDFDeserializer, RawLabel, Scanner};
+
+
+/// Decode single value of given type, label of the field is used for EA bug fix
+pub fn decode_value(buf: &[u8], value_type: TDFToken, label: Option<&str>) -> Result<TdfValue> {
+
+    let mut cursor = Cursor::new(buf);
+    let mut reader = SeekPeekReader::new(&mut cursor);
+
+    let mut des = BTDFDeserializer::new();
+    des.stream.push(TDFToken::Label(label.unwrap_or_default().to_string()));
+    des.stream.push(value_type.clone());
+    des.des_token(&mut reader, value_type, false)?;
+
+    // Label was needed only by pair list
+    des.stream.0.remove(0);
+
+    let mut value = TdfValue::map();
+    ValueSerializer::serialize(des.stream, &mut value)?;
+    Ok(value)
+}
+
+/// Decode values at given concrete paths, like GAME.PROS[2].PNAM, skipping the rest.
+/// Values are returned in order of appearance in the buffer
+pub fn project(buf: &[u8], paths: &[&str]) -> Result<Vec<QueryMatch>> {
+
+    let mut parsed = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = TdfPath::parse(path)?;
+        if !path.is_concrete() || path.0.is_empty() {
+            bail!("Projection path {:?} must be concrete and not empty", path.to_string());
+        }
+        parsed.push(path);
+    }
+
+    let selected: Vec<&[PathSegment]> = parsed.iter().map(|p| p.0.as_slice()).collect();
+
+    let mut out = Vec::new();
+    let mut scanner = Scanner::new(buf);
+    walk(&mut scanner, &TDFToken::MapType, None, &selected, "", &mut out)?;
+    Ok(out)
+}
+
+/// Rest of the paths going through the child
+fn descend<'a>(selected: &[&'a [PathSegment]], matches: impl Fn(&PathSegment) -> bool) -> Vec<&'a [PathSegment]> {
+    selected.iter()
+        .filter_map(|segments| segments.split_first())
+        .filter(|(first, _)| matches(first))
+        .map(|(_, rest)| rest)
+        .collect()
+}
+
+fn label_matches(segment: &PathSegment, label: &RawLabel) -> bool {
+    match segment {
+        PathSegment::Label(wanted) => label.matches(wanted),
+        _ => false,
+    }
+}
+
+fn walk(scanner: &mut Scanner, value_type: &TDFToken, label: Option<&str>, selected: &[&[PathSegment]], path: &str, out: &mut Vec<QueryMatch>) -> Result<()> {
+
+    if selected.is_empty() {
+        return scanner.skip_value(value_type, label);
+    }
+
+    // Value itself is selected, nested selections are taken from decoded one
+    if selected.iter().any(|segments| segments.is_empty()) {
+        let start = scanner.pos;
+        scanner.skip_value(value_type, label)?;
+        let value = decode_value(&scanner.buf()[start..scanner.pos], value_type.clone(), label)?;
+        let nested: Vec<QueryMatch> = selected.iter()
+            .filter(|segments| !segments.is_empty())
+            .flat_map(|segments| TdfPath(segments.to_vec()).evaluate(&value))
+            .collect();
+        out.push(QueryMatch { path: path.to_string(), value });
+        for found in nested {
+            let step = if found.path.starts_with('[') { found.path } else { format!(".{}", found.path) };
+            out.push(QueryMatch { path: join(path, &step), value: found.value });
+        }
+        return Ok(());
+    }
+
+    match value_type {
+        TDFToken::MapType => {
+            while let Some(field) = scanner.next_field()? {
+                let rest = descend(selected, |s| label_matches(s, &field.label));
+                if rest.is_empty() {
+                    scanner.skip_value(&field.value_type, Some(field.label.as_str()))?;
+                    continue;
+                }
+                let field_path = join(path, &label_step(field.label.as_str()));
+                walk(scanner, &field.value_type, Some(field.label.as_str()), &rest, &field_path, out)?;
+            }
+        },
+        TDFToken::ListType => {
+            let (item_type, size) = scanner.read_list_header()?;
+            for i in 0..size {
+                let rest = descend(selected, |s| *s == PathSegment::Index(i as i64));
+                if rest.is_empty() {
+                    scanner.skip_value(&item_type, None)?;
+                    continue;
+                }
+                walk(scanner, &item_type, None, &rest, &join(path, &format!("[{}]", i)), out)?;
+            }
+        },
+        TDFToken::IntListType => {
+            let size = scanner.read_size()?;
+            for i in 0..size {
+                let rest = descend(selected, |s| *s == PathSegment::Index(i as i64));
+                if rest.is_empty() {
+                    scanner.read_number()?;
+                    continue;
+                }
+                walk(scanner, &TDFToken::IntType, None, &rest, &join(path, &format!("[{}]", i)), out)?;
+            }
+        },
+        TDFToken::PairListType => {
+            let (key_type, item_type, size) = scanner.read_pair_list_header(label)?;
+            for _ in 0..size {
+                let key_start = scanner.pos;
+                let rest = match key_type {
+                    TDFToken::IntType => {
+                        let key = scanner.read_number()?;
+                        descend(selected, |s| *s == PathSegment::Index(key))
+                    },
+                    TDFToken::StringType => {
+                        let key = scanner.read_string()?;
+                        descend(selected, |s| matches!(s, PathSegment::Key(wanted) if wanted.as_bytes() == key))
+                    },
+                    _ => {
+                        scanner.skip_value(&key_type, None)?;
+                        vec![]
+                    }
+                };
+                if rest.is_empty() {
+                    scanner.skip_value(&item_type, None)?;
+                    continue;
+                }
+                // Key is read again only for the path of selected value
+                let key = match key_type {
+                    TDFToken::IntType => TdfValue::Int(Scanner::at(scanner.buf(), key_start).read_number()?),
+                    _ => TdfValue::String(Scanner::at(scanner.buf(), key_start).read_string()?.to_vec()),
+                };
+                let step = key_step(&key).unwrap_or_default();
+                walk(scanner, &item_type, None, &rest, &join(path, &step), out)?;
+            }
+        },
+        TDFToken::UnionType => {
+            if UnionType::from_tag(scanner.read_u8()?) != UnionType::Unset {
+                walk_member(scanner, selected, path, out)?;
+            }
+        },
+        TDFToken::GenericType => {
+            if scanner.read_u8()? != 0 {
+                scanner.read_number()?;
+                if scanner.peek_u8() == Some(0) {
+                    scanner.read_u8()?;
+                } else {
+                    walk_member(scanner, selected, path, out)?;
+                    scanner.read_u8()?;
+                }
+            }
+        },
+        _ => scanner.skip_value(value_type, label)?,
+    }
+
+    Ok(())
+}
+
+fn walk_member(scanner: &mut Scanner, selected: &[&[PathSegment]], path: &str, out: &mut Vec<QueryMatch>) -> Result<()> {
+    let member = scanner.read_label()?;
+    let member_type = scanner.read_type()?;
+    let rest = descend(selected, |s| label_matches(s, &member));
+    if rest.is_empty() {
+        return scanner.skip_value(&member_type, Some(member.as_str()));
+    }
+    walk(scanner, &member_type, Some(member.as_str()), &rest, &join(path, &label_step(member.as_str())), out)
+}
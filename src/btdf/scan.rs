@@ -0,0 +1,427 @@
+/*
+    Byte level scanner of TDF binary
+
+    Walks encoded buffer by the same rules as BTDFDeserializer,
+    but without producing tokens, so values can be located and skipped
+    without allocations.
+*/
+
+use crate::token::*;
+use crate::value::{PathSegment, TdfPath};
+use anyhow::{Result, bail};
+use std::fmt;
+use std::ops::Range;
+
+use super::des::{VARSIZE_MORE, VARSIZE_NEGATIVE, decode_label_bytes, pair_list_value_override};
+
+
+/// Label decoded in place, decoded labels are always ASCII
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawLabel([u8; 4]);
+
+impl RawLabel {
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+
+    /// Same label as normalized one, ignoring case and _ for space
+    pub fn matches(&self, label: &str) -> bool {
+        let normalize = |c: char| if c == '_' { ' ' } else { c.to_ascii_uppercase() };
+        let label = label.chars().chain(std::iter::repeat(' ')).take(4).map(normalize);
+        self.as_str().chars().map(normalize).eq(label)
+    }
+}
+
+impl fmt::Display for RawLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Header of map field
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldHeader {
+    /// Offset of the field, including union marker
+    pub start: usize,
+    /// Offset of the 3 label bytes
+    pub label_start: usize,
+    pub label: RawLabel,
+    pub value_type: TDFToken,
+    /// Offset of value bytes, right after type byte
+    pub value_start: usize,
+}
+
+/// Location of encoded value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueSpan {
+    pub value_type: TDFToken,
+    /// Label of the field or member holding the value
+    pub label: Option<String>,
+    /// Value bytes, without label and type byte
+    pub range: Range<usize>,
+}
+
+pub struct Scanner<'a> {
+    buf: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+        }
+    }
+
+    /// Scanner starting at given offset
+    pub fn at(buf: &'a [u8], pos: usize) -> Self {
+        Self {
+            buf,
+            pos,
+        }
+    }
+
+    pub fn buf(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn peek_u8(&self) -> Option<u8> {
+        self.buf.get(self.pos).copied()
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        match self.buf.get(self.pos) {
+            Some(b) => {
+                self.pos += 1;
+                Ok(*b)
+            },
+            None => bail!("Unexpected end of buffer at offset {}", self.pos),
+        }
+    }
+
+    /// Read given amount of bytes without copying
+    pub fn read_bytes(&mut self, size: usize) -> Result<&'a [u8]> {
+        if size > self.buf.len() - self.pos.min(self.buf.len()) {
+            bail!("Attempt to read {} bytes at offset {} outside of buffer bounds", size, self.pos);
+        }
+        let bytes = &self.buf[self.pos..self.pos + size];
+        self.pos += size;
+        Ok(bytes)
+    }
+
+    pub fn read_number(&mut self) -> Result<i64> {
+
+        let mut b = self.read_u8()?;
+
+        let is_negative = (b & VARSIZE_NEGATIVE) != 0;
+        let mut value = (b as u64) & (VARSIZE_NEGATIVE - 1) as u64;
+
+        let mut shift = 6;
+        let mut more = (b & VARSIZE_MORE) != 0;
+
+        while more {
+            b = self.read_u8()?;
+            if shift < 64 {
+                value |= ((b as u64) & ((VARSIZE_MORE - 1) as u64)) << shift;
+            }
+            more = (b & VARSIZE_MORE) != 0;
+            shift += 7;
+        }
+
+        let mut value = value as i64;
+
+        if is_negative {
+            if value != 0 {
+                value = -value;
+            } else {
+                value = i64::MIN;
+            }
+        }
+
+        Ok(value)
+    }
+
+    pub fn read_label(&mut self) -> Result<RawLabel> {
+        let bytes = self.read_bytes(3)?;
+        Ok(RawLabel(decode_label_bytes(&[bytes[0], bytes[1], bytes[2]])))
+    }
+
+    pub fn read_type(&mut self) -> Result<TDFToken> {
+        let tag = self.read_u8()?;
+        TDFToken::from_tag(tag)
+    }
+
+    /// String bytes, without terminator
+    pub fn read_string(&mut self) -> Result<&'a [u8]> {
+        let size = self.read_number()?;
+        if size == 0 {
+            return Ok(&[]);
+        }
+        if size < 0 {
+            // Read till terminator
+            let start = self.pos;
+            while self.read_u8()? != 0 {}
+            return Ok(&self.buf[start..self.pos - 1]);
+        }
+        let bytes = self.read_bytes((size - 1) as usize)?;
+        self.read_u8()?;
+        Ok(bytes)
+    }
+
+    pub fn read_blob(&mut self) -> Result<&'a [u8]> {
+        let size = self.read_number()?;
+        if size < 0 {
+            bail!("Negative blob size at offset {}", self.pos);
+        }
+        self.read_bytes(size as usize)
+    }
+
+    /// Element type and size of list
+    pub fn read_list_header(&mut self) -> Result<(TDFToken, usize)> {
+        let item_type = self.read_type()?;
+        let size = self.read_size()?;
+        Ok((item_type, size))
+    }
+
+    /// Key type, value type and size of pair list, field label is used for EA bug fix
+    pub fn read_pair_list_header(&mut self, label: Option<&str>) -> Result<(TDFToken, TDFToken, usize)> {
+        let key_type = self.read_type()?;
+        let mut value_type = self.read_type()?;
+        if let Some(value_override) = label.and_then(pair_list_value_override) {
+            value_type = value_override;
+        }
+        let size = self.read_size()?;
+        Ok((key_type, value_type, size))
+    }
+
+    pub fn read_size(&mut self) -> Result<usize> {
+        let size = self.read_number()?;
+        if size < 0 {
+            bail!("Negative size at offset {}", self.pos);
+        }
+        Ok(size as usize)
+    }
+
+    /// Next field of the map, None if map ended.
+    /// Map ends with zero byte, or with the end of buffer for root map
+    pub fn next_field(&mut self) -> Result<Option<FieldHeader>> {
+
+        let start = self.pos;
+
+        match self.peek_u8() {
+            None => return Ok(None),
+            Some(0) => {
+                self.pos += 1;
+                return Ok(None);
+            },
+            Some(b) if b <= 2 => {
+                // Union map marker
+                self.pos += 1;
+            },
+            _ => {},
+        }
+
+        let label_start = self.pos;
+        let label = self.read_label()?;
+        let value_type = self.read_type()?;
+
+        Ok(Some(FieldHeader {
+            start,
+            label_start,
+            label,
+            value_type,
+            value_start: self.pos,
+        }))
+    }
+
+    /// Skip value of the type, label of the field is used for EA bug fix
+    pub fn skip_value(&mut self, value_type: &TDFToken, label: Option<&str>) -> Result<()> {
+        match value_type {
+            TDFToken::IntType | TDFToken::TimeType => {
+                self.read_number()?;
+            },
+            TDFToken::StringType => {
+                self.read_string()?;
+            },
+            TDFToken::BlobType => {
+                self.read_blob()?;
+            },
+            TDFToken::MapType => {
+                while let Some(field) = self.next_field()? {
+                    self.skip_value(&field.value_type, Some(field.label.as_str()))?;
+                }
+            },
+            TDFToken::ListType => {
+                let (item_type, size) = self.read_list_header()?;
+                for _ in 0..size {
+                    self.skip_value(&item_type, None)?;
+                }
+            },
+            TDFToken::PairListType => {
+                let (key_type, item_type, size) = self.read_pair_list_header(label)?;
+                for _ in 0..size {
+                    self.skip_value(&key_type, None)?;
+                    self.skip_value(&item_type, None)?;
+                }
+            },
+            TDFToken::UnionType => {
+                if UnionType::from_tag(self.read_u8()?) != UnionType::Unset {
+                    let member = self.read_label()?;
+                    let member_type = self.read_type()?;
+                    self.skip_value(&member_type, Some(member.as_str()))?;
+                }
+            },
+            TDFToken::IntListType => {
+                let size = self.read_size()?;
+                for _ in 0..size {
+                    self.read_number()?;
+                }
+            },
+            TDFToken::ObjectTypeType => {
+                self.read_number()?;
+                self.read_number()?;
+            },
+            TDFToken::ObjectIdType => {
+                self.read_number()?;
+                self.read_number()?;
+                self.read_number()?;
+            },
+            TDFToken::FloatType => {
+                self.read_bytes(4)?;
+            },
+            TDFToken::GenericType => {
+                if self.read_u8()? != 0 {
+                    self.read_number()?;
+                    if self.peek_u8() == Some(0) {
+                        self.pos += 1;
+                    } else {
+                        let member = self.read_label()?;
+                        let member_type = self.read_type()?;
+                        self.skip_value(&member_type, Some(member.as_str()))?;
+                        self.read_u8()?;
+                    }
+                }
+            },
+            _ => bail!("Expected type token, found {:?}", value_type),
+        }
+        Ok(())
+    }
+
+    /// Move into the child of value of given type, addressed by path segment.
+    /// Returns type of the child and its label, if it has one
+    pub fn enter(&mut self, value_type: &TDFToken, label: Option<&str>, segment: &PathSegment) -> Result<Option<(TDFToken, Option<String>)>> {
+        match (value_type, segment) {
+            (TDFToken::MapType, PathSegment::Label(wanted)) => {
+                while let Some(field) = self.next_field()? {
+                    if field.label.matches(wanted) {
+                        return Ok(Some((field.value_type, Some(field.label.to_string()))));
+                    }
+                    self.skip_value(&field.value_type, Some(field.label.as_str()))?;
+                }
+                Ok(None)
+            },
+            (TDFToken::ListType, PathSegment::Index(index)) => {
+                let (item_type, size) = self.read_list_header()?;
+                if *index < 0 || *index as usize >= size {
+                    return Ok(None);
+                }
+                for _ in 0..*index {
+                    self.skip_value(&item_type, None)?;
+                }
+                Ok(Some((item_type, None)))
+            },
+            (TDFToken::IntListType, PathSegment::Index(index)) => {
+                let size = self.read_size()?;
+                if *index < 0 || *index as usize >= size {
+                    return Ok(None);
+                }
+                for _ in 0..*index {
+                    self.read_number()?;
+                }
+                Ok(Some((TDFToken::IntType, None)))
+            },
+            (TDFToken::PairListType, PathSegment::Index(_)) | (TDFToken::PairListType, PathSegment::Key(_)) => {
+                let (key_type, item_type, size) = self.read_pair_list_header(label)?;
+                for _ in 0..size {
+                    let found = match (&key_type, segment) {
+                        (TDFToken::IntType, PathSegment::Index(wanted)) => self.read_number()? == *wanted,
+                        (TDFToken::StringType, PathSegment::Key(wanted)) => self.read_string()? == wanted.as_bytes(),
+                        _ => {
+                            self.skip_value(&key_type, None)?;
+                            false
+                        }
+                    };
+                    if found {
+                        return Ok(Some((item_type, None)));
+                    }
+                    self.skip_value(&item_type, None)?;
+                }
+                Ok(None)
+            },
+            (TDFToken::UnionType, PathSegment::Label(wanted)) => {
+                if UnionType::from_tag(self.read_u8()?) == UnionType::Unset {
+                    return Ok(None);
+                }
+                self.enter_member(wanted)
+            },
+            (TDFToken::GenericType, PathSegment::Label(wanted)) => {
+                if self.read_u8()? == 0 {
+                    return Ok(None);
+                }
+                self.read_number()?;
+                if self.peek_u8() == Some(0) {
+                    return Ok(None);
+                }
+                self.enter_member(wanted)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn enter_member(&mut self, wanted: &str) -> Result<Option<(TDFToken, Option<String>)>> {
+        let member = self.read_label()?;
+        let member_type = self.read_type()?;
+        if !member.matches(wanted) {
+            return Ok(None);
+        }
+        Ok(Some((member_type, Some(member.to_string()))))
+    }
+}
+
+/// Find encoded value at concrete path inside of root map
+pub fn locate(buf: &[u8], path: &str) -> Result<Option<ValueSpan>> {
+
+    let path = TdfPath::parse(path)?;
+    if !path.is_concrete() {
+        bail!("Path {} must not contain wildcards", path);
+    }
+
+    let mut scanner = Scanner::new(buf);
+    let mut value_type = TDFToken::MapType;
+    let mut label: Option<String> = None;
+
+    for segment in &path.0 {
+        match scanner.enter(&value_type, label.as_deref(), segment)? {
+            Some((child_type, child_label)) => {
+                value_type = child_type;
+                label = child_label;
+            },
+            None => return Ok(None),
+        }
+    }
+
+    let start = scanner.pos;
+    scanner.skip_value(&value_type, label.as_deref())?;
+
+    Ok(Some(ValueSpan {
+        value_type,
+        label,
+        range: start..scanner.pos,
+    }))
+}
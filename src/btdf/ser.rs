@@ -31,11 +31,18 @@ impl BTDFSerializer {
             TDFToken::ObjectTypeType => self.ser_object_type(writer),
             TDFToken::ObjectIdType   => self.ser_object_id(writer),
             TDFToken::FloatType      => self.ser_float(writer),
+            TDFToken::TimeType       => self.ser_int(writer),
             TDFToken::GenericType    => self.ser_generic(writer),
             _ => bail!("Trying to parse type token, but found {:?}", token_type)
         }
     }
 
+    /// Serialize the next typed value of the stream as nested one, maps get terminated
+    pub fn ser_nested(&mut self, writer: &mut dyn Write) -> Result<()> {
+        let token = self.stream.next()?;
+        self.ser_token(writer, token, false)
+    }
+
     pub fn ser_int(&mut self, writer: &mut dyn Write) -> Result<()> {
         let token =  self.stream.next()?;
         match token {
@@ -347,15 +354,7 @@ impl BTDFSerializer {
 
     pub fn write_label(&self, writer: &mut dyn Write, label: &String) -> Result<()> {
 
-        let mut label_string = label.to_uppercase().replace("_", " ");
-
-        if label_string.len() < 4 {
-            for _ in label_string.len()..4 {   
-                label_string.push_str(" ");
-            }
-        } else if label_string.len() > 4 {
-            label_string = label_string.drain(..4).collect();
-        }
+        let label_string = normalize_label(label);
 
         let c = label_string.as_bytes();
 
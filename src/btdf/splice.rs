@@ -0,0 +1,56 @@
+/*
+    In-place replacement of a single value inside of TDF binary
+
+    Value is located by scanning, only its bytes are replaced,
+    everything before and after stays untouched, including unknown fields.
+*/
+
+use crate::token::*;
+use crate::value::TdfValue;
+use anyhow::{Result, bail};
+
+use super::{BTDFSerializer, Scanner, locate};
+
+
+/// Replace value at concrete path, like GAME.PROS[2].PNAM, with the new one of the same type.
+/// Returns the new length of the buffer
+pub fn splice_field(buf: &mut Vec<u8>, path: &str, value: &TdfValue) -> Result<usize> {
+
+    if path.trim().is_empty() {
+        bail!("Unable to splice the root map, path must not be empty");
+    }
+
+    let span = match locate(buf, path)? {
+        Some(span) => span,
+        None => bail!("Value {} doesn't exist", path),
+    };
+
+    if span.value_type != value.type_token() {
+        bail!("Type mismatch at {}: expected {:?}, found {:?}", path, span.value_type, value.type_token());
+    }
+
+    // Containers must keep their element types
+    let mut scanner = Scanner::at(buf, span.range.start);
+    match (&span.value_type, value) {
+        (TDFToken::ListType, TdfValue::List(item_type, _)) => {
+            let (old_type, _) = scanner.read_list_header()?;
+            if old_type != *item_type {
+                bail!("List type mismatch at {}: expected {:?}, found {:?}", path, old_type, item_type);
+            }
+        },
+        (TDFToken::PairListType, TdfValue::PairList(key_type, value_type, _)) => {
+            let (old_key, old_value, _) = scanner.read_pair_list_header(span.label.as_deref())?;
+            if old_key != *key_type || old_value != *value_type {
+                bail!("Pair list type mismatch at {}: expected {:?} to {:?}, found {:?} to {:?}", path, old_key, old_value, key_type, value_type);
+            }
+        },
+        _ => {},
+    }
+
+    let mut encoded = Vec::new();
+    BTDFSerializer::new(value.to_stream()?).ser_nested(&mut encoded)?;
+
+    buf.splice(span.range, encoded);
+
+    Ok(buf.len())
+}
@@ -0,0 +1,64 @@
+use crate::token::*;
+use crate::value::{TdfValue, Tagged};
+use anyhow::{Result, bail};
+use ciborium::value::Value;
+use std::convert::TryFrom;
+
+use super::TAG_BASE;
+
+
+/// Reads CBOR into token stream
+pub struct CborDeserializer {
+    pub stream: TDFTokenStream,
+    input: Vec<u8>,
+}
+
+fn from_cbor(value: Value) -> Result<Tagged> {
+    Ok(match value {
+        Value::Integer(n) => {
+            let n = i128::from(n);
+            match i64::try_from(n) {
+                Ok(v) => Tagged::Int(v),
+                Err(_) => bail!("Integer {} doesn't fit into 64 bits", n),
+            }
+        },
+        Value::Bool(v) => Tagged::Bool(v),
+        Value::Float(v) => Tagged::Float(v as f32),
+        Value::Text(v) => Tagged::String(v),
+        Value::Bytes(v) => Tagged::Bytes(v),
+        Value::Array(items) => Tagged::Array(items.into_iter().map(from_cbor).collect::<Result<_>>()?),
+        Value::Map(entries) => Tagged::Map(entries.into_iter()
+            .map(|(k, v)| Ok((from_cbor(k)?, from_cbor(v)?)))
+            .collect::<Result<_>>()?),
+        Value::Tag(tag, value) => match tag.checked_sub(TAG_BASE).and_then(|t| u8::try_from(t).ok()) {
+            Some(tag) => Tagged::Tag(tag, Box::new(from_cbor(*value)?)),
+            None => bail!("Unknown tag {}", tag),
+        },
+        value => bail!("Unexpected CBOR value {:?}", value),
+    })
+}
+
+impl CborDeserializer {
+
+    pub fn new(input: &[u8]) -> Self {
+        Self {
+            stream: TDFTokenStream::new(),
+            input: input.to_vec(),
+        }
+    }
+
+    /// Parse whole input into the stream
+    pub fn des_cbor(&mut self) -> Result<()> {
+        let value: Value = ciborium::de::from_reader(self.input.as_slice())?;
+        self.stream = TdfValue::tagged_to_stream(from_cbor(value)?)?;
+        Ok(())
+    }
+}
+
+impl TDFDeserializer<Vec<u8>> for CborDeserializer {
+    fn deserialize(reader: &mut Vec<u8>) -> Result<TDFTokenStream> {
+        let mut des = Self::new(reader);
+        des.des_cbor()?;
+        Ok(des.stream)
+    }
+}
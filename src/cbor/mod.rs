@@ -0,0 +1,28 @@
+/*
+    CBOR form of TDF, enabled by "cbor" feature
+
+    Int, String, Float, Map and List are plain CBOR values, Blob is byte
+    string and labels are written trimmed. Other types are tagged with
+    TAG_BASE + TDF type byte, content is the same as in MessagePack form:
+
+    PairList    0x05  map of pairs
+    Union       0x06  [member, label, value] or [member] when unset
+    IntList     0x07  [ints]
+    ObjectType  0x08  [a, b]
+    ObjectId    0x09  [a, b, c]
+    Time        0x0B  int
+    Generic     0x0C  [id, label, value], [id] or [] when invalid
+    String      0x01  byte string, for strings that aren't UTF-8
+    Map         0x03  map that starts with union marker
+
+    Types of empty List and PairList are not kept and are read back as Int
+*/
+
+mod ser;
+pub use ser::*;
+
+mod des;
+pub use des::*;
+
+/// First tag of TDF types, "TDF\0" in ASCII
+pub const TAG_BASE: u64 = 0x5444_4600;
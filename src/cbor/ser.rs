@@ -0,0 +1,48 @@
+use crate::token::*;
+use crate::value::{TdfValue, Tagged};
+use anyhow::Result;
+use ciborium::value::Value;
+
+use super::TAG_BASE;
+
+
+/// Writes token stream as CBOR
+pub struct CborSerializer {
+    stream: TDFTokenStream,
+}
+
+fn to_cbor(value: Tagged) -> Value {
+    match value {
+        Tagged::Int(v) => Value::from(v),
+        Tagged::Bool(v) => Value::Bool(v),
+        Tagged::Float(v) => Value::Float(v as f64),
+        Tagged::String(v) => Value::Text(v),
+        Tagged::Bytes(v) => Value::Bytes(v),
+        Tagged::Array(items) => Value::Array(items.into_iter().map(to_cbor).collect()),
+        Tagged::Map(entries) => Value::Map(entries.into_iter().map(|(k, v)| (to_cbor(k), to_cbor(v))).collect()),
+        Tagged::Tag(tag, value) => Value::Tag(TAG_BASE + tag as u64, Box::new(to_cbor(*value))),
+    }
+}
+
+impl CborSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream
+        }
+    }
+
+    /// Write whole stream as CBOR into the writer
+    pub fn write_cbor(&mut self, writer: &mut Vec<u8>) -> Result<()> {
+        let stream = std::mem::replace(&mut self.stream, TDFTokenStream::new());
+        let value = to_cbor(TdfValue::stream_to_tagged(stream)?);
+        ciborium::ser::into_writer(&value, writer)?;
+        Ok(())
+    }
+}
+
+impl TDFSerializer<Vec<u8>> for CborSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut Vec<u8>) -> Result<()> {
+        Self::new(stream).write_cbor(writer)
+    }
+}
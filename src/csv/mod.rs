@@ -0,0 +1,21 @@
+/*
+    CSV table of a list of maps inside a message
+
+    PROS = list(Map) [
+        { PNAM = "a", STAT = { KILL = 3 } }
+        { PNAM = "b", TEAM = 1 }
+    ]
+
+    with path "PROS" is written as
+
+    pnam,stat.kill,team
+    a,3,
+    b,,1
+
+    One row per element, nested labels are dotted column names and columns
+    are the union of all rows in order of appearance. Pair lists are flattened
+    by their keys, rendering of blobs, lists and unions is chosen by CsvOptions
+*/
+
+mod ser;
+pub use ser::*;
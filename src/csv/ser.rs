@@ -0,0 +1,226 @@
+use crate::token::*;
+use crate::value::{TdfValue, TdfGeneric, TdfPath, ValueSerializer, join};
+use crate::json::{LabelCase, base64};
+use crate::hex::hex;
+use anyhow::{Result, bail};
+
+
+/// How blobs are written in cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvBlob {
+    Hex,
+    Base64,
+    /// Only size in bytes
+    Size,
+}
+
+/// How lists and int lists are written
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvList {
+    /// One cell with items joined by separator
+    Join(String),
+    /// One cell with number of items
+    Count,
+    /// Column per item, named by index like pros.0.pnam
+    Columns,
+}
+
+/// How unions are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvUnion {
+    /// Member flattened under its label like addr.valu.port
+    Member,
+    /// One cell with union type, 127 when unset
+    Type,
+    /// Union type cell and flattened member
+    TypeAndMember,
+}
+
+/// Layout of CSV table
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    /// Write row with column names
+    pub header: bool,
+    pub label_case: LabelCase,
+    pub blob: CsvBlob,
+    pub list: CsvList,
+    pub union: CsvUnion,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: true,
+            label_case: LabelCase::Lower,
+            blob: CsvBlob::Hex,
+            list: CsvList::Join(";".to_string()),
+            union: CsvUnion::Member,
+        }
+    }
+}
+
+/// Writes list of maps at label path as CSV table
+pub struct CsvSerializer {
+    stream: TDFTokenStream,
+    path: String,
+    options: CsvOptions,
+}
+
+impl CsvSerializer {
+
+    /// Serializer of rows at path like "PROS" or "GAME.PROS"
+    pub fn new<S: Into<String>>(stream: TDFTokenStream, path: S) -> Self {
+        Self {
+            stream,
+            path: path.into(),
+            options: CsvOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CsvOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Write the table into the writer, fails when two values of a row
+    /// flatten to the same column
+    pub fn write_csv(&mut self, writer: &mut String) -> Result<()> {
+        let stream = std::mem::replace(&mut self.stream, TDFTokenStream::new());
+        let mut root = TdfValue::map();
+        ValueSerializer::serialize(stream, &mut root)?;
+
+        let path = TdfPath::parse(&self.path)?;
+        let items = match path.get(&root) {
+            Some(TdfValue::List(_, items)) => items,
+            Some(value) => bail!("Expected List at {}, found {:?}", self.path, value.type_token()),
+            None => bail!("Nothing found at {}", self.path),
+        };
+
+        let mut columns: Vec<String> = Vec::new();
+        let mut rows = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            if !matches!(item, TdfValue::Map(_)) {
+                bail!("Expected List of Maps at {}, found {:?}", self.path, item.type_token());
+            }
+            let mut row = Vec::new();
+            self.flatten("", item, &mut row);
+            for (j, (column, _)) in row.iter().enumerate() {
+                if row[..j].iter().any(|(c, _)| c == column) {
+                    bail!("Column {} appears twice in row {} at {}", column, i, self.path);
+                }
+                if !columns.contains(column) {
+                    columns.push(column.clone());
+                }
+            }
+            rows.push(row);
+        }
+
+        if self.options.header {
+            self.write_row(writer, columns.iter().map(|c| c.as_str()));
+        }
+        for row in &rows {
+            let cells = columns.iter().map(|column| {
+                row.iter().find(|(c, _)| c == column).map(|(_, cell)| cell.as_str()).unwrap_or("")
+            });
+            self.write_row(writer, cells);
+        }
+        Ok(())
+    }
+
+    fn write_row<'a, I: Iterator<Item = &'a str>>(&self, writer: &mut String, cells: I) {
+        for (i, cell) in cells.enumerate() {
+            if i != 0 {
+                writer.push(self.options.delimiter);
+            }
+            writer.push_str(&self.quote(cell));
+        }
+        writer.push('\n');
+    }
+
+    /// Cell quoted when it has delimiter, quotes, line breaks or edge spaces
+    fn quote(&self, cell: &str) -> String {
+        let needs_quotes = cell.contains([self.options.delimiter, '"', '\n', '\r'])
+            || cell.starts_with(' ') || cell.ends_with(' ');
+        if needs_quotes {
+            format!("\"{}\"", cell.replace('"', "\"\""))
+        } else {
+            cell.to_string()
+        }
+    }
+
+    fn column(&self, label: &str) -> String {
+        let label = label.trim_end().replace(' ', "_");
+        match self.options.label_case {
+            LabelCase::Lower => label.to_lowercase(),
+            LabelCase::Upper => label.to_uppercase(),
+        }
+    }
+
+    /// Text of single value, nested values are in literal notation
+    fn cell(&self, value: &TdfValue) -> String {
+        match value {
+            TdfValue::Int(v) | TdfValue::Time(v) => v.to_string(),
+            TdfValue::String(v) => String::from_utf8_lossy(v).to_string(),
+            TdfValue::Blob(v) => match self.options.blob {
+                CsvBlob::Hex => hex(v, ""),
+                CsvBlob::Base64 => base64(v),
+                CsvBlob::Size => v.len().to_string(),
+            },
+            TdfValue::Float(v) => v.to_string(),
+            TdfValue::ObjectType(v) => format!("{}/{}", v.0, v.1),
+            TdfValue::ObjectId(v) => format!("{}/{}/{}", v.0, v.1, v.2),
+            value => value.to_string(),
+        }
+    }
+
+    /// Cells of value under column prefix
+    fn flatten(&self, prefix: &str, value: &TdfValue, row: &mut Vec<(String, String)>) {
+        match value {
+            TdfValue::Map(map) => {
+                for (label, value) in &map.entries {
+                    self.flatten(&join(prefix, &format!(".{}", self.column(label))), value, row);
+                }
+            },
+            TdfValue::PairList(_, _, pairs) => {
+                for (key, value) in pairs {
+                    self.flatten(&join(prefix, &format!(".{}", self.cell(key))), value, row);
+                }
+            },
+            TdfValue::List(_, items) => self.flatten_list(prefix, items, row),
+            TdfValue::IntList(items) => {
+                let items: Vec<TdfValue> = items.iter().map(|v| TdfValue::Int(*v)).collect();
+                self.flatten_list(prefix, &items, row)
+            },
+            TdfValue::Union(union_type, member) => {
+                if self.options.union != CsvUnion::Member {
+                    row.push((prefix.to_string(), (*union_type as u8).to_string()));
+                }
+                if self.options.union != CsvUnion::Type {
+                    if let Some((label, value)) = member {
+                        self.flatten(&join(prefix, &format!(".{}", self.column(label))), value, row);
+                    }
+                }
+            },
+            TdfValue::Generic(TdfGeneric::Valid(_, Some((label, value)))) => self.flatten(&join(prefix, &format!(".{}", self.column(label))), value, row),
+            TdfValue::Generic(_) => {},
+            value => row.push((prefix.to_string(), self.cell(value))),
+        }
+    }
+
+    fn flatten_list(&self, prefix: &str, items: &[TdfValue], row: &mut Vec<(String, String)>) {
+        match &self.options.list {
+            CsvList::Join(separator) => {
+                let cells: Vec<String> = items.iter().map(|item| self.cell(item)).collect();
+                row.push((prefix.to_string(), cells.join(separator)));
+            },
+            CsvList::Count => row.push((prefix.to_string(), items.len().to_string())),
+            CsvList::Columns => {
+                for (i, item) in items.iter().enumerate() {
+                    self.flatten(&join(prefix, &format!(".{}", i)), item, row);
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,25 @@
+/*
+    Hex text of bytes, shared by the dump and text based formats
+*/
+
+use anyhow::{Result, bail};
+
+/// Lowercase hex of bytes, joined by separator
+pub(crate) fn hex(bytes: &[u8], separator: &str) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(separator)
+}
+
+/// Bytes of hex string without separators, surrounding whitespace is ignored
+pub(crate) fn unhex(s: &str) -> Result<Vec<u8>> {
+    let s = s.trim();
+    if s.len() % 2 != 0 {
+        bail!("Expected hex string of even length, found {:?}", s);
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| match s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()) {
+            Some(b) => Ok(b),
+            None => bail!("Invalid hex string {:?}", s),
+        })
+        .collect()
+}
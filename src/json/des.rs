@@ -1,138 +1,384 @@
+/*
+    JSON to TDF conversion
+
+    JSON has fewer types than TDF, so the type of every value is
+    taken from type hints given by label path, or inferred:
+
+    integer number        Int, or Float/Time by hint
+    fractional number     Float
+    true/false            Int 1/0
+    "text"                String
+    "Bytes[1, ff]"        Blob, as written by JsonSerializer
+    [[k, v], ...]         PairList, when every element is a pair
+    [...]                 List, element type is inferred from elements
+    {"type": "union", "union": 2, "tag": "valu", "value": ...}
+    {"type": "generic", "id": 42, "valu": {"value": ...}}
+    {...}                 Map
+
+    Hinted values accept more forms: Blob is "Bytes[..]", hex string,
+    array of bytes, or base64 string when set by with_blob_encoding.
+    ObjectType/ObjectId are "component/type[/id]" strings or arrays,
+    IntList is array of ints, PairList can be an object.
+
+    Hints use query paths with [*] for elements of lists and values
+    of pair lists, like "PROS[*].BLOB". Keys of objects are labels,
+    or Rust field names when hints are taken from struct schema.
+*/
+
 use crate::token::*;
-use peekread::{PeekRead, SeekPeekReader};
-use std::io::{Read, Seek};
+use crate::rtdf::{ObjectRegistry, ObjectType, ObjectId, TdfSchema, Deserialize};
+use crate::value::{TdfPath, PathSegment};
+use crate::hex::unhex;
 use anyhow::{Result, bail};
-use byteorder::{BigEndian, ReadBytesExt};
+use std::convert::TryFrom;
+use std::cell::OnceCell;
+
+use super::tree::{JsonValue, parse_json};
+use super::fields::SchemaFields;
+use super::ser::{BlobEncoding, unbase64};
+
 
 pub struct JsonDeserializer {
     pub stream: TDFTokenStream,
     input: String,
-    cursor: usize,
-}
-
-
-#[derive(Debug)]
-pub enum JsonDesError {
-    
+    types: Vec<(TdfPath, TDFToken)>,
+    registry: ObjectRegistry,
+    /// Fields that can be keyed by Rust names, built on first lookup
+    fields: OnceCell<SchemaFields>,
+    schema: Option<fn() -> TdfSchema>,
+    blob: BlobEncoding,
 }
 
 impl JsonDeserializer {
-    fn new(input: String) -> Self {
+
+    pub fn new(input: &str) -> Self {
         Self {
             stream: TDFTokenStream::new(),
-            input,
-            cursor: 0,
+            input: input.to_string(),
+            types: Vec::new(),
+            registry: ObjectRegistry::new(),
+            fields: OnceCell::new(),
+            schema: None,
+            blob: BlobEncoding::Bytes,
+        }
+    }
+
+    /// Type of value at label path, [*] stands for elements of lists and pair lists
+    pub fn with_type(mut self, path: &str, value_type: TDFToken) -> Result<Self> {
+        let path = TdfPath::parse(path)?;
+        if !path.0.iter().all(|s| matches!(s, PathSegment::Label(_) | PathSegment::AnyIndex)) {
+            bail!("Type hint path can contain only labels and [*], found {:?}", path);
         }
+        self.types.push((path, value_type));
+        Ok(self)
     }
 
-    fn get_char(&self) -> Option<&str> {
-        self.input.get(self.cursor.clone()..1)
+    /// Types of values from struct schema and its Rust field names as keys.
+    /// Hints given by with_type take precedence
+    pub fn with_schema(mut self, schema: &TdfSchema) -> Self {
+        self.fields = OnceCell::from(SchemaFields::new(schema));
+        self
     }
 
-    fn validate_symbol(&mut self) -> Result<&str> {
-        let c = match self.get_char() {
-            Some(c) => c,
-            None => bail!("Expected char, got None!"),
-        };
-        Ok(c)
+    /// Same as with_schema, schema of the struct is built only when
+    /// a key or value type is looked up
+    pub fn with_struct_schema<D: Deserialize>(mut self) -> Self {
+        self.fields = OnceCell::new();
+        self.schema = Some(D::schema);
+        self
+    }
+
+    /// Encoding of hinted blobs given as plain strings, hex unless it is Base64
+    pub fn with_blob_encoding(mut self, blob: BlobEncoding) -> Self {
+        self.blob = blob;
+        self
+    }
+
+    /// Accept object types and ids given by registry names
+    pub fn with_registry(mut self, registry: ObjectRegistry) -> Self {
+        self.registry = registry;
+        self
     }
 
-    fn read_string(&mut self) -> Result<String> {
-        let a = self.validate_symbol()?;
-        if a != "\"" {
-            bail!("Expected string start, found {}!", a)
+    /// Parse whole input, root value must be an object
+    pub fn des_json(&mut self) -> Result<()> {
+        let root = parse_json(&self.input)?;
+        if !matches!(root, JsonValue::Object(_)) {
+            bail!("Root of JSON must be an object");
         }
-        self.cursor += 1;
-        let mut s = String::new();
-
-        loop {
-            match self.validate_symbol()? {
-                "\\" => {
-                    self.cursor += 2;
-                    continue;
-                },
-                "\"" => {
-                    self.cursor += 1;
-                    break;
-                },
-                k => {
-                    s.push_str(k);
-                    self.cursor += 1;
-                },
-            }
+        let mut tokens = vec![TDFToken::MapType];
+        self.des_map(&root, &mut Vec::new(), &mut tokens)?;
+        for token in tokens {
+            self.stream.push(token);
         }
-        Ok(s)
+        Ok(())
     }
 
-    fn deserialize_string(&mut self) -> Result<()> {
-        let s = self.read_string()?;
-        self.stream.push(TDFToken::String(s.as_bytes().to_vec()));
-        Ok(())
+
+    fn fields(&self) -> &SchemaFields {
+        self.fields.get_or_init(|| match self.schema {
+            Some(schema) => SchemaFields::new(&schema()),
+            None => SchemaFields::default(),
+        })
     }
 
-    fn deserialize_map(&mut self) -> Result<()> {
+    /// Hinted type of value at path
+    fn hint(&self, path: &[PathSegment]) -> Option<TDFToken> {
+        self.types.iter().rev().find(|(hint, _)| {
+            hint.0.len() == path.len() && hint.0.iter().zip(path).all(|(a, b)| match (a, b) {
+                (PathSegment::Label(a), PathSegment::Label(b)) => normalize_label(a) == *b,
+                (PathSegment::AnyIndex, PathSegment::AnyIndex) => true,
+                _ => false,
+            })
+        }).map(|(_, t)| t.clone()).or_else(|| self.fields().value_type(path))
+    }
 
-        let key = self.read_string()?;
+    /// Type of value by hint, or by its JSON form
+    fn value_type(&self, value: &JsonValue, path: &[PathSegment]) -> Result<TDFToken> {
+        if let Some(hint) = self.hint(path) {
+            return Ok(hint);
+        }
+        Ok(match value {
+            JsonValue::Null => bail!("Null can't be converted to TDF"),
+            JsonValue::Bool(_) => TDFToken::IntType,
+            JsonValue::Number(_) if value.is_integer() => TDFToken::IntType,
+            JsonValue::Number(_) => TDFToken::FloatType,
+            JsonValue::String(s) if s.starts_with("Bytes[") && s.ends_with(']') => TDFToken::BlobType,
+            JsonValue::String(_) => TDFToken::StringType,
+            JsonValue::Array(items) if !items.is_empty() && items.iter().all(|i| matches!(i, JsonValue::Array(pair) if pair.len() == 2)) => TDFToken::PairListType,
+            JsonValue::Array(_) => TDFToken::ListType,
+            JsonValue::Object(_) => match value.field("type") {
+                Some(JsonValue::String(t)) if t == "union" && value.field("union").is_some() => TDFToken::UnionType,
+                Some(JsonValue::String(t)) if t == "generic" => TDFToken::GenericType,
+                _ => TDFToken::MapType,
+            },
+        })
+    }
 
-        loop {
-            let a = self.validate_symbol()?;
-            match a {
-                "}" => {
-                    self.cursor += 1;
-                    self.stream.push(TDFToken::MapEnd);
-                    break;
-                },
-                "\"" => {
-                    self.stream.push(TDFToken::StringType);
-                    self.deserialize_string()?;
-                },
-                a => {
-                    bail!("Unexpected symbol {}!", a)
-                }
+    /// Common type of elements, Int and Float mix into Float
+    fn items_type(&self, items: &[&JsonValue], path: &mut Vec<PathSegment>) -> Result<TDFToken> {
+        path.push(PathSegment::AnyIndex);
+        let mut common: Option<TDFToken> = self.hint(path);
+        if common.is_none() {
+            for item in items {
+                let item_type = self.value_type(item, path)?;
+                common = match common {
+                    None => Some(item_type),
+                    Some(t) if t == item_type => Some(t),
+                    Some(TDFToken::IntType) if item_type == TDFToken::FloatType => Some(item_type),
+                    Some(TDFToken::FloatType) if item_type == TDFToken::IntType => Some(TDFToken::FloatType),
+                    Some(t) => bail!("Elements have different types {:?} and {:?}, add type hint", t, item_type),
+                };
             }
         }
+        path.pop();
+        Ok(common.unwrap_or(TDFToken::IntType))
+    }
+
+    fn read_int(&self, value: &JsonValue) -> Result<i64> {
+        match value {
+            JsonValue::Bool(b) => Ok(*b as i64),
+            JsonValue::Number(n) => match n.parse::<i64>() {
+                Ok(number) => Ok(number),
+                Err(_) if value.is_integer() => bail!("Integer {} doesn't fit into 64 bits", n),
+                Err(_) => bail!("Expected integer, found {}", n),
+            },
+            _ => bail!("Expected integer, found {:?}", value),
+        }
+    }
+
+    fn read_ints(&self, value: &JsonValue) -> Result<Vec<i64>> {
+        match value {
+            JsonValue::Array(items) => items.iter().map(|i| self.read_int(i)).collect(),
+            _ => bail!("Expected array of integers, found {:?}", value),
+        }
+    }
+
+    fn read_blob(&self, value: &JsonValue) -> Result<Vec<u8>> {
+        match value {
+            JsonValue::String(s) => {
+                let parsed: Result<Vec<u8>, _> = match s.strip_prefix("Bytes[").and_then(|s| s.strip_suffix(']')) {
+                    Some("") => Ok(Vec::new()),
+                    Some(bytes) => bytes.split(',').map(|b| u8::from_str_radix(b.trim(), 16)).collect(),
+                    None if self.blob == BlobEncoding::Base64 => return unbase64(s),
+                    None => return unhex(s),
+                };
+                match parsed {
+                    Ok(blob) => Ok(blob),
+                    Err(_) => bail!("Invalid Blob {:?}", s),
+                }
+            },
+            JsonValue::Array(_) => self.read_ints(value)?.into_iter().map(|b| match u8::try_from(b) {
+                Ok(b) => Ok(b),
+                Err(_) => bail!("Blob byte {} is out of range", b),
+            }).collect(),
+            _ => bail!("Expected Blob, found {:?}", value),
+        }
+    }
 
+    fn des_map(&self, value: &JsonValue, path: &mut Vec<PathSegment>, out: &mut Vec<TDFToken>) -> Result<()> {
+        let fields = match value {
+            JsonValue::Object(fields) => fields,
+            _ => bail!("Expected object for Map, found {:?}", value),
+        };
+        out.push(TDFToken::MapStart);
+        for (key, field) in fields {
+            self.des_member(key, field, path, out)?;
+        }
+        out.push(TDFToken::MapEnd);
         Ok(())
     }
 
-    fn deserialize(&mut self) -> Result<()> {
+    /// Label, type and value of map field or union/generic member
+    fn des_member(&self, key: &str, value: &JsonValue, path: &mut Vec<PathSegment>, out: &mut Vec<TDFToken>) -> Result<()> {
+        let label = match self.fields().label(path, key) {
+            Some(label) => label,
+            None if key.chars().count() > 4 => bail!("Label {:?} is longer than 4 chars", key),
+            None => normalize_label(key),
+        };
+        path.push(PathSegment::Label(label.clone()));
+        let value_type = self.value_type(value, path)?;
+        out.push(TDFToken::Label(label));
+        out.push(value_type.clone());
+        self.des_value(value, &value_type, path, out)?;
+        path.pop();
+        Ok(())
+    }
 
-        match self.validate_symbol()? {
-            "{" => {
-                self.cursor += 1;
-                self.stream.push(TDFToken::MapStart);
-                self.deserialize_map()?;
+    fn des_value(&self, value: &JsonValue, value_type: &TDFToken, path: &mut Vec<PathSegment>, out: &mut Vec<TDFToken>) -> Result<()> {
+        match value_type {
+            TDFToken::IntType | TDFToken::TimeType => out.push(TDFToken::Int(self.read_int(value)?)),
+            TDFToken::FloatType => match value {
+                JsonValue::Number(n) => match n.parse::<f32>() {
+                    Ok(f) => out.push(TDFToken::Float(f)),
+                    Err(_) => bail!("Expected float, found {}", n),
+                },
+                _ => bail!("Expected float, found {:?}", value),
             },
-            "}" => {
-                self.cursor += 1;
-                self.stream.push(TDFToken::MapEnd);
+            TDFToken::StringType => match value {
+                JsonValue::String(s) => out.push(TDFToken::String(s.as_bytes().to_vec())),
+                _ => bail!("Expected string, found {:?}", value),
+            },
+            TDFToken::BlobType => out.push(TDFToken::Blob(self.read_blob(value)?)),
+            TDFToken::MapType => self.des_map(value, path, out)?,
+            TDFToken::ListType => {
+                let items = match value {
+                    JsonValue::Array(items) => items,
+                    _ => bail!("Expected array for List, found {:?}", value),
+                };
+                let item_type = self.items_type(&items.iter().collect::<Vec<_>>(), path)?;
+                out.push(TDFToken::ListStart(items.len()));
+                out.push(item_type.clone());
+                path.push(PathSegment::AnyIndex);
+                for item in items {
+                    self.des_value(item, &item_type, path, out)?;
+                }
+                path.pop();
+                out.push(TDFToken::ListEnd);
+            },
+            TDFToken::PairListType => {
+                let pairs: Vec<(JsonValue, &JsonValue)> = match value {
+                    JsonValue::Array(items) => items.iter().map(|item| match item {
+                        JsonValue::Array(pair) if pair.len() == 2 => Ok((pair[0].clone(), &pair[1])),
+                        _ => bail!("Expected [key, value] pair, found {:?}", item),
+                    }).collect::<Result<_>>()?,
+                    JsonValue::Object(fields) => fields.iter().map(|(k, v)| (JsonValue::String(k.clone()), v)).collect(),
+                    _ => bail!("Expected array of pairs or object for PairList, found {:?}", value),
+                };
+                let key_type = match pairs.first() {
+                    Some((key, _)) => self.value_type(key, &[])?,
+                    None => TDFToken::IntType,
+                };
+                let values: Vec<&JsonValue> = pairs.iter().map(|(_, v)| *v).collect();
+                let item_type = self.items_type(&values, path)?;
+                out.push(TDFToken::PairListStart(pairs.len()));
+                out.push(key_type.clone());
+                out.push(item_type.clone());
+                path.push(PathSegment::AnyIndex);
+                for (key, item) in &pairs {
+                    self.des_value(key, &key_type, &mut Vec::new(), out)?;
+                    self.des_value(item, &item_type, path, out)?;
+                }
+                path.pop();
+                out.push(TDFToken::PairListEnd);
             },
-            "\"" => {
-                self.cursor += 1;
-                self.stream.push(TDFToken::StringType);
-                let mut s = String::new();
-                while let Some(a) = self.get_char() {
-                    if a == "\"" {
-                        break;
+            TDFToken::UnionType => {
+                let union_type = match value.field("union") {
+                    Some(index) => UnionType::from_tag(self.read_int(index)? as u8),
+                    None => bail!("Expected \"union\" index in Union, found {:?}", value),
+                };
+                out.push(TDFToken::UnionStart(union_type));
+                if union_type != UnionType::Unset {
+                    let tag = match value.field("tag") {
+                        Some(JsonValue::String(tag)) => tag,
+                        _ => bail!("Expected \"tag\" label in Union, found {:?}", value),
+                    };
+                    match value.field("value") {
+                        Some(member) => self.des_member(tag, member, path, out)?,
+                        None => bail!("Expected \"value\" in Union, found {:?}", value),
                     }
-                    s.push_str(a);
                 }
-                
-                self.deserialize()?;
+                out.push(TDFToken::UnionEnd);
             },
-            a => {
-                bail!("Unexpected symbol {}!", a)
-            }
+            TDFToken::GenericType => {
+                match value.field("id") {
+                    Some(id) => {
+                        out.push(TDFToken::GenericStart(true));
+                        out.push(TDFToken::Int(self.read_int(id)?));
+                        let member = match value {
+                            JsonValue::Object(fields) => fields.iter().find(|(k, _)| k != "type" && k != "id"),
+                            _ => None,
+                        };
+                        if let Some((label, member)) = member {
+                            match member.field("value") {
+                                Some(member) => self.des_member(label, member, path, out)?,
+                                None => bail!("Expected {{\"value\": ..}} in Generic member, found {:?}", member),
+                            }
+                        }
+                    },
+                    None => out.push(TDFToken::GenericStart(false)),
+                }
+                out.push(TDFToken::GenericEnd);
+            },
+            TDFToken::IntListType => {
+                let ints = self.read_ints(value)?;
+                out.push(TDFToken::IntListStart(ints.len()));
+                out.extend(ints.into_iter().map(TDFToken::Int));
+                out.push(TDFToken::IntListEnd);
+            },
+            TDFToken::ObjectTypeType => {
+                let ObjectType(component, type_id) = match value {
+                    JsonValue::String(s) => self.registry.parse_type(s)?,
+                    _ => match self.read_ints(value)?[..] {
+                        [component, type_id] => ObjectType(component, type_id),
+                        _ => bail!("Expected [component, type] for ObjectType, found {:?}", value),
+                    },
+                };
+                out.push(TDFToken::Int(component));
+                out.push(TDFToken::Int(type_id));
+            },
+            TDFToken::ObjectIdType => {
+                let ObjectId(component, type_id, id) = match value {
+                    JsonValue::String(s) => self.registry.parse_id(s)?,
+                    _ => match self.read_ints(value)?[..] {
+                        [component, type_id, id] => ObjectId(component, type_id, id),
+                        _ => bail!("Expected [component, type, id] for ObjectId, found {:?}", value),
+                    },
+                };
+                out.push(TDFToken::Int(component));
+                out.push(TDFToken::Int(type_id));
+                out.push(TDFToken::Int(id));
+            },
+            _ => bail!("Expected type token as type hint, found {:?}", value_type),
         }
         Ok(())
     }
 }
 
-impl<'a> TDFDeserializer<String> for JsonDeserializer {
+impl TDFDeserializer<String> for JsonDeserializer {
     fn deserialize(reader: &mut String) -> Result<TDFTokenStream> {
-
-        let mut des = Self::new(reader.clone());
-        des.deserialize()?;
+        let mut des = Self::new(reader);
+        des.des_json()?;
         Ok(des.stream)
     }
-}
\ No newline at end of file
+}
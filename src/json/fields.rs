@@ -0,0 +1,93 @@
+use crate::token::{TDFToken, normalize_label};
+use crate::rtdf::TdfSchema;
+use crate::value::PathSegment;
+
+use super::ser::{JsonOptions, LabelCase};
+
+
+/// Struct schema walked along label paths, [*] for list items and pair list values.
+/// Structs referenced by name are resolved on the way
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SchemaFields {
+    root: Option<TdfSchema>,
+    /// Every struct of the root, for references
+    structs: Vec<TdfSchema>,
+}
+
+impl SchemaFields {
+
+    pub fn new(schema: &TdfSchema) -> Self {
+        Self {
+            root: Some(schema.clone()),
+            structs: schema.structs().into_iter().map(|s| TdfSchema::Struct(s.clone())).collect(),
+        }
+    }
+
+    /// Optional value as its inner one, reference as the struct
+    fn resolve<'a>(&'a self, mut schema: &'a TdfSchema) -> Option<&'a TdfSchema> {
+        loop {
+            match schema {
+                TdfSchema::Optional(inner) => schema = inner,
+                TdfSchema::Ref(name) => return self.structs.iter()
+                    .find(|s| matches!(s, TdfSchema::Struct(s) if s.name == *name)),
+                schema => return Some(schema),
+            }
+        }
+    }
+
+    /// Schema of value at path and Rust name of the field, if path ends with one
+    fn at(&self, path: &[PathSegment]) -> Option<(&TdfSchema, Option<&str>)> {
+        let mut schema = self.root.as_ref()?;
+        let mut name = None;
+        for segment in path {
+            match (self.resolve(schema)?, segment) {
+                (TdfSchema::Struct(s), PathSegment::Label(label)) => {
+                    let field = s.fields.iter().find(|f| normalize_label(&f.label) == *label)?;
+                    schema = &field.schema;
+                    name = Some(field.name.as_str());
+                },
+                (TdfSchema::List(item), PathSegment::AnyIndex) | (TdfSchema::PairList(_, item), PathSegment::AnyIndex) => {
+                    schema = item;
+                    name = None;
+                },
+                _ => return None,
+            }
+        }
+        Some((schema, name))
+    }
+
+    /// Type of value at path below the root
+    pub fn value_type(&self, path: &[PathSegment]) -> Option<TDFToken> {
+        if path.is_empty() {
+            return None;
+        }
+        self.at(path).map(|(schema, _)| schema.type_token())
+    }
+
+    /// Rust name of field at path
+    pub fn name(&self, path: &[PathSegment]) -> Option<&str> {
+        self.at(path).and_then(|(_, name)| name)
+    }
+
+    /// Label of field with Rust name in struct at parent path
+    pub fn label(&self, parent: &[PathSegment], name: &str) -> Option<String> {
+        match self.resolve(self.at(parent)?.0)? {
+            TdfSchema::Struct(s) => s.fields.iter()
+                .find(|f| f.name == name)
+                .map(|f| normalize_label(&f.label)),
+            _ => None,
+        }
+    }
+}
+
+/// Key of struct field in json written with given options
+pub(crate) fn field_key(options: &JsonOptions, field_names: bool, name: &str, label: &str) -> String {
+    if field_names {
+        return name.to_string();
+    }
+    let label = normalize_label(label).trim_end().replace(' ', "_");
+    match options.label_case {
+        LabelCase::Lower => label.to_lowercase(),
+        LabelCase::Upper => label.to_uppercase(),
+    }
+}
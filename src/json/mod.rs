@@ -2,4 +2,19 @@ mod ser;
 pub use ser::*;
 
 mod des;
-pub use des::*;
\ No newline at end of file
+pub use des::*;
+
+mod typed;
+pub use typed::*;
+
+mod schema;
+pub use schema::*;
+
+mod typescript;
+pub use typescript::*;
+
+mod patch;
+
+mod tree;
+
+mod fields;
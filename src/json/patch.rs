@@ -0,0 +1,80 @@
+/*
+    JSON form of TDF patches, in the manner of JSON Patch (RFC 6902)
+
+    [
+        {"op": "replace", "path": "GID", "value": 6},
+        {"op": "add", "path": "PROS[1]", "value": {"PNAM": "new"}},
+        {"op": "remove", "path": "ATTR[\"mode\"]"}
+    ]
+
+    Paths are the concrete paths TdfPatch takes, values are written
+    in typed JSON notation, so their TDF types are kept.
+*/
+
+use crate::value::{PatchOp, TdfPatch, TdfValue, ValueSerializer};
+use anyhow::{Result, bail};
+
+use super::tree::{JsonValue, parse_json};
+use super::typed::{typed_tokens, typed_value};
+
+
+fn string(value: Option<&JsonValue>, key: &str) -> Result<String> {
+    match value {
+        Some(JsonValue::String(s)) => Ok(s.clone()),
+        _ => bail!("Expected string {:?} in patch operation, found {:?}", key, value),
+    }
+}
+
+fn read_value(value: Option<&JsonValue>) -> Result<TdfValue> {
+    let value = match value {
+        Some(value) => value,
+        None => bail!("Expected \"value\" in patch operation"),
+    };
+    let (value_type, stream) = typed_tokens(value)?;
+    ValueSerializer::new(stream).ser_token(value_type)
+}
+
+fn read_op(op: &JsonValue) -> Result<PatchOp> {
+    let path = string(op.field("path"), "path")?;
+    Ok(match string(op.field("op"), "op")?.as_str() {
+        "add" => PatchOp::Add { path, value: read_value(op.field("value"))? },
+        "remove" => PatchOp::Remove { path },
+        "replace" => PatchOp::Replace { path, value: read_value(op.field("value"))? },
+        name => bail!("Unknown patch operation {:?}", name),
+    })
+}
+
+fn write_op(op: &PatchOp) -> Result<JsonValue> {
+    let (name, path, value) = match op {
+        PatchOp::Add { path, value } => ("add", path, Some(value)),
+        PatchOp::Remove { path } => ("remove", path, None),
+        PatchOp::Replace { path, value } => ("replace", path, Some(value)),
+    };
+    let mut fields = vec![
+        ("op".to_string(), JsonValue::String(name.to_string())),
+        ("path".to_string(), JsonValue::String(path.clone())),
+    ];
+    if let Some(value) = value {
+        fields.push(("value".to_string(), typed_value(value.to_stream()?)?));
+    }
+    Ok(JsonValue::Object(fields))
+}
+
+impl TdfPatch {
+
+    /// Patch from its JSON form, array of operations
+    pub fn from_json(input: &str) -> Result<Self> {
+        match parse_json(input)? {
+            JsonValue::Array(ops) => Ok(Self(ops.iter().map(read_op).collect::<Result<_>>()?)),
+            value => bail!("Expected array of patch operations, found {:?}", value),
+        }
+    }
+
+    /// JSON form of patch, empty indent gives compact output
+    pub fn to_json(&self, indent: &str) -> Result<String> {
+        let ops = self.0.iter().map(write_op).collect::<Result<_>>()?;
+        let mut output = String::new();
+        JsonValue::Array(ops).write(&mut output, indent, 0);
+        Ok(output)
+    }
+}
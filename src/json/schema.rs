@@ -0,0 +1,177 @@
+use crate::token::*;
+use crate::rtdf::{TdfSchema, StructSchema};
+use anyhow::{Result, bail};
+
+use super::ser::{JsonOptions, BlobEncoding};
+use super::tree::JsonValue;
+use super::fields::field_key;
+
+
+/// Writes JSON Schema (draft 2020-12) of the JSON that JsonSerializer
+/// writes for a struct, following the same options
+pub struct JsonSchemaWriter {
+    options: JsonOptions,
+    field_names: bool,
+}
+
+fn string(s: &str) -> JsonValue {
+    JsonValue::String(s.to_string())
+}
+
+fn object(fields: Vec<(&str, JsonValue)>) -> JsonValue {
+    JsonValue::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn typed(json_type: &str) -> JsonValue {
+    object(vec![("type", string(json_type))])
+}
+
+fn integers(values: &[i64]) -> JsonValue {
+    JsonValue::Array(values.iter().map(|v| JsonValue::Number(v.to_string())).collect())
+}
+
+fn pattern(regex: &str) -> JsonValue {
+    object(vec![("type", string("string")), ("pattern", string(regex))])
+}
+
+impl JsonSchemaWriter {
+
+    pub fn new() -> Self {
+        Self {
+            options: JsonOptions::default(),
+            field_names: false,
+        }
+    }
+
+    /// Options of JsonSerializer the JSON is written with
+    pub fn with_options(mut self, options: JsonOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keys are Rust field names, like in JsonSerializer::with_field_names
+    pub fn with_field_names(mut self) -> Self {
+        self.field_names = true;
+        self
+    }
+
+    /// Write schema of root struct, nested structs are in $defs
+    pub fn write_schema(&self, schema: &TdfSchema, writer: &mut String) -> Result<()> {
+        let root = match schema {
+            TdfSchema::Struct(root) => root,
+            _ => bail!("Expected struct schema as root, found {:?}", schema),
+        };
+        let defs = schema.structs().into_iter()
+            .map(|s| (s.name.clone(), self.struct_schema(s)))
+            .collect();
+        let document = object(vec![
+            ("$schema", string("https://json-schema.org/draft/2020-12/schema")),
+            ("title", string(&root.name)),
+            ("$ref", string(&format!("#/$defs/{}", root.name))),
+            ("$defs", JsonValue::Object(defs)),
+        ]);
+        document.write(writer, &self.options.indent, 0);
+        Ok(())
+    }
+
+    fn struct_schema(&self, schema: &StructSchema) -> JsonValue {
+        let mut properties = Vec::new();
+        let mut required = Vec::new();
+        for field in &schema.fields {
+            let key = field_key(&self.options, self.field_names, &field.name, &field.label);
+            if !matches!(field.schema, TdfSchema::Optional(_)) {
+                required.push(string(&key));
+            }
+            properties.push((key, self.value_schema(&field.schema)));
+        }
+        object(vec![
+            ("type", string("object")),
+            ("properties", JsonValue::Object(properties)),
+            ("required", JsonValue::Array(required)),
+            ("additionalProperties", JsonValue::Bool(false)),
+        ])
+    }
+
+    fn value_schema(&self, schema: &TdfSchema) -> JsonValue {
+        match schema {
+            TdfSchema::Value(token) => self.type_schema(token),
+            TdfSchema::Bool => object(vec![("type", string("integer")), ("enum", integers(&[0, 1]))]),
+            TdfSchema::Struct(StructSchema { name, .. }) | TdfSchema::Ref(name) => object(vec![("$ref", string(&format!("#/$defs/{}", name)))]),
+            TdfSchema::List(item) => object(vec![("type", string("array")), ("items", self.value_schema(item))]),
+            TdfSchema::PairList(key, value) => {
+                if self.options.pair_list_objects && key.type_token() == TDFToken::StringType {
+                    return object(vec![("type", string("object")), ("additionalProperties", self.value_schema(value))]);
+                }
+                let pair = object(vec![
+                    ("type", string("array")),
+                    ("prefixItems", JsonValue::Array(vec![self.value_schema(key), self.value_schema(value)])),
+                    ("minItems", JsonValue::Number("2".to_string())),
+                    ("maxItems", JsonValue::Number("2".to_string())),
+                ]);
+                object(vec![("type", string("array")), ("items", pair)])
+            },
+            TdfSchema::Optional(inner) => self.value_schema(inner),
+        }
+    }
+
+    /// Schema of value known only by type token
+    fn type_schema(&self, token: &TDFToken) -> JsonValue {
+        match token {
+            TDFToken::IntType | TDFToken::TimeType => typed("integer"),
+            TDFToken::StringType => typed("string"),
+            // NaN and infinities are written as null
+            TDFToken::FloatType => object(vec![("type", JsonValue::Array(vec![string("number"), string("null")]))]),
+            TDFToken::BlobType => match self.options.blob {
+                BlobEncoding::Bytes => pattern("^Bytes\\[([0-9a-f]{1,2}(, [0-9a-f]{1,2})*)?\\]$"),
+                BlobEncoding::Hex => pattern("^([0-9a-f]{2})*$"),
+                BlobEncoding::Base64 => object(vec![("type", string("string")), ("contentEncoding", string("base64"))]),
+                BlobEncoding::Array => object(vec![
+                    ("type", string("array")),
+                    ("items", object(vec![("type", string("integer")), ("minimum", JsonValue::Number("0".to_string())), ("maximum", JsonValue::Number("255".to_string()))])),
+                ]),
+            },
+            TDFToken::IntListType => object(vec![("type", string("array")), ("items", typed("integer"))]),
+            // Numbers or registry names
+            TDFToken::ObjectTypeType => pattern("^[^/]+/[^/]+$"),
+            TDFToken::ObjectIdType => pattern("^[^/]+/[^/]+/-?[0-9]+$"),
+            TDFToken::UnionType => {
+                let members = [UnionType::XboxClientAddr, UnionType::XboxServerAddr, UnionType::IpPairAddr, UnionType::IpAddr, UnionType::HostnameAddr, UnionType::Unset];
+                object(vec![
+                    ("type", string("object")),
+                    ("properties", object(vec![
+                        ("type", object(vec![("const", string("union"))])),
+                        ("union", object(vec![("enum", integers(&members.iter().map(|m| *m as i64).collect::<Vec<_>>()))])),
+                        ("tag", typed("string")),
+                        ("value", object(Vec::new())),
+                    ])),
+                    ("required", JsonValue::Array(vec![string("type"), string("union")])),
+                    ("additionalProperties", JsonValue::Bool(false)),
+                ])
+            },
+            TDFToken::GenericType => object(vec![
+                ("type", string("object")),
+                ("properties", object(vec![
+                    ("type", object(vec![("const", string("generic"))])),
+                    ("id", typed("integer")),
+                ])),
+                ("required", JsonValue::Array(vec![string("type")])),
+                // Member is keyed by its label
+                ("additionalProperties", object(vec![
+                    ("type", string("object")),
+                    ("properties", object(vec![("value", object(Vec::new()))])),
+                    ("required", JsonValue::Array(vec![string("value")])),
+                ])),
+            ]),
+            TDFToken::MapType => typed("object"),
+            TDFToken::ListType => typed("array"),
+            TDFToken::PairListType => typed("array"),
+            _ => object(Vec::new()),
+        }
+    }
+}
+
+impl Default for JsonSchemaWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,20 +1,174 @@
-
 use crate::token::*;
-use std::io::{Write};
+use crate::rtdf::{ObjectId, ObjectRegistry, ObjectType, TdfSchema};
+use crate::value::PathSegment;
+use crate::hex::hex;
 use anyhow::{Result, bail};
-use byteorder::{BigEndian, WriteBytesExt};
+
+use super::tree::quote;
+use super::fields::SchemaFields;
+
+
+/// How blobs are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlobEncoding {
+    /// "Bytes[1, ff]" string in Rust debug form, kept for compatibility.
+    /// Default, as it is the only string form JsonDeserializer reads
+    /// as Blob without type hints. Prefer Hex or Base64 with hints
+    Bytes,
+    /// "01ff" string
+    Hex,
+    /// "Af8=" string, standard alphabet with padding
+    Base64,
+    /// [1, 255]
+    Array,
+}
+
+/// Case of labels used as keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelCase {
+    Lower,
+    Upper,
+}
+
+/// What to do with a label repeated in one map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicateLabels {
+    /// Write the key again, most parsers keep the last value
+    Keep,
+    /// Append _2, _3 and so on to repeated keys
+    Suffix,
+    Error,
+}
+
+/// Options of json output
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    /// Indentation of nested values, empty one gives compact json
+    pub indent: String,
+    pub blob: BlobEncoding,
+    pub label_case: LabelCase,
+    /// Write pair lists with String keys as objects, instead of [[k, v]] arrays
+    pub pair_list_objects: bool,
+    pub duplicate_labels: DuplicateLabels,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self {
+            indent: "\t".to_string(),
+            blob: BlobEncoding::Bytes,
+            label_case: LabelCase::Lower,
+            pair_list_objects: false,
+            duplicate_labels: DuplicateLabels::Keep,
+        }
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - i * 6)) & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Bytes of base64 string, padding is optional
+pub(crate) fn unbase64(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    if text.len() % 4 == 1 {
+        bail!("Invalid length of base64 string {:?}", text);
+    }
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match BASE64.iter().position(|b| *b == c) {
+            Some(value) => value as u32,
+            None => bail!("Invalid char {:?} in base64 string", c as char),
+        };
+        n = (n << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
 
 
 pub struct JsonSerializer {
     stream: TDFTokenStream,
+    registry: ObjectRegistry,
+    options: JsonOptions,
+    /// Fields named by Rust names
+    fields: SchemaFields,
+    /// Label path of current value
+    path: Vec<PathSegment>,
 }
 
 impl JsonSerializer {
 
     pub fn new(stream: TDFTokenStream) -> Self {
         Self {
-            stream
+            stream,
+            registry: ObjectRegistry::new(),
+            options: JsonOptions::default(),
+            fields: SchemaFields::default(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Use registry names when writing object types and ids
+    pub fn with_registry(mut self, registry: ObjectRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn with_options(mut self, options: JsonOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Write Rust field names of the schema as keys instead of labels
+    pub fn with_field_names(mut self, schema: &TdfSchema) -> Self {
+        self.fields = SchemaFields::new(schema);
+        self
+    }
+
+    /// Write whole stream as json into the writer
+    pub fn write_json(&mut self, writer: &mut String) -> Result<()> {
+        let token = self.stream.next()?;
+        let str_res = self.ser_token(token, 0)?;
+        writer.insert_str(writer.len(), &str_res);
+        Ok(())
+    }
+
+    /// New line with indentation of the level, nothing in compact json
+    fn line(&self, level: u32) -> String {
+        if self.options.indent.is_empty() {
+            return String::new();
         }
+        format!("\n{}", self.options.indent.repeat(level as usize))
+    }
+
+    /// Separator of inline array items
+    fn comma(&self) -> &'static str {
+        if self.options.indent.is_empty() { "," } else { ", " }
+    }
+
+    fn colon(&self) -> &'static str {
+        if self.options.indent.is_empty() { ":" } else { ": " }
     }
 
     pub fn ser_token(&mut self, token_type: TDFToken, level: u32) -> Result<String> {
@@ -30,6 +184,7 @@ impl JsonSerializer {
             TDFToken::ObjectTypeType => self.ser_object_type(),
             TDFToken::ObjectIdType   => self.ser_object_id(),
             TDFToken::FloatType      => self.ser_float(),
+            TDFToken::TimeType       => self.ser_int(),
             TDFToken::GenericType    => self.ser_generic(level),
             _ => bail!("Trying to parse type token, but found {:?}", token_type)
         }
@@ -67,52 +222,71 @@ impl JsonSerializer {
         }
 
         let mut output = String::new();
-        let mut iter = 0;
-        output.push_str("{");
+        let mut keys: Vec<String> = Vec::new();
+        output.push('{');
 
         loop {
 
-            let mut label = self.stream.next()?;
+            let label = self.stream.next()?;
 
             if label == TDFToken::MapEnd {
-                if iter != 0 {
-                    output.push_str("\n");
-                    for _ in 0..level {
-                        output.push_str("\t");
-                    }
+                if !keys.is_empty() {
+                    output.push_str(&self.line(level));
                 }
-                output.push_str("}");
+                output.push('}');
                 return Ok(output);
             } else if label == TDFToken::MapUnion {
-                label = self.stream.next()?;
+                continue;
             }
 
-            if iter != 0 {
-                output.push_str(",\n");
-            } else {
-                output.push_str("\n");
-            }
-
-            for _ in 0..level+1 {
-                output.push_str("\t");
+            if !keys.is_empty() {
+                output.push(',');
             }
+            output.push_str(&self.line(level + 1));
 
-            match label {
-                TDFToken::Label(label_string) => {
-                    output.push_str(&self.write_label(&label_string)?);
-                },
+            let label_string = match label {
+                TDFToken::Label(label_string) => label_string,
                 _ => bail!("Expected Label in Map, found {:?}", label),
-            }
-            output.push_str(": ");
-            
+            };
+            self.path.push(PathSegment::Label(normalize_label(&label_string)));
+            let key = match self.fields.name(&self.path) {
+                Some(name) => quote(name),
+                None => self.write_label(&label_string)?,
+            };
+            let key = self.dedup_key(key, &keys)?;
+            output.push_str(&key);
+            output.push_str(self.colon());
+            keys.push(key);
+
             let value = self.stream.next()?;
             output.push_str(&self.ser_token(value, level+1)?);
-
-            iter += 1;
+            self.path.pop();
         }
 
     }
 
+    /// Apply duplicate labels option to quoted key
+    fn dedup_key(&self, key: String, keys: &[String]) -> Result<String> {
+        if !keys.contains(&key) {
+            return Ok(key);
+        }
+        match self.options.duplicate_labels {
+            DuplicateLabels::Keep => Ok(key),
+            DuplicateLabels::Error => bail!("Label {} is repeated in map", key),
+            DuplicateLabels::Suffix => {
+                let base = key.trim_end_matches('"');
+                let mut n = 2;
+                loop {
+                    let candidate = format!("{}_{}\"", base, n);
+                    if !keys.contains(&candidate) {
+                        return Ok(candidate);
+                    }
+                    n += 1;
+                }
+            },
+        }
+    }
+
     pub fn ser_list(&mut self, level: u32) -> Result<String> {
 
         let token = self.stream.next()?;
@@ -122,17 +296,19 @@ impl JsonSerializer {
         };
 
         let mut output = String::new();
-        
+
         let inner_type = self.stream.next()?;
 
-        output.push_str("[");
+        output.push('[');
+        self.path.push(PathSegment::AnyIndex);
         for i in 0..size {
             output.push_str(&self.ser_token( inner_type.clone(), level)?);
             if i != size-1 {
-                output.push_str(", ");
+                output.push_str(self.comma());
             }
         }
-        output.push_str("]");
+        self.path.pop();
+        output.push(']');
 
         let end_token = self.stream.next()?;
         if end_token != TDFToken::ListEnd {
@@ -151,22 +327,37 @@ impl JsonSerializer {
         };
 
         let mut output = String::new();
-        
+
         let k_type = self.stream.next()?;
         let v_type = self.stream.next()?;
+        let as_object = self.options.pair_list_objects && k_type == TDFToken::StringType;
 
-        output.push_str("[");
+        output.push(if as_object { '{' } else { '[' });
+        self.path.push(PathSegment::AnyIndex);
         for i in 0..size {
-            output.push_str("[");
-            output.push_str(&self.ser_token( k_type.clone(), level)?);
-            output.push_str(", ");
-            output.push_str(&self.ser_token( v_type.clone(), level)?);
-            output.push_str("]");
+            output.push_str(&self.line(level + 1));
+            let key = self.ser_token(k_type.clone(), level + 1)?;
+            let value = self.ser_token(v_type.clone(), level + 1)?;
+            if as_object {
+                output.push_str(&key);
+                output.push_str(self.colon());
+                output.push_str(&value);
+            } else {
+                output.push('[');
+                output.push_str(&key);
+                output.push_str(self.comma());
+                output.push_str(&value);
+                output.push(']');
+            }
             if i != size-1 {
-                output.push_str(",\n");
+                output.push(',');
             }
         }
-        output.push_str("]");
+        self.path.pop();
+        if size != 0 {
+            output.push_str(&self.line(level));
+        }
+        output.push(if as_object { '}' } else { ']' });
 
         let end_token = self.stream.next()?;
         if end_token != TDFToken::PairListEnd {
@@ -185,15 +376,11 @@ impl JsonSerializer {
         };
 
         let mut output = String::new();
-        output.push_str("{\n");
-        for _ in 0..level+1 {
-            output.push_str("\t");
-        }
-        output.push_str("\"type\": \"union\",\n");
-        for _ in 0..level+1 {
-            output.push_str("\t");
-        }
-        output.push_str(&format!("\"union\": {}", union_type as u8));
+        output.push('{');
+        output.push_str(&self.line(level + 1));
+        output.push_str(&format!("\"type\"{}\"union\",", self.colon()));
+        output.push_str(&self.line(level + 1));
+        output.push_str(&format!("\"union\"{}{}", self.colon(), union_type as u8));
 
         if union_type == UnionType::Unset {
 
@@ -201,11 +388,8 @@ impl JsonSerializer {
             if end_token != TDFToken::UnionEnd {
                 bail!("Expected End of Union, found {:?}", end_token)
             }
-            output.push_str("\n");
-            for _ in 0..level {
-                output.push_str("\t");
-            }
-            output.push_str("}");
+            output.push_str(&self.line(level));
+            output.push('}');
 
             return Ok(output);
 
@@ -215,29 +399,26 @@ impl JsonSerializer {
 
         match union_label {
             TDFToken::Label(label_string) => {
-                output.push_str(",\n");
-                for _ in 0..level+1 {
-                    output.push_str("\t");
-                }
-                output.push_str("\"tag\": ");
+                output.push(',');
+                output.push_str(&self.line(level + 1));
+                output.push_str("\"tag\"");
+                output.push_str(self.colon());
                 output.push_str(&self.write_label(&label_string)?);
+                self.path.push(PathSegment::Label(normalize_label(&label_string)));
             },
             _ => bail!("Expected Label in Union, found {:?}", union_label),
         }
-        
+
         let value = self.stream.next()?;
-        output.push_str(",\n");
-        for _ in 0..level+1 {
-            output.push_str("\t");
-        }
-        output.push_str("\"value\": ");
+        output.push(',');
+        output.push_str(&self.line(level + 1));
+        output.push_str("\"value\"");
+        output.push_str(self.colon());
         output.push_str(&self.ser_token(value, level+1)?);
-        output.push_str("\n");
-        for _ in 0..level {
-            output.push_str("\t");
-        }
-        output.push_str("}");
-        
+        self.path.pop();
+        output.push_str(&self.line(level));
+        output.push('}');
+
 
         let end_token = self.stream.next()?;
         if end_token != TDFToken::UnionEnd {
@@ -255,10 +436,10 @@ impl JsonSerializer {
             _ => bail!("Expected Generic start, found {:?}", token),
         };
         let mut output = String::new();
-        
+
         if !exist {
-            
-            output.push_str("{\"type\": \"generic\"}");
+
+            output.push_str(&format!("{{\"type\"{}\"generic\"}}", self.colon()));
 
             let end_token = self.stream.next()?;
             if end_token != TDFToken::GenericEnd {
@@ -270,18 +451,15 @@ impl JsonSerializer {
         }
 
         let tdf_id = self.stream.next()?;
-        output.push_str("{\n");
-        for _ in 0..level+1 {
-            output.push_str("\t");
-        }
-        output.push_str("\"type\": \"generic\",\n");
+        output.push('{');
+        output.push_str(&self.line(level + 1));
+        output.push_str(&format!("\"type\"{}\"generic\",", self.colon()));
 
         match tdf_id {
             TDFToken::Int(id) => {
-                for _ in 0..level+1 {
-                    output.push_str("\t");
-                }
-                output.push_str("\"id\": ");
+                output.push_str(&self.line(level + 1));
+                output.push_str("\"id\"");
+                output.push_str(self.colon());
                 output.push_str(&self.write_number(id)?);
             },
             _ => bail!("Expected TDFID in Generic, found {:?}", tdf_id),
@@ -291,47 +469,37 @@ impl JsonSerializer {
 
         match generic_label {
             TDFToken::Label(label_string) => {
-                output.push_str(",\n");
-                for _ in 0..level+1 {
-                    output.push_str("\t");
-                }
+                output.push(',');
+                output.push_str(&self.line(level + 1));
                 output.push_str(&self.write_label(&label_string)?);
-                output.push_str(": ");
+                output.push_str(self.colon());
+                self.path.push(PathSegment::Label(normalize_label(&label_string)));
             },
             TDFToken::GenericEnd => {
-                output.push_str("\n");
-                for _ in 0..level {
-                    output.push_str("\t");
-                }
-                output.push_str("}");
+                output.push_str(&self.line(level));
+                output.push('}');
                 return Ok(output);
             }
             _ => bail!("Expected Label in Generic, found {:?}", generic_label),
         };
-        
+
         let value = self.stream.next()?;
 
-        output.push_str("{\n");
-        for _ in 0..level+2 {
-            output.push_str("\t");
-        }
-        output.push_str(&format!("\"value\": "));
+        output.push('{');
+        output.push_str(&self.line(level + 2));
+        output.push_str("\"value\"");
+        output.push_str(self.colon());
         output.push_str(&self.ser_token(value, level+2)?);
-        output.push_str("\n");
-        for _ in 0..level+1 {
-            output.push_str("\t");
-        }
-        output.push_str("}");
+        self.path.pop();
+        output.push_str(&self.line(level + 1));
+        output.push('}');
 
         let end_token = self.stream.next()?;
         if end_token != TDFToken::GenericEnd {
             bail!("Expected End of Generic, found {:?}", end_token)
         }
-        output.push_str("\n");
-        for _ in 0..level {
-            output.push_str("\t");
-        }
-        output.push_str("}");
+        output.push_str(&self.line(level));
+        output.push('}');
 
         Ok(output)
     }
@@ -343,51 +511,47 @@ impl JsonSerializer {
             TDFToken::IntListStart(s) => s,
             _ => bail!("Expected Int List start, found {:?}", token),
         };
-        
+
         let mut output = String::new();
-        output.push_str("[");
+        output.push('[');
 
         for i in 0..size {
             output.push_str(&self.ser_int()?);
            if i != size-1 {
-                output.push_str(",");
+                output.push(',');
            }
         }
-        output.push_str("]");
+        output.push(']');
 
         let end_token = self.stream.next()?;
         if end_token != TDFToken::IntListEnd {
             bail!("Expected End of Int List, found {:?}", end_token)
         }
-        
+
         Ok(output)
     }
 
-    
+
     pub fn ser_object_type(&mut self) -> Result<String> {
 
-        let mut output = String::new();
-        output.push_str("[");
-        output.push_str(&self.ser_int()?);
-        output.push_str(",");
-        output.push_str(&self.ser_int()?);
-        output.push_str("]");
+        let object_type = ObjectType(self.read_int()?, self.read_int()?);
 
-        Ok(output)
+        Ok(quote(&self.registry.format_type(object_type)))
     }
 
     pub fn ser_object_id(&mut self) -> Result<String> {
 
-        let mut output = String::new();
-        output.push_str("[");
-        output.push_str(&self.ser_int()?);
-        output.push_str(",");
-        output.push_str(&self.ser_int()?);
-        output.push_str(",");
-        output.push_str(&self.ser_int()?);
-        output.push_str("]");
+        let object_id = ObjectId(self.read_int()?, self.read_int()?, self.read_int()?);
 
-        Ok(output)
+        Ok(quote(&self.registry.format_id(object_id)))
+    }
+
+    fn read_int(&mut self) -> Result<i64> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::Int(number) => Ok(number),
+            _=> bail!("Expected Integer, found {:?}", token),
+        }
     }
 
     pub fn ser_float(&mut self) -> Result<String> {
@@ -398,7 +562,12 @@ impl JsonSerializer {
             _ => bail!("Expected Int List start, found {:?}", token),
         };
 
-        Ok(format!("{}", number))
+        // NaN and infinities have no json form
+        if !number.is_finite() {
+            return Ok("null".to_string());
+        }
+        // Debug form keeps fraction or exponent, so whole floats aren't read back as Int
+        Ok(format!("{:?}", number))
     }
 
     pub fn write_number(&self, number: i64) -> Result<String> {
@@ -406,28 +575,34 @@ impl JsonSerializer {
     }
 
     pub fn write_string(&self, string: Vec<u8>) -> Result<String> {
-        Ok(format!("{:?}", String::from_utf8_lossy(&string).to_string()))
+        Ok(quote(&String::from_utf8_lossy(&string)))
     }
 
     pub fn write_blob(&self, blob: Vec<u8>) -> Result<String> {
-        Ok(format!("\"Bytes{:x?}\"", blob))
+        Ok(match self.options.blob {
+            BlobEncoding::Bytes => format!("\"Bytes{:x?}\"", blob),
+            BlobEncoding::Hex => format!("\"{}\"", hex(&blob, "")),
+            BlobEncoding::Base64 => format!("\"{}\"", base64(&blob)),
+            BlobEncoding::Array => {
+                let bytes: Vec<String> = blob.iter().map(|b| b.to_string()).collect();
+                format!("[{}]", bytes.join(self.comma()))
+            },
+        })
     }
 
-    pub fn write_label(&self, label: &String) -> Result<String> {
-        let mut label = label.to_lowercase();
-        label.retain(|c| !c.is_ascii_whitespace());
-        Ok(format!("\"{}\"", label))
+    /// Label without trailing spaces, inner spaces are written as _
+    pub fn write_label(&self, label: &str) -> Result<String> {
+        let label = label.trim_end().replace(' ', "_");
+        let label = match self.options.label_case {
+            LabelCase::Lower => label.to_lowercase(),
+            LabelCase::Upper => label.to_uppercase(),
+        };
+        Ok(quote(&label))
     }
 }
 
 impl TDFSerializer<String> for JsonSerializer {
     fn serialize(stream: TDFTokenStream, writer: &mut String) -> Result<()> {
-        let mut des = Self {
-            stream
-        };
-        let token = des.stream.next()?;
-        let str_res = des.ser_token(token, 0)?;
-        writer.insert_str(writer.len(), &str_res);
-        Ok(())
+        Self::new(stream).write_json(writer)
     }
-}
\ No newline at end of file
+}
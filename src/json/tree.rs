@@ -0,0 +1,305 @@
+/*
+    Minimal JSON tree shared by JSON deserializers and writers
+*/
+
+use anyhow::{Result, bail};
+use std::fmt::Write;
+
+
+/// Parsed JSON, fields are kept in order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Number as it is written
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::Number(n) if !n.contains(['.', 'e', 'E']))
+    }
+
+    pub fn field(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn is_scalar(&self) -> bool {
+        !matches!(self, Self::Array(_) | Self::Object(_))
+    }
+
+    /// Written on one line
+    fn is_inline(&self) -> bool {
+        match self {
+            Self::Array(items) => items.iter().all(|i| i.is_scalar()),
+            Self::Object(fields) => fields.is_empty() || (fields.len() == 1 && fields[0].1.is_inline() && !matches!(fields[0].1, Self::Object(_))),
+            _ => true,
+        }
+    }
+
+    /// Write JSON, empty indent gives compact output
+    pub fn write(&self, out: &mut String, indent: &str, level: usize) {
+        let (space, new_line) = if indent.is_empty() { ("", "") } else { (" ", "\n") };
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Self::Number(n) => out.push_str(n),
+            Self::String(s) => out.push_str(&quote(s)),
+            Self::Array(items) if items.is_empty() => out.push_str("[]"),
+            // Arrays of scalars stay on one line
+            Self::Array(items) if self.is_inline() => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                        out.push_str(space);
+                    }
+                    item.write(out, indent, level + 1);
+                }
+                out.push(']');
+            },
+            Self::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    out.push_str(new_line);
+                    out.push_str(&indent.repeat(level + 1));
+                    item.write(out, indent, level + 1);
+                }
+                out.push_str(new_line);
+                out.push_str(&indent.repeat(level));
+                out.push(']');
+            },
+            Self::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            // Single field objects, like tagged values, stay on one line
+            Self::Object(fields) if self.is_inline() => {
+                out.push('{');
+                out.push_str(&quote(&fields[0].0));
+                out.push(':');
+                out.push_str(space);
+                fields[0].1.write(out, indent, level + 1);
+                out.push('}');
+            },
+            Self::Object(fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    out.push_str(new_line);
+                    out.push_str(&indent.repeat(level + 1));
+                    out.push_str(&quote(key));
+                    out.push(':');
+                    out.push_str(space);
+                    value.write(out, indent, level + 1);
+                }
+                out.push_str(new_line);
+                out.push_str(&indent.repeat(level));
+                out.push('}');
+            },
+        }
+    }
+}
+
+/// JSON string literal, control chars are escaped as \uXXXX
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+struct JsonParser {
+    input: Vec<char>,
+    cursor: usize,
+}
+
+/// Parse JSON text, fields of objects are kept in order, duplicates included
+pub(crate) fn parse_json(input: &str) -> Result<JsonValue> {
+    let mut parser = JsonParser {
+        input: input.chars().collect(),
+        cursor: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_space();
+    if parser.cursor < parser.input.len() {
+        bail!("Unexpected {:?} after JSON at {}", parser.input[parser.cursor], parser.cursor);
+    }
+    Ok(value)
+}
+
+impl JsonParser {
+
+    fn skip_space(&mut self) {
+        while self.input.get(self.cursor).is_some_and(|c| c.is_whitespace()) {
+            self.cursor += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.input.get(self.cursor).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.cursor += 1;
+                Ok(())
+            },
+            Some(c) => bail!("Expected {:?}, found {:?} at {}", expected, c, self.cursor),
+            None => bail!("Expected {:?}, found end of JSON", expected),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue> {
+        match self.peek() {
+            Some('{') => {
+                self.cursor += 1;
+                let mut fields = Vec::new();
+                if self.peek() == Some('}') {
+                    self.cursor += 1;
+                    return Ok(JsonValue::Object(fields));
+                }
+                loop {
+                    let key = self.parse_string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.parse_value()?));
+                    match self.peek() {
+                        Some(',') => self.cursor += 1,
+                        _ => break,
+                    }
+                }
+                self.expect('}')?;
+                Ok(JsonValue::Object(fields))
+            },
+            Some('[') => {
+                self.cursor += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(']') {
+                    self.cursor += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    match self.peek() {
+                        Some(',') => self.cursor += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(']')?;
+                Ok(JsonValue::Array(items))
+            },
+            Some('"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.cursor;
+                while self.input.get(self.cursor).is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                    self.cursor += 1;
+                }
+                Ok(JsonValue::Number(self.input[start..self.cursor].iter().collect()))
+            },
+            Some(_) => {
+                let start = self.cursor;
+                while self.input.get(self.cursor).is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.cursor += 1;
+                }
+                let word: String = self.input[start..self.cursor].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(JsonValue::Bool(true)),
+                    "false" => Ok(JsonValue::Bool(false)),
+                    "null" => Ok(JsonValue::Null),
+                    _ => bail!("Unexpected {:?} at {}", self.input[start], start),
+                }
+            },
+            None => bail!("Expected value, found end of JSON"),
+        }
+    }
+
+    fn read_hex(&mut self, size: usize) -> Result<u32> {
+        let hex: String = self.input.iter().skip(self.cursor).take(size).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(code) if hex.len() == size => {
+                self.cursor += size;
+                Ok(code)
+            },
+            _ => bail!("Bad unicode escape {:?} at {}", hex, self.cursor),
+        }
+    }
+
+    /// String with JSON escapes, Rust style \u{..} is accepted as well
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let c = match self.input.get(self.cursor) {
+                Some(c) => *c,
+                None => bail!("Unterminated string"),
+            };
+            self.cursor += 1;
+            if c == '"' {
+                return Ok(s);
+            }
+            if c != '\\' {
+                s.push(c);
+                continue;
+            }
+            let escaped = match self.input.get(self.cursor) {
+                Some(c) => *c,
+                None => bail!("Unterminated string"),
+            };
+            self.cursor += 1;
+            match escaped {
+                'n' => s.push('\n'),
+                'r' => s.push('\r'),
+                't' => s.push('\t'),
+                'b' => s.push('\u{8}'),
+                'f' => s.push('\u{c}'),
+                'u' if self.input.get(self.cursor) == Some(&'{') => {
+                    let end = match self.input[self.cursor..].iter().position(|c| *c == '}') {
+                        Some(end) => self.cursor + end,
+                        None => bail!("Unterminated unicode escape at {}", self.cursor),
+                    };
+                    let hex: String = self.input[self.cursor + 1..end].iter().collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(c) => s.push(c),
+                        None => bail!("Bad unicode escape {:?} at {}", hex, self.cursor),
+                    }
+                    self.cursor = end + 1;
+                },
+                'u' => {
+                    let mut code = self.read_hex(4)?;
+                    // Surrogate pair
+                    if (0xD800..0xDC00).contains(&code) && self.input.get(self.cursor) == Some(&'\\') && self.input.get(self.cursor + 1) == Some(&'u') {
+                        self.cursor += 2;
+                        let low = self.read_hex(4)?;
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                    }
+                    s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                },
+                c => s.push(c),
+            }
+        }
+    }
+}
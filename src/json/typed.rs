@@ -0,0 +1,509 @@
+/*
+    Lossless typed JSON form of TDF
+
+    Values are tagged with TDF type where JSON can't tell it,
+    so binary converted to typed JSON and back gives the same bytes.
+    Tags start with $, which can't appear in labels.
+
+    Int             5
+    String          "text", or {"$bytes": "ff00"} if not valid UTF-8
+    Map             {"GID": 5, "NAME": "a"}, keys are labels without trailing spaces,
+                    {"$map_union": true} entry stands for map union marker
+    List            [1, 2], type of elements is taken from the first one,
+                    empty list is {"$list": "Int", "items": []}
+    PairList        {"$pair_list": ["String", "Int"], "pairs": [["a", 1]]}
+    Blob            {"$blob": "0102"}
+    Float           {"$float": 1.5}, NaN and infinities by raw bits {"$f32bits": "7fc00000"}
+    Time            {"$time": 1600000000}
+    IntList         {"$int_list": [1, 2]}
+    ObjectType      {"$object_type": [4, 1]}
+    ObjectId        {"$object_id": [4, 1, 9]}
+    Union           {"$union": 2, "VALU": value}, {"$union": 127} is unset
+    Generic         {"$generic": 42, "VALU": value}, {"$generic": 42}, {"$generic": null}
+
+    Type names are the ones of text notation. Map union marker byte
+    is always written as 2 and numbers are encoded in shortest form,
+    as the binary serializer does.
+*/
+
+use crate::token::*;
+use crate::text::{type_name, type_from_name};
+use crate::hex::{hex, unhex};
+use anyhow::{Result, bail};
+use std::convert::TryFrom;
+
+use super::tree::{JsonValue, parse_json};
+
+
+/// Writes token stream as typed JSON
+pub struct TypedJsonSerializer {
+    stream: TDFTokenStream,
+    indent: String,
+}
+
+/// Reads typed JSON into token stream
+pub struct TypedJsonDeserializer {
+    pub stream: TDFTokenStream,
+    input: String,
+}
+
+fn tagged(tag: &str, value: JsonValue) -> JsonValue {
+    JsonValue::Object(vec![(tag.to_string(), value)])
+}
+
+fn number<N: ToString>(n: N) -> JsonValue {
+    JsonValue::Number(n.to_string())
+}
+
+impl TypedJsonSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream,
+            indent: "    ".to_string(),
+        }
+    }
+
+    /// Indentation of nested values, empty one gives compact JSON
+    pub fn with_indent<S: Into<String>>(mut self, indent: S) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    /// Write whole stream as typed JSON into the writer
+    pub fn write_json(&mut self, writer: &mut String) -> Result<()> {
+        let token = self.stream.next()?;
+        let value = self.ser_token(token)?;
+        value.write(writer, &self.indent, 0);
+        Ok(())
+    }
+
+    fn read_int(&mut self) -> Result<i64> {
+        match self.stream.next()? {
+            TDFToken::Int(n) => Ok(n),
+            token => bail!("Expected Integer, found {:?}", token),
+        }
+    }
+
+    /// Label and value of union or generic member
+    fn ser_member(&mut self, fields: &mut Vec<(String, JsonValue)>) -> Result<()> {
+        let label = match self.stream.next()? {
+            TDFToken::Label(label) => label,
+            token => bail!("Expected Label, found {:?}", token),
+        };
+        let value_type = self.stream.next()?;
+        fields.push((label.trim_end().to_string(), self.ser_token(value_type)?));
+        Ok(())
+    }
+
+    fn expect_end(&mut self, end: TDFToken) -> Result<()> {
+        let token = self.stream.next()?;
+        if token != end {
+            bail!("Expected {:?}, found {:?}", end, token);
+        }
+        Ok(())
+    }
+
+    fn ser_token(&mut self, token_type: TDFToken) -> Result<JsonValue> {
+        Ok(match token_type {
+            TDFToken::IntType => number(self.read_int()?),
+            TDFToken::TimeType => tagged("$time", number(self.read_int()?)),
+            TDFToken::StringType => match self.stream.next()? {
+                TDFToken::String(bytes) => match String::from_utf8(bytes) {
+                    Ok(s) => JsonValue::String(s),
+                    Err(e) => tagged("$bytes", JsonValue::String(hex(e.as_bytes(), ""))),
+                },
+                token => bail!("Expected String, found {:?}", token),
+            },
+            TDFToken::BlobType => match self.stream.next()? {
+                TDFToken::Blob(blob) => tagged("$blob", JsonValue::String(hex(&blob, ""))),
+                token => bail!("Expected Blob, found {:?}", token),
+            },
+            TDFToken::FloatType => match self.stream.next()? {
+                TDFToken::Float(f) if f.is_finite() => tagged("$float", number(format!("{:?}", f))),
+                // Keeps NaN payload
+                TDFToken::Float(f) => tagged("$f32bits", JsonValue::String(format!("{:08x}", f.to_bits()))),
+                token => bail!("Expected Float, found {:?}", token),
+            },
+            TDFToken::MapType => {
+                self.expect_end(TDFToken::MapStart)?;
+                let mut fields = Vec::new();
+                loop {
+                    match self.stream.next()? {
+                        TDFToken::MapEnd => break,
+                        TDFToken::MapUnion => fields.push(("$map_union".to_string(), JsonValue::Bool(true))),
+                        TDFToken::Label(label) => {
+                            let value_type = self.stream.next()?;
+                            fields.push((label.trim_end().to_string(), self.ser_token(value_type)?));
+                        },
+                        token => bail!("Expected Label in Map, found {:?}", token),
+                    }
+                }
+                JsonValue::Object(fields)
+            },
+            TDFToken::ListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::ListStart(size) => size,
+                    token => bail!("Expected List, found {:?}", token),
+                };
+                let item_type = self.stream.next()?;
+                let mut items = Vec::with_capacity(size);
+                for _ in 0..size {
+                    items.push(self.ser_token(item_type.clone())?);
+                }
+                self.expect_end(TDFToken::ListEnd)?;
+                if items.is_empty() {
+                    JsonValue::Object(vec![
+                        ("$list".to_string(), JsonValue::String(type_name(&item_type)?.to_string())),
+                        ("items".to_string(), JsonValue::Array(items)),
+                    ])
+                } else {
+                    JsonValue::Array(items)
+                }
+            },
+            TDFToken::PairListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::PairListStart(size) => size,
+                    token => bail!("Expected Pair list, found {:?}", token),
+                };
+                let key_type = self.stream.next()?;
+                let value_type = self.stream.next()?;
+                let mut pairs = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let key = self.ser_token(key_type.clone())?;
+                    let value = self.ser_token(value_type.clone())?;
+                    pairs.push(JsonValue::Array(vec![key, value]));
+                }
+                self.expect_end(TDFToken::PairListEnd)?;
+                JsonValue::Object(vec![
+                    ("$pair_list".to_string(), JsonValue::Array(vec![
+                        JsonValue::String(type_name(&key_type)?.to_string()),
+                        JsonValue::String(type_name(&value_type)?.to_string()),
+                    ])),
+                    ("pairs".to_string(), JsonValue::Array(pairs)),
+                ])
+            },
+            TDFToken::UnionType => {
+                let union_type = match self.stream.next()? {
+                    TDFToken::UnionStart(union_type) => union_type,
+                    token => bail!("Expected Union start, found {:?}", token),
+                };
+                let mut fields = vec![("$union".to_string(), number(union_type as u8))];
+                if union_type != UnionType::Unset {
+                    self.ser_member(&mut fields)?;
+                }
+                self.expect_end(TDFToken::UnionEnd)?;
+                JsonValue::Object(fields)
+            },
+            TDFToken::GenericType => {
+                let valid = match self.stream.next()? {
+                    TDFToken::GenericStart(valid) => valid,
+                    token => bail!("Expected Generic start, found {:?}", token),
+                };
+                if !valid {
+                    self.expect_end(TDFToken::GenericEnd)?;
+                    return Ok(tagged("$generic", JsonValue::Null));
+                }
+                let mut fields = vec![("$generic".to_string(), number(self.read_int()?))];
+                if !matches!(self.stream.get(self.stream.1)?, TDFToken::GenericEnd) {
+                    self.ser_member(&mut fields)?;
+                }
+                self.expect_end(TDFToken::GenericEnd)?;
+                JsonValue::Object(fields)
+            },
+            TDFToken::IntListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::IntListStart(size) => size,
+                    token => bail!("Expected Int List start, found {:?}", token),
+                };
+                let mut ints = Vec::with_capacity(size);
+                for _ in 0..size {
+                    ints.push(number(self.read_int()?));
+                }
+                self.expect_end(TDFToken::IntListEnd)?;
+                tagged("$int_list", JsonValue::Array(ints))
+            },
+            TDFToken::ObjectTypeType => tagged("$object_type", JsonValue::Array(vec![
+                number(self.read_int()?),
+                number(self.read_int()?),
+            ])),
+            TDFToken::ObjectIdType => tagged("$object_id", JsonValue::Array(vec![
+                number(self.read_int()?),
+                number(self.read_int()?),
+                number(self.read_int()?),
+            ])),
+            _ => bail!("Trying to parse type token, but found {:?}", token_type),
+        })
+    }
+}
+
+/// Typed JSON of single value, stream starts with its type token
+pub(crate) fn typed_value(stream: TDFTokenStream) -> Result<JsonValue> {
+    let mut ser = TypedJsonSerializer::new(stream);
+    let token = ser.stream.next()?;
+    ser.ser_token(token)
+}
+
+impl TDFSerializer<String> for TypedJsonSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut String) -> Result<()> {
+        Self::new(stream).write_json(writer)
+    }
+}
+
+/// Tag of typed object, first key starting with $
+fn tag_of(value: &JsonValue) -> Option<&str> {
+    match value {
+        JsonValue::Object(fields) => fields.first()
+            .map(|(k, _)| k.as_str())
+            .filter(|k| k.starts_with('$') && *k != "$map_union"),
+        _ => None,
+    }
+}
+
+fn read_int(value: &JsonValue) -> Result<i64> {
+    match value {
+        JsonValue::Number(n) => match n.parse::<i64>() {
+            Ok(n) => Ok(n),
+            Err(_) => bail!("Expected integer, found {}", n),
+        },
+        _ => bail!("Expected integer, found {:?}", value),
+    }
+}
+
+fn read_ints(value: &JsonValue, count: Option<usize>) -> Result<Vec<i64>> {
+    let ints = match value {
+        JsonValue::Array(items) => items.iter().map(read_int).collect::<Result<Vec<_>>>()?,
+        _ => bail!("Expected array of integers, found {:?}", value),
+    };
+    if count.is_some_and(|count| count != ints.len()) {
+        bail!("Expected {} integers, found {:?}", count.unwrap_or_default(), value);
+    }
+    Ok(ints)
+}
+
+fn read_type(value: Option<&JsonValue>) -> Result<TDFToken> {
+    match value {
+        Some(JsonValue::String(name)) => type_from_name(name),
+        _ => bail!("Expected type name, found {:?}", value),
+    }
+}
+
+fn wire_label(key: &str) -> Result<String> {
+    if key.chars().count() > 4 {
+        bail!("Label {:?} is longer than 4 chars", key);
+    }
+    Ok(format!("{:<4}", key.to_uppercase()))
+}
+
+/// Type of typed JSON value
+fn typed_json_type(value: &JsonValue) -> Result<TDFToken> {
+    Ok(match (value, tag_of(value)) {
+        (JsonValue::Number(_), _) => TDFToken::IntType,
+        (JsonValue::String(_), _) => TDFToken::StringType,
+        (JsonValue::Array(_), _) => TDFToken::ListType,
+        (JsonValue::Object(_), None) => TDFToken::MapType,
+        (_, Some("$bytes")) => TDFToken::StringType,
+        (_, Some("$blob")) => TDFToken::BlobType,
+        (_, Some("$float")) | (_, Some("$f32bits")) => TDFToken::FloatType,
+        (_, Some("$time")) => TDFToken::TimeType,
+        (_, Some("$list")) => TDFToken::ListType,
+        (_, Some("$pair_list")) => TDFToken::PairListType,
+        (_, Some("$union")) => TDFToken::UnionType,
+        (_, Some("$generic")) => TDFToken::GenericType,
+        (_, Some("$int_list")) => TDFToken::IntListType,
+        (_, Some("$object_type")) => TDFToken::ObjectTypeType,
+        (_, Some("$object_id")) => TDFToken::ObjectIdType,
+        (_, Some(tag)) => bail!("Unknown tag {:?}", tag),
+        _ => bail!("Can't convert {:?} to TDF", value),
+    })
+}
+
+impl TypedJsonDeserializer {
+
+    pub fn new(input: &str) -> Self {
+        Self {
+            stream: TDFTokenStream::new(),
+            input: input.to_string(),
+        }
+    }
+
+    /// Parse whole input, root value must be a map
+    pub fn des_json(&mut self) -> Result<()> {
+        let root = parse_json(&self.input)?;
+        if typed_json_type(&root)? != TDFToken::MapType {
+            bail!("Root of typed JSON must be a map");
+        }
+        let mut tokens = vec![TDFToken::MapType];
+        des_value(&root, &TDFToken::MapType, &mut tokens)?;
+        for token in tokens {
+            self.stream.push(token);
+        }
+        Ok(())
+    }
+}
+
+/// Type and tokens of single typed JSON value
+pub(crate) fn typed_tokens(value: &JsonValue) -> Result<(TDFToken, TDFTokenStream)> {
+    let value_type = typed_json_type(value)?;
+    let mut tokens = Vec::new();
+    des_value(value, &value_type, &mut tokens)?;
+    Ok((value_type, TDFTokenStream(tokens, 0)))
+}
+
+/// Label, type and value of map field or union/generic member
+fn des_member(key: &str, value: &JsonValue, out: &mut Vec<TDFToken>) -> Result<()> {
+    let value_type = typed_json_type(value)?;
+    out.push(TDFToken::Label(wire_label(key)?));
+    out.push(value_type.clone());
+    des_value(value, &value_type, out)
+}
+
+/// Fields of tagged object other than the tag
+fn members(value: &JsonValue) -> &[(String, JsonValue)] {
+    match value {
+        JsonValue::Object(fields) if !fields.is_empty() => &fields[1..],
+        _ => &[],
+    }
+}
+
+fn des_value(value: &JsonValue, value_type: &TDFToken, out: &mut Vec<TDFToken>) -> Result<()> {
+
+    let actual = typed_json_type(value)?;
+    if actual != *value_type {
+        bail!("Expected {:?}, found {:?}", value_type, value);
+    }
+    let tag = tag_of(value).and_then(|tag| value.field(tag));
+
+    match (value_type, value) {
+        (TDFToken::IntType, _) => out.push(TDFToken::Int(read_int(value)?)),
+        (TDFToken::StringType, JsonValue::String(s)) => out.push(TDFToken::String(s.as_bytes().to_vec())),
+        (TDFToken::StringType, _) | (TDFToken::BlobType, _) => {
+            let bytes = match tag {
+                Some(JsonValue::String(s)) => unhex(s)?,
+                _ => bail!("Expected hex string, found {:?}", value),
+            };
+            out.push(match value_type {
+                TDFToken::BlobType => TDFToken::Blob(bytes),
+                _ => TDFToken::String(bytes),
+            });
+        },
+        (TDFToken::FloatType, _) => {
+            let f = match (tag_of(value), tag) {
+                (Some("$f32bits"), Some(JsonValue::String(bits))) => u32::from_str_radix(bits, 16).ok().map(f32::from_bits),
+                (Some("$float"), Some(JsonValue::Number(n))) | (Some("$float"), Some(JsonValue::String(n))) => n.parse::<f32>().ok(),
+                _ => None,
+            };
+            match f {
+                Some(f) => out.push(TDFToken::Float(f)),
+                None => bail!("Expected float, found {:?}", value),
+            }
+        },
+        (TDFToken::TimeType, _) => out.push(TDFToken::Int(read_int(tag.unwrap_or(value))?)),
+        (TDFToken::MapType, JsonValue::Object(fields)) => {
+            out.push(TDFToken::MapStart);
+            for (key, field) in fields {
+                if key == "$map_union" {
+                    out.push(TDFToken::MapUnion);
+                    continue;
+                }
+                des_member(key, field, out)?;
+            }
+            out.push(TDFToken::MapEnd);
+        },
+        (TDFToken::ListType, _) => {
+            let (item_type, items) = match value {
+                JsonValue::Array(items) => match items.first() {
+                    Some(first) => (typed_json_type(first)?, items),
+                    None => bail!("Empty list must be written as {{\"$list\": type, \"items\": []}}"),
+                },
+                _ => match value.field("items") {
+                    Some(JsonValue::Array(items)) => (read_type(tag)?, items),
+                    _ => bail!("Expected \"items\" array in List, found {:?}", value),
+                },
+            };
+            out.push(TDFToken::ListStart(items.len()));
+            out.push(item_type.clone());
+            for item in items {
+                des_value(item, &item_type, out)?;
+            }
+            out.push(TDFToken::ListEnd);
+        },
+        (TDFToken::PairListType, _) => {
+            let (key_type, item_type) = match tag {
+                Some(JsonValue::Array(types)) if types.len() == 2 => (read_type(types.first())?, read_type(types.get(1))?),
+                _ => bail!("Expected [key type, value type] in Pair list, found {:?}", value),
+            };
+            let pairs = match value.field("pairs") {
+                Some(JsonValue::Array(pairs)) => pairs,
+                _ => bail!("Expected \"pairs\" array in Pair list, found {:?}", value),
+            };
+            out.push(TDFToken::PairListStart(pairs.len()));
+            out.push(key_type.clone());
+            out.push(item_type.clone());
+            for pair in pairs {
+                match pair {
+                    JsonValue::Array(pair) if pair.len() == 2 => {
+                        des_value(&pair[0], &key_type, out)?;
+                        des_value(&pair[1], &item_type, out)?;
+                    },
+                    _ => bail!("Expected [key, value] pair, found {:?}", pair),
+                }
+            }
+            out.push(TDFToken::PairListEnd);
+        },
+        (TDFToken::UnionType, _) => {
+            let index = match tag {
+                Some(index) => u8::try_from(read_int(index)?)?,
+                None => bail!("Expected union index, found {:?}", value),
+            };
+            let union_type = UnionType::from_tag(index);
+            if union_type == UnionType::Unset && index != UnionType::Unset as u8 {
+                bail!("Unknown union index {}", index);
+            }
+            out.push(TDFToken::UnionStart(union_type));
+            match (union_type, members(value)) {
+                (UnionType::Unset, []) => {},
+                (UnionType::Unset, _) => bail!("Unset union can't have a member, found {:?}", value),
+                (_, [(label, member)]) => des_member(label, member, out)?,
+                _ => bail!("Expected single member in Union, found {:?}", value),
+            }
+            out.push(TDFToken::UnionEnd);
+        },
+        (TDFToken::GenericType, _) => {
+            match tag {
+                Some(JsonValue::Null) => out.push(TDFToken::GenericStart(false)),
+                Some(id) => {
+                    out.push(TDFToken::GenericStart(true));
+                    out.push(TDFToken::Int(read_int(id)?));
+                    match members(value) {
+                        [] => {},
+                        [(label, member)] => des_member(label, member, out)?,
+                        _ => bail!("Expected at most one member in Generic, found {:?}", value),
+                    }
+                },
+                None => bail!("Expected tdf id in Generic, found {:?}", value),
+            }
+            out.push(TDFToken::GenericEnd);
+        },
+        (TDFToken::IntListType, _) => {
+            let ints = read_ints(tag.unwrap_or(value), None)?;
+            out.push(TDFToken::IntListStart(ints.len()));
+            out.extend(ints.into_iter().map(TDFToken::Int));
+            out.push(TDFToken::IntListEnd);
+        },
+        (TDFToken::ObjectTypeType, _) | (TDFToken::ObjectIdType, _) => {
+            let count = if *value_type == TDFToken::ObjectTypeType { 2 } else { 3 };
+            out.extend(read_ints(tag.unwrap_or(value), Some(count))?.into_iter().map(TDFToken::Int));
+        },
+        _ => bail!("Can't convert {:?} to {:?}", value, value_type),
+    }
+    Ok(())
+}
+
+impl TDFDeserializer<String> for TypedJsonDeserializer {
+    fn deserialize(reader: &mut String) -> Result<TDFTokenStream> {
+        let mut des = Self::new(reader);
+        des.des_json()?;
+        Ok(des.stream)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::token::TDFToken;
+use crate::rtdf::{TdfSchema, StructSchema};
+use anyhow::{Result, bail};
+
+use super::ser::{JsonOptions, BlobEncoding};
+use super::tree::quote;
+use super::fields::field_key;
+
+
+/// Writes TypeScript definitions (.d.ts) of the JSON that JsonSerializer
+/// writes for a struct, following the same options
+pub struct TypeScriptWriter {
+    options: JsonOptions,
+    field_names: bool,
+}
+
+/// Types without struct schema, written once before the interfaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Helper {
+    ObjectType,
+    ObjectId,
+    Union,
+    Generic,
+}
+
+impl Helper {
+    fn definition(self) -> &'static str {
+        match self {
+            Helper::ObjectType => "/** Object type written as \"component/type\" */\nexport type ObjectType = string;\n",
+            Helper::ObjectId => "/** Object id written as \"component/type/id\" */\nexport type ObjectId = string;\n",
+            Helper::Union => "/** Network union, tag and value are left out when unset */\nexport type TdfUnion =\n    | { type: \"union\"; union: 0 | 1 | 2 | 3 | 4; tag: string; value: unknown }\n    | { type: \"union\"; union: 127 };\n",
+            Helper::Generic => "/** Generic value keyed by its label, id is left out when invalid */\nexport type TdfGeneric = {\n    type: \"generic\";\n    id?: number;\n    [label: string]: { value: unknown } | string | number | undefined;\n};\n",
+        }
+    }
+}
+
+/// Key as property name, quoted when it isn't an identifier
+fn property(key: &str) -> String {
+    let identifier = key.chars().enumerate()
+        .all(|(i, c)| c == '_' || c == '$' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+    if identifier && !key.is_empty() { key.to_string() } else { quote(key) }
+}
+
+impl TypeScriptWriter {
+
+    pub fn new() -> Self {
+        Self {
+            options: JsonOptions::default(),
+            field_names: false,
+        }
+    }
+
+    /// Options of JsonSerializer the JSON is written with
+    pub fn with_options(mut self, options: JsonOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keys are Rust field names, like in JsonSerializer::with_field_names
+    pub fn with_field_names(mut self) -> Self {
+        self.field_names = true;
+        self
+    }
+
+    /// Write interfaces of root struct and every nested struct
+    pub fn write_definitions(&self, schema: &TdfSchema, writer: &mut String) -> Result<()> {
+        if !matches!(schema, TdfSchema::Struct(_)) {
+            bail!("Expected struct schema as root, found {:?}", schema);
+        }
+        let mut helpers = Vec::new();
+        let interfaces: Vec<String> = schema.structs().into_iter()
+            .map(|s| self.interface(s, &mut helpers))
+            .collect();
+        helpers.sort();
+        helpers.dedup();
+        let mut parts: Vec<&str> = helpers.iter().map(|h| h.definition()).collect();
+        parts.extend(interfaces.iter().map(|i| i.as_str()));
+        writer.push_str(&parts.join("\n"));
+        Ok(())
+    }
+
+    fn interface(&self, schema: &StructSchema, helpers: &mut Vec<Helper>) -> String {
+        let mut output = format!("export interface {} {{\n", schema.name);
+        for field in &schema.fields {
+            let key = field_key(&self.options, self.field_names, &field.name, &field.label);
+            let optional = if matches!(field.schema, TdfSchema::Optional(_)) { "?" } else { "" };
+            output.push_str(&format!("    {}{}: {};\n", property(&key), optional, self.value_type(&field.schema, helpers)));
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    fn value_type(&self, schema: &TdfSchema, helpers: &mut Vec<Helper>) -> String {
+        match schema {
+            TdfSchema::Value(token) => self.token_type(token, helpers),
+            TdfSchema::Bool => "0 | 1".to_string(),
+            TdfSchema::Struct(StructSchema { name, .. }) | TdfSchema::Ref(name) => name.clone(),
+            TdfSchema::List(item) => {
+                let item = self.value_type(item, helpers);
+                if item.contains('|') { format!("({})[]", item) } else { format!("{}[]", item) }
+            },
+            TdfSchema::PairList(key, value) => {
+                let value = self.value_type(value, helpers);
+                if self.options.pair_list_objects && key.type_token() == TDFToken::StringType {
+                    return format!("Record<string, {}>", value);
+                }
+                format!("[{}, {}][]", self.value_type(key, helpers), value)
+            },
+            TdfSchema::Optional(inner) => self.value_type(inner, helpers),
+        }
+    }
+
+    /// Type of value known only by type token
+    fn token_type(&self, token: &TDFToken, helpers: &mut Vec<Helper>) -> String {
+        let helper = |helper: Helper, helpers: &mut Vec<Helper>| {
+            helpers.push(helper);
+            match helper {
+                Helper::ObjectType => "ObjectType",
+                Helper::ObjectId => "ObjectId",
+                Helper::Union => "TdfUnion",
+                Helper::Generic => "TdfGeneric",
+            }
+        };
+        match token {
+            TDFToken::IntType | TDFToken::TimeType => "number",
+            TDFToken::StringType => "string",
+            // NaN and infinities are written as null
+            TDFToken::FloatType => "number | null",
+            TDFToken::BlobType => match self.options.blob {
+                BlobEncoding::Array => "number[]",
+                _ => "string",
+            },
+            TDFToken::IntListType => "number[]",
+            TDFToken::ObjectTypeType => helper(Helper::ObjectType, helpers),
+            TDFToken::ObjectIdType => helper(Helper::ObjectId, helpers),
+            TDFToken::UnionType => helper(Helper::Union, helpers),
+            TDFToken::GenericType => helper(Helper::Generic, helpers),
+            TDFToken::MapType => "Record<string, unknown>",
+            TDFToken::ListType | TDFToken::PairListType => "unknown[]",
+            _ => "unknown",
+        }.to_string()
+    }
+}
+
+impl Default for TypeScriptWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
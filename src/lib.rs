@@ -3,6 +3,20 @@ pub mod token;
 pub mod btdf;
 pub mod rtdf;
 pub mod json;
+pub mod value;
+pub mod text;
+pub mod xml;
+pub mod csv;
+pub mod wireshark;
+#[cfg(feature = "serde")]
+pub mod serde_tdf;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+mod hex;
 //pub mod auto;
 
 extern crate macro_tdf;
@@ -15,11 +29,18 @@ pub mod prelude {
     pub use macro_tdf::*;
 
     // Ser/des rust tdf
-    pub use crate::rtdf::{Generic, GenericContent, GenericType, RTDFDeserializer, RTDFSerializer, Deserialize, Serialize, StructConstructor, ObjectType, ObjectId, IntList, Union, Localization, IpAddress};
+    pub use crate::rtdf::{Generic, GenericContent, GenericType, RTDFDeserializer, RTDFSerializer, Deserialize, Serialize, StructConstructor, ObjectType, ObjectId, ObjectRegistry, IntList, Union, Localization, IpAddress, TdfSchema, StructSchema, FieldSchema};
 
     // Ser/des defenitions
     pub use crate::token::{TDFSerializer, TDFDeserializer, TDFTokenStream, TDFToken};
 
+    // Dynamic values
+    pub use crate::value::{TdfValue, TdfMap, TdfGeneric, TdfPath, QueryMatch, TdfDiff, DiffEntry, DiffKind, DiffOptions, TdfPatch, PatchOp};
+    pub use crate::tdf;
+
+    // Lazy access to binary
+    pub use crate::btdf::{TdfIndex, IndexNode};
+
     // Important for results in des/ser
     pub use anyhow::Result;
 
@@ -27,8 +48,12 @@ pub mod prelude {
 
 
 use btdf::{BTDFDeserializer, BTDFSerializer};
-use json::JsonSerializer;
-use rtdf::{Deserialize, RTDFSerializer, Serialize, StructConstructor, RTDFDeserializer};
+use json::{JsonOptions, JsonSerializer, JsonDeserializer, TypedJsonSerializer, TypedJsonDeserializer, JsonSchemaWriter, TypeScriptWriter};
+use xml::{XmlSerializer, XmlDeserializer};
+use csv::{CsvSerializer, CsvOptions};
+use text::{TextSerializer, TextDeserializer, TextOptions};
+use rtdf::{Deserialize, RTDFSerializer, Serialize, StructConstructor, RTDFDeserializer, ObjectRegistry};
+use value::{TdfValue, ValueSerializer, QueryMatch, query_stream, TdfDiff, DiffOptions, diff_streams, TdfPatch};
 use token::{TDFSerializer, TDFDeserializer};
 use anyhow::Result;
 use std::io::{Write, Read, Seek};
@@ -63,6 +88,250 @@ pub fn bin_to_json< R: Read + Seek+ Sized>(reader: &mut R) -> Result<String>  {
     Ok(sc)
 }
 
+/// Performs TDF binary to json conversion, naming object types by registry
+pub fn bin_to_json_with_registry< R: Read + Seek+ Sized>(reader: &mut R, registry: &ObjectRegistry) -> Result<String>  {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut sc = String::new();
+    JsonSerializer::new(stream).with_registry(registry.clone()).write_json(&mut sc)?;
+    Ok(sc)
+}
+
+/// Performs rust struct to json conversion, keys are Rust field names when field_names is set
+pub fn struct_to_json<D: Deserialize>(structure: &mut D, field_names: bool) -> Result<String> {
+    let stream = RTDFDeserializer::deserialize(structure)?;
+    let mut json = String::new();
+    let mut ser = JsonSerializer::new(stream);
+    if field_names {
+        ser = ser.with_field_names(&D::schema());
+    }
+    ser.write_json(&mut json)?;
+    Ok(json)
+}
+
+/// Performs json to rust struct conversion, types of values are taken from struct.
+/// Keys can be labels or Rust field names
+pub fn json_to_struct<T: Serialize + Deserialize>(json: &str) -> Result<T> {
+    let mut des = JsonDeserializer::new(json).with_struct_schema::<T>();
+    des.des_json()?;
+    let mut sc = StructConstructor::<T>::new();
+    RTDFSerializer::serialize(des.stream, &mut sc)?;
+    sc.build()
+}
+
+/// JSON Schema of json written for the struct with given options, keys are Rust field names when field_names is set
+pub fn json_schema<D: Deserialize>(options: JsonOptions, field_names: bool) -> Result<String> {
+    let mut writer = JsonSchemaWriter::new().with_options(options);
+    if field_names {
+        writer = writer.with_field_names();
+    }
+    let mut schema = String::new();
+    writer.write_schema(&D::schema(), &mut schema)?;
+    Ok(schema)
+}
+
+/// TypeScript definitions of json written for the struct with given options, keys are Rust field names when field_names is set
+pub fn typescript_definitions<D: Deserialize>(options: JsonOptions, field_names: bool) -> Result<String> {
+    let mut writer = TypeScriptWriter::new().with_options(options);
+    if field_names {
+        writer = writer.with_field_names();
+    }
+    let mut definitions = String::new();
+    writer.write_definitions(&D::schema(), &mut definitions)?;
+    Ok(definitions)
+}
+
+/// Performs json to TDF binary conversion, types of values are inferred
+/// Use JsonDeserializer with type hints for blobs, object ids and other types JSON doesn't have
+pub fn json_to_bin<W: Write>(json: &str, writer: &mut W) -> Result<()> {
+    let stream = JsonDeserializer::deserialize(&mut json.to_string())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to json conversion with given blob encoding, label case and layout
+pub fn bin_to_json_with_options<R: Read + Seek + Sized>(reader: &mut R, options: JsonOptions) -> Result<String> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut json = String::new();
+    JsonSerializer::new(stream).with_options(options).write_json(&mut json)?;
+    Ok(json)
+}
+
+/// Performs TDF binary to lossless typed json conversion
+pub fn bin_to_typed_json<R: Read + Seek + Sized>(reader: &mut R) -> Result<String> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut json = String::new();
+    TypedJsonSerializer::serialize(stream, &mut json)?;
+    Ok(json)
+}
+
+/// Performs typed json to TDF binary conversion
+pub fn typed_json_to_bin<W: Write>(json: &str, writer: &mut W) -> Result<()> {
+    let stream = TypedJsonDeserializer::deserialize(&mut json.to_string())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs serde serializable struct to TDF binary conversion
+#[cfg(feature = "serde")]
+pub fn serde_to_bin<T: ::serde::Serialize, W: Write>(value: &T, writer: &mut W) -> Result<()> {
+    let stream = serde_tdf::to_stream(value)?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to serde deserializable struct conversion
+#[cfg(feature = "serde")]
+pub fn bin_to_serde<T: ::serde::de::DeserializeOwned, R: Read + Seek + Sized>(reader: &mut R) -> Result<T> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    serde_tdf::from_stream(stream)
+}
+
+/// Performs TDF binary to YAML conversion
+#[cfg(feature = "yaml")]
+pub fn bin_to_yaml<R: Read + Seek + Sized>(reader: &mut R) -> Result<String> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut yaml = String::new();
+    yaml::YamlSerializer::serialize(stream, &mut yaml)?;
+    Ok(yaml)
+}
+
+/// Performs YAML to TDF binary conversion
+#[cfg(feature = "yaml")]
+pub fn yaml_to_bin<W: Write>(yaml: &str, writer: &mut W) -> Result<()> {
+    let stream = yaml::YamlDeserializer::deserialize(&mut yaml.to_string())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to MessagePack conversion
+#[cfg(feature = "msgpack")]
+pub fn bin_to_msgpack<R: Read + Seek + Sized>(reader: &mut R) -> Result<Vec<u8>> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut msgpack = Vec::new();
+    msgpack::MsgPackSerializer::serialize(stream, &mut msgpack)?;
+    Ok(msgpack)
+}
+
+/// Performs MessagePack to TDF binary conversion
+#[cfg(feature = "msgpack")]
+pub fn msgpack_to_bin<W: Write>(msgpack: &[u8], writer: &mut W) -> Result<()> {
+    let stream = msgpack::MsgPackDeserializer::deserialize(&mut msgpack.to_vec())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to CBOR conversion
+#[cfg(feature = "cbor")]
+pub fn bin_to_cbor<R: Read + Seek + Sized>(reader: &mut R) -> Result<Vec<u8>> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut cbor = Vec::new();
+    cbor::CborSerializer::serialize(stream, &mut cbor)?;
+    Ok(cbor)
+}
+
+/// Performs CBOR to TDF binary conversion
+#[cfg(feature = "cbor")]
+pub fn cbor_to_bin<W: Write>(cbor: &[u8], writer: &mut W) -> Result<()> {
+    let stream = cbor::CborDeserializer::deserialize(&mut cbor.to_vec())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to Blaze-like XML conversion
+pub fn bin_to_xml<R: Read + Seek + Sized>(reader: &mut R) -> Result<String> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut xml = String::new();
+    XmlSerializer::serialize(stream, &mut xml)?;
+    Ok(xml)
+}
+
+/// Performs XML to TDF binary conversion
+pub fn xml_to_bin<W: Write>(xml: &str, writer: &mut W) -> Result<()> {
+    let stream = XmlDeserializer::deserialize(&mut xml.to_string())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to CSV conversion of the list of maps at label path like "GAME.PROS"
+pub fn bin_to_csv<R: Read + Seek + Sized>(reader: &mut R, path: &str, options: CsvOptions) -> Result<String> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut csv = String::new();
+    CsvSerializer::new(stream, path).with_options(options).write_csv(&mut csv)?;
+    Ok(csv)
+}
+
+/// Performs TDF binary to Blaze-like text conversion
+pub fn bin_to_text<R: Read + Seek + Sized>(reader: &mut R) -> Result<String> {
+    bin_to_text_with_options(reader, TextOptions::default())
+}
+
+/// Performs TDF binary to text conversion with given indentation, blob truncation and label names
+pub fn bin_to_text_with_options<R: Read + Seek + Sized>(reader: &mut R, options: TextOptions) -> Result<String> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut text = String::new();
+    TextSerializer::new(stream).with_options(options).write_text(&mut text)?;
+    Ok(text)
+}
+
+/// Performs text notation to TDF binary conversion
+pub fn text_to_bin<W: Write>(text: &str, writer: &mut W) -> Result<()> {
+    let stream = TextDeserializer::deserialize(&mut text.to_string())?;
+    BTDFSerializer::serialize(stream, writer)?;
+    Ok(())
+}
+
+/// Performs TDF binary to dynamic value conversion
+pub fn bin_to_value<R: Read + Seek + Sized>(reader: &mut R) -> Result<TdfValue> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let mut value = TdfValue::map();
+    ValueSerializer::serialize(stream, &mut value)?;
+    Ok(value)
+}
+
+/// Performs dynamic value to TDF binary conversion
+pub fn value_to_bin<W: Write>(value: &TdfValue, writer: &mut W) -> Result<()> {
+    BTDFSerializer::serialize(value.to_stream()?, writer)?;
+    Ok(())
+}
+
+/// Evaluates path query over TDF binary, decoding the whole message.
+/// For concrete paths project_bin decodes only the selected values
+pub fn query_bin<R: Read + Seek + Sized>(reader: &mut R, query: &str) -> Result<Vec<QueryMatch>> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    query_stream(stream, query)
+}
+
+/// Performs structural diff of two TDF binaries
+pub fn diff_bin<R1: Read + Seek + Sized, R2: Read + Seek + Sized>(old: &mut R1, new: &mut R2, options: &DiffOptions) -> Result<TdfDiff> {
+    let old_stream = BTDFDeserializer::deserialize(old)?;
+    let new_stream = BTDFDeserializer::deserialize(new)?;
+    diff_streams(old_stream, new_stream, options)
+}
+
+/// Applies patch to TDF binary, writing patched binary
+pub fn patch_bin<R: Read + Seek + Sized, W: Write>(reader: &mut R, patch: &TdfPatch, writer: &mut W) -> Result<()> {
+    let stream = BTDFDeserializer::deserialize(reader)?;
+    let patched = patch.apply_stream(stream)?;
+    BTDFSerializer::serialize(patched, writer)?;
+    Ok(())
+}
+
+/// Replaces single value of TDF binary in place, leaving other bytes untouched.
+/// Returns the new length of the buffer
+pub fn splice_bin(buf: &mut Vec<u8>, path: &str, value: &TdfValue) -> Result<usize> {
+    btdf::splice_field(buf, path, value)
+}
+
+/// Decodes only values at given paths of TDF binary, skipping the rest
+pub fn project_bin(buf: &[u8], paths: &[&str]) -> Result<Vec<QueryMatch>> {
+    btdf::project(buf, paths)
+}
+
+/// Annotated hex dump of TDF binary, with offsets, labels, types and values
+pub fn dump_bin(buf: &[u8]) -> String {
+    btdf::hex_dump(buf)
+}
+
 // /// Auto generates Rust pseudo code for given binary stream
 // pub fn auto_gen_from_bin<R: Read + Seek+ Sized>(reader: &mut R) -> Result<String>  {
 //     // Conver bin into token stream
@@ -79,8 +348,12 @@ pub fn bin_to_json< R: Read + Seek+ Sized>(reader: &mut R) -> Result<String>  {
 mod tests {
 
     use peekread::{SeekPeekReader};
-    use crate::{prelude::*, bin_to_json};
-    use crate::{struct_to_bin, bin_to_struct};
+    use crate::{prelude::*, bin_to_json, bin_to_json_with_registry, bin_to_value, value_to_bin, query_bin, diff_bin, patch_bin, splice_bin, project_bin, dump_bin, bin_to_text, bin_to_text_with_options, text_to_bin, json_to_bin, bin_to_typed_json, typed_json_to_bin, bin_to_json_with_options, bin_to_xml, xml_to_bin};
+    use crate::json::{JsonOptions, BlobEncoding, LabelCase, DuplicateLabels};
+    use crate::json::JsonDeserializer;
+    use crate::btdf::BTDFSerializer;
+    use crate::{struct_to_bin, bin_to_struct, struct_to_json, json_to_struct, json_schema, typescript_definitions};
+    use crate::text::TextOptions;
     use std::collections::HashMap;
     use std::io::Cursor;
     use std::fmt::Debug;
@@ -100,6 +373,12 @@ mod tests {
         d: u32,
     }
 
+    #[derive(Pack, Debug, PartialEq)]
+    struct TestBools {
+        a: bool,
+        b: bool,
+    }
+
     #[derive(Pack, Debug, PartialEq)]
     struct TestBasic {
         a: String,
@@ -179,7 +458,7 @@ mod tests {
     impl TestCustom {
         fn new() -> Self {
             Self {
-                a: Localization("enUS".to_string()),
+                a: Localization::from_bytes(*b"enUS"),
                 b: ObjectType(34, 56),
                 c: ObjectId(1, 2, 3),
             }
@@ -201,6 +480,12 @@ mod tests {
         test_bi_direct(TestNumbers::new()).unwrap();
     }
 
+    #[test]
+    fn bools_test() {
+        test_bi_direct(TestBools { a: true, b: false }).unwrap();
+        test_bi_direct(TestBools { a: false, b: true }).unwrap();
+    }
+
     #[test]
     fn customs_test() {
         test_bi_direct(TestCustom::new()).unwrap();
@@ -216,6 +501,926 @@ mod tests {
         test_bi_direct(TestUnions::new()).unwrap();
     }
 
+    #[test]
+    fn object_notation_test() -> Result<()> {
+
+        let object_id: ObjectId = "4/1/12345".parse()?;
+        assert_eq!(object_id, ObjectId(4, 1, 12345));
+        assert_eq!(object_id.component(), 4);
+        assert_eq!(object_id.type_id(), 1);
+        assert_eq!(object_id.id(), 12345);
+        assert_eq!(object_id.to_string(), "4/1/12345");
+        assert_eq!("0x1C/0x1".parse::<ObjectType>()?, ObjectType(0x1c, 1));
+        assert!("4/1".parse::<ObjectId>().is_err());
+
+        let mut registry = ObjectRegistry::new();
+        registry.register_component(4, "gamemanager").register_type(4, 1, "game");
+        assert_eq!(registry.format_id(object_id), "gamemanager/game/12345");
+        assert_eq!(registry.format_type(ObjectType(4, 2)), "gamemanager/2");
+        assert_eq!(registry.parse_id("gamemanager/game/12345")?, object_id);
+
+        #[derive(Pack, Debug, PartialEq)]
+        struct Test {
+            objt: ObjectType,
+            obji: ObjectId,
+        }
+
+        let mut rw_cursor = Cursor::new(vec![]);
+        struct_to_bin(&mut Test { objt: ObjectType(4, 1), obji: object_id }, &mut rw_cursor)?;
+        rw_cursor.set_position(0);
+        let json_string = bin_to_json_with_registry(&mut rw_cursor, &registry)?;
+        assert!(json_string.contains("\"objt\": \"gamemanager/game\""));
+        assert!(json_string.contains("\"obji\": \"gamemanager/game/12345\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn localization_test() -> Result<()> {
+
+        let locale: Localization = "enUS".parse()?;
+        assert_eq!(locale, Localization(0x656e5553));
+        assert_eq!(locale.parts(), Some(("en".to_string(), "US".to_string())));
+        assert_eq!(locale.to_string(), "enUS");
+
+        let unset = Localization(0);
+        assert_eq!(unset.language(), None);
+        assert_eq!(unset.to_string(), "0x00000000");
+        assert_eq!(unset.to_string().parse::<Localization>()?, unset);
+        assert_eq!("0x00000001".parse::<Localization>()?, Localization(1));
+
+        #[derive(Pack, Debug, PartialEq)]
+        struct Test {
+            a: Localization,
+            b: Localization,
+            c: Localization,
+        }
+
+        test_bi_direct(Test { a: locale, b: unset, c: Localization(0xFFFF_0001) })
+    }
+
+    #[test]
+    fn optional_test() -> Result<()> {
+
+        test_bi_direct(TestOptional { a: Some(-5) })?;
+        test_bi_direct(TestOptional { a: None })?;
+
+        #[derive(Pack, Debug, PartialEq)]
+        struct Test {
+            a: i32,
+            #[rename("NAME")]
+            b: Option<String>,
+            list: Option<Vec<Option<u32>>>,
+            d: Option<TestOptional>,
+            e: u32,
+        }
+
+        test_bi_direct(Test { a: 1, b: None, list: None, d: None, e: 2 })?;
+        test_bi_direct(Test { a: 1, b: Some("x".into()), list: Some(vec![Some(3)]), d: Some(TestOptional { a: None }), e: 2 })?;
+
+        // Absent values can't be a part of list
+        let mut rw_cursor = Cursor::new(vec![]);
+        assert!(struct_to_bin(&mut Test { a: 1, b: None, list: Some(vec![None]), d: None, e: 2 }, &mut rw_cursor).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn value_test() -> Result<()> {
+
+        #[derive(Pack, Debug, PartialEq)]
+        struct Test {
+            t: TestBasic,
+            unio: TestUnions,
+            gama: Generic,
+            list: Vec<TestCustom>,
+            pair: Vec<(String, f32)>,
+        }
+
+        let mut input = Test {
+            t: TestBasic::new(),
+            unio: TestUnions::new(),
+            gama: Generic::Valid(0x05, GenericContent::Labeled("GAMA".into(), GenericType::Int(34))),
+            list: vec![TestCustom::new(), TestCustom::new()],
+            pair: vec![("a".into(), 0.5)],
+        };
+
+        let mut expected = Cursor::new(vec![]);
+        struct_to_bin(&mut input, &mut expected)?;
+        expected.set_position(0);
+
+        let mut value = bin_to_value(&mut expected)?;
+        assert_eq!(value["T"]["A"].as_str(), Some("crossplayGames"));
+        assert_eq!(value["list"][1]["c"], TdfValue::ObjectId(ObjectId(1, 2, 3)));
+        assert_eq!(value["unio"]["c"]["VALU"]["MACI"].as_i64(), Some(80));
+        assert!(value.get("none").is_none());
+
+        // Unchanged value gives the same binary
+        let mut output = vec![];
+        value_to_bin(&value, &mut output)?;
+        assert_eq!(&output, expected.get_ref());
+
+        value["list"][0]["b"] = TdfValue::ObjectType(ObjectType(4, 1));
+        value.insert("time", TdfValue::Time(1_000_000));
+        assert_eq!(value["TIME"], TdfValue::Time(1_000_000));
+
+        let mut output = vec![];
+        value_to_bin(&value, &mut output)?;
+        let changed = bin_to_value(&mut Cursor::new(output))?;
+        assert_eq!(changed, value);
+
+        // Union marker is kept without entries
+        let mut empty = TdfMap::new();
+        empty.union = true;
+        let empty = TdfValue::Map(empty);
+        let mut output = TdfValue::map();
+        crate::value::ValueSerializer::serialize(empty.to_stream()?, &mut output)?;
+        assert_eq!(output, empty);
+
+        // List elements must have the list type
+        value["list"][0] = TdfValue::Int(5);
+        assert!(value_to_bin(&value, &mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn literal_test() -> Result<()> {
+
+        let name = "player";
+        let value = tdf!({
+            "GID": 5,
+            "NEG": -6,
+            "NAME": "x",
+            "PLST": [ { "PNAM": (name), "SCOR": float(1.5) }, { "PNAM": "other", "SCOR": 2.5 } ],
+            "ADDR": union(2, "VALU", { "IP": 34, "PORT": 3659 }),
+            "NONE": union(),
+            "BLOB": blob(0, 0xff),
+            "OBJT": object_type(4, 1),
+            "OBJI": object_id(4, 1, 55),
+            "TIME": time(1000),
+            "ILST": int_list(1, -2, 3),
+            "PAIR": pair_list(1 => "one", 2 => "two"),
+            "EPAR": pair_list(IntType, MapType),
+            "ELST": list(StringType),
+            "GEN1": generic(),
+            "GEN2": generic(3),
+            "GEN3": generic(3, "DATA", [1, 2]),
+        });
+
+        assert_eq!(value["GID"], TdfValue::Int(5));
+        assert_eq!(value["NEG"], TdfValue::Int(-6));
+        assert_eq!(value["PLST"][0]["PNAM"].as_str(), Some("player"));
+        assert_eq!(value["PLST"][1]["SCOR"], TdfValue::Float(2.5));
+        assert_eq!(value["ADDR"]["VALU"]["PORT"].as_i64(), Some(3659));
+        assert_eq!(value["PAIR"], TdfValue::PairList(TDFToken::IntType, TDFToken::StringType, vec![(1.into(), "one".into()), (2.into(), "two".into())]));
+        assert_eq!(value["GEN3"]["DATA"][1], TdfValue::Int(2));
+
+        let stream = value.to_stream()?;
+        assert_eq!(stream.get(0)?, TDFToken::MapType);
+
+        let mut output = vec![];
+        value_to_bin(&value, &mut output)?;
+        assert_eq!(bin_to_value(&mut Cursor::new(output))?, value);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_test() -> Result<()> {
+
+        let value = tdf!({
+            "GAME": {
+                "GID": 7,
+                "PROS": [ { "PNAM": "first" }, { "PNAM": "second" }, { "PNAM": "third", "GID": 9 } ],
+                "ATTR": pair_list("mode" => "conquest", "map" => "MP_01"),
+                "SLOT": pair_list(3 => "c"),
+                "ADDR": union(2, "VALU", { "IP": 34 }),
+            },
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+
+        let found = query_bin(&mut Cursor::new(bin), "GAME.PROS[2].PNAM")?;
+        assert_eq!(found, vec![QueryMatch { path: "GAME.PROS[2].PNAM".into(), value: "third".into() }]);
+
+        let paths: Vec<String> = value.query("**.gid")?.into_iter().map(|m| m.path).collect();
+        assert_eq!(paths, vec!["GAME.GID", "GAME.PROS[2].GID"]);
+
+        let names: Vec<TdfValue> = value.query("GAME.PROS[*].PNAM")?.into_iter().map(|m| m.value).collect();
+        assert_eq!(names, vec!["first".into(), "second".into(), "third".into()]);
+
+        assert_eq!(value.query("GAME.ATTR[\"map\"]")?[0].path, "GAME.ATTR[\"map\"]");
+        assert_eq!(value.pointer("GAME.SLOT[3]"), Some(&"c".into()));
+        assert_eq!(value.pointer("GAME.ADDR.VALU.IP"), Some(&TdfValue::Int(34)));
+        assert_eq!(value.query("GAME.*")?.len(), 5);
+        assert!(value.query("GAME.PROS[7]")?.is_empty());
+        assert!(TdfPath::parse("GAME.PROS[x]").is_err());
+        assert_eq!(TdfPath::parse("GAME.PROS[2].PNAM")?.to_string(), "GAME.PROS[2].PNAM");
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff_test() -> Result<()> {
+
+        let old = tdf!({
+            "GID": 5,
+            "NAME": "old",
+            "GONE": 1,
+            "PROS": [ { "PNAM": "a" }, { "PNAM": "b" } ],
+            "ATTR": pair_list("mode" => "conquest", "map" => "MP_01"),
+            "TYPE": 1,
+        });
+        let new = tdf!({
+            "ATTR": pair_list("map" => "MP_01", "mode" => "rush"),
+            "NAME": "new",
+            "GID": 5,
+            "PROS": [ { "PNAM": "a" }, { "PNAM": "c" }, { "PNAM": "d" } ],
+            "TYPE": "1",
+            "NEW": float(0.5),
+        });
+
+        let (mut old_bin, mut new_bin) = (vec![], vec![]);
+        value_to_bin(&old, &mut old_bin)?;
+        value_to_bin(&new, &mut new_bin)?;
+
+        let ordered = diff_bin(&mut Cursor::new(&old_bin), &mut Cursor::new(&new_bin), &DiffOptions::default())?;
+        assert_eq!(ordered.to_string(), [
+            "~ NAME: \"old\" -> \"new\"",
+            "- GONE = 1",
+            "# PROS: length 2 -> 3",
+            "~ PROS[1].PNAM: \"b\" -> \"c\"",
+            "+ PROS[2] = {\"PNAM\": \"d\"}",
+            "- ATTR[\"mode\"] = \"conquest\"",
+            "+ ATTR[\"map\"] = \"MP_01\"",
+            "- ATTR[\"map\"] = \"MP_01\"",
+            "+ ATTR[\"mode\"] = \"rush\"",
+            "! TYPE: type IntType -> StringType",
+            "+ NEW = float(0.5)",
+            "",
+        ].join("\n"));
+
+        let unordered = old.diff(&new, &DiffOptions { unordered_pair_lists: true });
+        assert!(unordered.0.contains(&DiffEntry { path: "ATTR[\"mode\"]".into(), kind: DiffKind::Changed("conquest".into(), "rush".into()) }));
+        assert_eq!(unordered.len(), 8);
+
+        assert!(old.diff(&old, &DiffOptions::default()).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn patch_test() -> Result<()> {
+
+        let value = tdf!({
+            "GID": 5,
+            "PROS": [ { "PNAM": "a" }, { "PNAM": "b" } ],
+            "ATTR": pair_list("mode" => "conquest"),
+            "ADDR": union(2, "VALU", { "IP": 34, "PORT": 3659 }),
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+
+        let patch = TdfPatch::new()
+            .replace("GID", 6)
+            .replace("ADDR.VALU.IP", 0x7f000001)
+            .add("PROS[1]", tdf!({ "PNAM": "new" }))
+            .remove("PROS[0]")
+            .add("ATTR[\"map\"]", "MP_01")
+            .remove("ATTR[\"mode\"]")
+            .add("NEW", tdf!(time(5)));
+
+        let mut output = vec![];
+        patch_bin(&mut Cursor::new(bin), &patch, &mut output)?;
+
+        assert_eq!(bin_to_value(&mut Cursor::new(output))?, tdf!({
+            "GID": 6,
+            "PROS": [ { "PNAM": "new" }, { "PNAM": "b" } ],
+            "ATTR": pair_list("map" => "MP_01"),
+            "ADDR": union(2, "VALU", { "IP": 0x7f000001, "PORT": 3659 }),
+            "NEW": time(5),
+        }));
+
+        // JSON form gives the same patch
+        assert_eq!(TdfPatch::from_json(&patch.to_json("    ")?)?, patch);
+        let from_json = TdfPatch::from_json(r#"[
+            {"op": "replace", "path": "GID", "value": 6},
+            {"op": "add", "path": "NEW", "value": {"$time": 5}},
+            {"op": "remove", "path": "ATTR[\"mode\"]"}
+        ]"#)?;
+        assert_eq!(from_json, TdfPatch::new().replace("GID", 6).add("NEW", tdf!(time(5))).remove("ATTR[\"mode\"]"));
+        assert!(TdfPatch::from_json(r#"[{"op": "move", "path": "GID"}]"#).is_err());
+        assert!(TdfPatch::from_json(r#"[{"op": "add", "path": "GID"}]"#).is_err());
+
+        // Failed patch keeps value untouched
+        let mut untouched = value.clone();
+        assert!(untouched.patch(&TdfPatch::new().replace("GID", 1).replace("GID", "text")).is_err());
+        assert!(untouched.patch(&TdfPatch::new().add("PROS[0]", 5)).is_err());
+        assert!(untouched.patch(&TdfPatch::new().add("GID", 5)).is_err());
+        assert!(untouched.patch(&TdfPatch::new().remove("PROS[*]")).is_err());
+        assert!(untouched.patch(&TdfPatch::new().replace("NONE.GID", 5)).is_err());
+        assert_eq!(untouched, value);
+
+        Ok(())
+    }
+
+    #[test]
+    fn splice_test() -> Result<()> {
+
+        let value = tdf!({
+            "GID": 5,
+            "PROS": [ { "PNAM": "a" }, { "PNAM": "b" } ],
+            "ATTR": pair_list("mode" => "conquest"),
+            "NAME": "game",
+            "ADDR": union(2, "VALU", { "IP": 34, "PORT": 3659 }),
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+        let original = bin.clone();
+
+        // Same size value changes only its own bytes
+        let len = splice_bin(&mut bin, "ADDR.VALU.PORT", &TdfValue::Int(3660))?;
+        assert_eq!(len, original.len());
+        assert_eq!(original.iter().zip(&bin).filter(|(a, b)| a != b).count(), 1);
+
+        let len = splice_bin(&mut bin, "PROS[1].PNAM", &TdfValue::from("longer name"))?;
+        assert_eq!(len, original.len() + 10);
+        splice_bin(&mut bin, "ATTR[\"mode\"]", &TdfValue::from("rush"))?;
+        splice_bin(&mut bin, "GID", &TdfValue::Int(-100000))?;
+
+        assert_eq!(bin_to_value(&mut Cursor::new(bin.clone()))?, tdf!({
+            "GID": -100000,
+            "PROS": [ { "PNAM": "a" }, { "PNAM": "longer name" } ],
+            "ATTR": pair_list("mode" => "rush"),
+            "NAME": "game",
+            "ADDR": union(2, "VALU", { "IP": 34, "PORT": 3660 }),
+        }));
+
+        assert!(splice_bin(&mut bin, "GID", &TdfValue::from("text")).is_err());
+        assert!(splice_bin(&mut bin, "NONE", &TdfValue::Int(1)).is_err());
+        assert!(splice_bin(&mut bin, "PROS", &tdf!([1, 2])).is_err());
+        assert!(splice_bin(&mut bin, "PROS[*]", &TdfValue::Int(1)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn project_test() -> Result<()> {
+
+        let value = tdf!({
+            "GID": 5,
+            "PROS": [ { "PNAM": "a", "STAT": 1 }, { "PNAM": "b", "STAT": 2 } ],
+            "ATTR": pair_list("mode" => "conquest", "map" => "MP_01"),
+            "BLOB": blob(1, 2, 3),
+            "ADDR": union(2, "VALU", { "IP": 34, "PORT": 3659 }),
+            "GBRA": pair_list(1 => pair_list(2 => 3)),
+            "NAME": "game",
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+
+        let found = project_bin(&bin, &["name", "PROS[1].PNAM", "ATTR[\"map\"]", "ADDR.VALU.PORT", "GBRA", "GBRA[1][2]", "NONE"])?;
+        let found: Vec<(&str, &TdfValue)> = found.iter().map(|m| (m.path.as_str(), &m.value)).collect();
+
+        assert_eq!(found, vec![
+            ("PROS[1].PNAM", &tdf!("b")),
+            ("ATTR[\"map\"]", &tdf!("MP_01")),
+            ("ADDR.VALU.PORT", &tdf!(3659)),
+            ("GBRA", &tdf!(pair_list(1 => pair_list(2 => 3)))),
+            ("GBRA[1][2]", &tdf!(3)),
+            ("NAME", &tdf!("game")),
+        ]);
+
+        // Same values as full decoding gives
+        for m in project_bin(&bin, &["GID", "BLOB", "PROS[0]"])? {
+            assert_eq!(value.pointer(&m.path), Some(&m.value));
+        }
+
+        assert!(project_bin(&bin, &["PROS[*]"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_test() -> Result<()> {
+
+        #[derive(Pack, Debug, PartialEq)]
+        struct Player {
+            pnam: String,
+            stat: u32,
+        }
+
+        let value = tdf!({
+            "GID": 5,
+            "PROS": [ { "PNAM": "a", "STAT": 1 }, { "PNAM": "b", "STAT": 2 } ],
+            "ATTR": pair_list("mode" => "conquest", "map" => "MP_01"),
+            "ADDR": union(2, "VALU", { "IP": 34, "PORT": 3659 }),
+            "INTS": int_list(4, 5, 6),
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+
+        let index = TdfIndex::build(&bin)?;
+        let root = index.root();
+        assert_eq!(root.child_count(), 5);
+        assert_eq!(root.range(), 0..bin.len());
+
+        let labels: Vec<&str> = root.children().filter_map(|c| c.label()).collect();
+        assert_eq!(labels, vec!["GID ", "PROS", "ATTR", "ADDR", "INTS"]);
+
+        let pros = root.get("pros").unwrap();
+        assert_eq!(*pros.value_type(), TDFToken::ListType);
+        assert_eq!(pros.child_count(), 2);
+
+        // Subtree decoded on its own
+        let player: Player = pros.at(1).unwrap().decode()?;
+        assert_eq!(player, Player { pnam: "b".to_string(), stat: 2 });
+        assert!(pros.decode::<Player>().is_err());
+
+        assert_eq!(index.pointer("ATTR[\"map\"]")?.unwrap().value()?, tdf!("MP_01"));
+        assert_eq!(index.pointer("ADDR.VALU.PORT")?.unwrap().value()?, tdf!(3659));
+        assert_eq!(index.pointer("INTS[2]")?.unwrap().value()?, tdf!(6));
+        assert!(index.pointer("PROS[2]")?.is_none());
+
+        for node in root.children() {
+            assert_eq!(Some(&node.value()?), value.get(node.label().unwrap()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dump_test() -> Result<()> {
+
+        let value = tdf!({
+            "GID": 100,
+            "NAME": "game",
+            "PROS": [ { "STAT": -1 } ],
+            "ADDR": union(2, "VALU", { "PORT": 3659 }),
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+
+        let dump = dump_bin(&bin);
+
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "000000  9e 99 00 00                 GID  [9e 99 00] IntType");
+        assert_eq!(lines[1], "000004  a4 01                         varint (36 + 1<<6) = 100");
+        assert!(dump.contains("string \"game\""));
+        assert!(dump.contains("      STAT [cf 48 74] IntType"));
+        assert!(dump.contains("varint -1 = -1"));
+        assert!(dump.contains("union type"));
+        assert!(dump.contains("member VALU"));
+        assert!(!dump.contains("!!"));
+
+        // Truncated message
+        let dump = dump_bin(&bin[..bin.len() - 3]);
+        assert!(dump.contains("!! undecodable"));
+
+        // Bytes are marked from start of the field with bad type
+        let mut bad = bin.clone();
+        bad[9] = 0x1f;
+        let dump = dump_bin(&bad);
+        assert!(dump.contains("!! undecodable at 000006"));
+        assert!(dump.contains("000006  ba 1b 65 1f"));
+
+        Ok(())
+    }
+
+    #[derive(Pack, Debug, PartialEq)]
+    struct Player {
+        #[rename("PNAM")]
+        name: String,
+        stat: u32,
+    }
+
+    #[derive(Pack, Debug, PartialEq)]
+    struct Game {
+        #[rename("GID")]
+        game_id: u64,
+        #[rename("PROS")]
+        players: Vec<Player>,
+        attr: Option<HashMap<String, String>>,
+    }
+
+    /// Dissector of Game with a valid and a truncated sample
+    fn dissector_script() -> Result<String> {
+        let mut game = Game {
+            game_id: 5,
+            players: vec![Player { name: "a".to_string(), stat: 1 }],
+            attr: None,
+        };
+        let mut body = vec![];
+        struct_to_bin(&mut game, &mut body)?;
+
+        Ok(crate::wireshark::LuaDissector::new("blaze")
+            .port(10041)
+            .header_size(12)
+            .body::<Game>()
+            .sample("game", body.clone())
+            .sample("truncated", body[..body.len() - 2].to_vec())
+            .generate())
+    }
+
+    #[test]
+    fn dissector_test() -> Result<()> {
+
+        let schema = Game::schema();
+        assert_eq!(schema.type_token(), TDFToken::MapType);
+        let names: Vec<&str> = schema.structs().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Player", "Game"]);
+
+        let script = dissector_script()?;
+        assert!(script.contains("[\"GID \"] = { name = \"game_id\", schema = {} },"));
+        assert!(script.contains("[\"PROS\"] = { name = \"players\", schema = { list = { struct = \"Player\" } } },"));
+        assert!(script.contains("local PORTS = { 10041 }"));
+        assert!(!script.contains("{{"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs lua interpreter on PATH, run with --ignored"]
+    fn dissector_lua_test() -> Result<()> {
+
+        let path = std::env::temp_dir().join("tdf_dissector_test.lua");
+        std::fs::write(&path, dissector_script()?)?;
+        let output = std::process::Command::new("lua").arg(&path).output()?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("game_id (GID ): Int = 5"));
+        assert!(stdout.contains("name (PNAM): String = \"a\""));
+        assert!(stdout.contains("Undecodable"));
+        // Truncated sample fails the run
+        assert!(!output.status.success());
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_test() -> Result<()> {
+
+        let value = tdf!({
+            "GID": 5,
+            "NAME": "a \"b\"\n",
+            "PROS": [ { "PNAM": "a" } ],
+            "ATTR": pair_list("mode" => "conquest"),
+            "BLOB": blob(1, 2, 3, 4, 5),
+            "ADDR": union(2, "VALU", { "PORT": 3659 }),
+            "UNST": union(),
+            "GEN": generic(42, "VALU", 7),
+            "INTS": int_list(1, 2),
+            "OBJI": object_id(4, 1, 9),
+            "FLT": float(1.5),
+            "EMPT": list(IntType),
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+
+        assert_eq!(bin_to_text(&mut Cursor::new(bin.clone()))?, concat!(
+            "GID = 5\n",
+            "NAME = \"a \\\"b\\\"\\n\"\n",
+            "PROS = list(Map) [\n",
+            "    {\n",
+            "        PNAM = \"a\"\n",
+            "    }\n",
+            "]\n",
+            "ATTR = pair_list(String, String) {\n",
+            "    \"mode\" = \"conquest\"\n",
+            "}\n",
+            "BLOB = blob(0102030405)\n",
+            "ADDR = union(2) VALU = {\n",
+            "    PORT = 3659\n",
+            "}\n",
+            "UNST = union(unset)\n",
+            "GEN = generic(42) VALU = 7\n",
+            "INTS = int_list(1, 2)\n",
+            "OBJI = object_id(4/1/9)\n",
+            "FLT = float(1.5)\n",
+            "EMPT = list(Int) []\n",
+        ));
+
+        let options = TextOptions { indent: 2, max_blob: Some(2), type_hints: true, ..Default::default() }
+            .label("GID", "game_id");
+        let text = bin_to_text_with_options(&mut Cursor::new(bin), options)?;
+        assert!(text.contains("GID = 5  # Int, game_id\n"));
+        assert!(text.contains("BLOB = blob(0102...5 bytes)  # Blob\n"));
+        assert!(text.contains("PROS = list(Map) [  # List\n  {\n    PNAM = \"a\"  # String\n  }\n]\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn text_parse_test() -> Result<()> {
+
+        let text = concat!(
+            "GID = 5\n",
+            "NEG = -300\n",
+            "NAME = \"a \\\"b\\\"\\n\\xff\"\n",
+            "PROS = list(Map) [\n",
+            "    {\n",
+            "        PNAM = \"a\"\n",
+            "    }\n",
+            "]\n",
+            "ATTR = pair_list(String, Int) {\n",
+            "    \"mode\" = 1\n",
+            "}\n",
+            "BLOB = blob(0102030405)\n",
+            "@ADDR = union(2) VALU = {\n",
+            "    PORT = 3659\n",
+            "}\n",
+            "UNST = union(unset)\n",
+            "GEN = generic(42) VALU = 7\n",
+            "EGEN = generic(42)\n",
+            "IGEN = generic(invalid)\n",
+            "INTS = int_list(1, 2)\n",
+            "OBJT = object_type(4/1)\n",
+            "OBJI = object_id(4/1/9)\n",
+            "FLT = float(1.5)\n",
+            "TIME = time(1600000000)\n",
+            "EMPT = list(Int) []\n",
+        );
+
+        let mut bin = vec![];
+        text_to_bin(text, &mut bin)?;
+        assert_eq!(bin_to_text(&mut Cursor::new(bin))?, text);
+
+        // Comments, hex ints and free layout
+        let mut bin = vec![];
+        text_to_bin("# header\ngid = 0x10 # game\nlst = list(Int) [1 2]", &mut bin)?;
+        assert_eq!(bin_to_text(&mut Cursor::new(bin))?, "GID = 16\nLST = list(Int) [\n    1\n    2\n]\n");
+
+        let mut bin = vec![];
+        assert!(text_to_bin("GID = \"a\" \nLIST = list(Int) [\"a\"]", &mut bin).is_err());
+        assert!(text_to_bin("BLOB = blob(0102...5 bytes)", &mut bin).is_err());
+        assert!(text_to_bin("LONGER = 1", &mut bin).is_err());
+        assert!(text_to_bin("GID = -9223372036854775807", &mut bin).is_ok());
+        assert!(text_to_bin("GID = 9223372036854775808", &mut bin).is_err());
+        assert!(text_to_bin("GID = 0xffffffffffffffff", &mut bin).is_err());
+        assert!(text_to_bin("GID = --5", &mut bin).is_err());
+        assert!(text_to_bin("MAP = { GID = 1", &mut bin).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_des_test() -> Result<()> {
+
+        let value = tdf!({
+            "GID": 5,
+            "NAME": "a \"b\"\n",
+            "PROS": [ { "PNAM": "a", "BLOB": blob(1, 255) } ],
+            "ATTR": pair_list("mode" => "conquest"),
+            "BLOB": blob(1, 2, 3),
+            "ADDR": union(2, "VALU", { "PORT": 3659 }),
+            "UNST": union(),
+            "GEN": generic(42, "VALU", 7),
+            "INTS": int_list(1, 2),
+            "OBJI": object_id(4, 1, 9),
+            "FLT": float(1.5),
+            "EMPT": list(MapType),
+        });
+
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+        let json = bin_to_json(&mut Cursor::new(bin.clone()))?;
+
+        // Types JSON can't express are given by hints
+        let mut des = JsonDeserializer::new(&json)
+            .with_type("INTS", TDFToken::IntListType)?
+            .with_type("OBJI", TDFToken::ObjectIdType)?
+            .with_type("EMPT[*]", TDFToken::MapType)?;
+        des.des_json()?;
+        let mut round_trip = vec![];
+        BTDFSerializer::serialize(des.stream, &mut round_trip)?;
+        assert_eq!(round_trip, bin);
+
+        let mut bin = vec![];
+        json_to_bin(r#"{"gid": 5, "flt": 0.5, "lst": [1, 2.5], "ok": true, "plst": [[1, "a"]]}"#, &mut bin)?;
+        assert_eq!(bin_to_value(&mut Cursor::new(bin))?, tdf!({
+            "GID": 5,
+            "FLT": float(0.5),
+            "LST": [ float(1.0), float(2.5) ],
+            "OK": 1,
+            "PLST": pair_list(1 => "a"),
+        }));
+
+        // Whole floats keep their type through JSON
+        let value = tdf!({ "FLT": float(2.0), "BIG": float(1e20) });
+        let mut bin = vec![];
+        value_to_bin(&value, &mut bin)?;
+        let json = bin_to_json(&mut Cursor::new(bin))?;
+        assert!(json.contains("\"flt\": 2.0"));
+        let mut bin = vec![];
+        json_to_bin(&json, &mut bin)?;
+        assert_eq!(bin_to_value(&mut Cursor::new(bin))?, value);
+
+        let mut bin = vec![];
+        assert!(json_to_bin(r#"{"longer": 1}"#, &mut bin).is_err());
+        assert!(json_to_bin(r#"{"lst": [1, "a"]}"#, &mut bin).is_err());
+        assert!(json_to_bin(r#"{"gid": null}"#, &mut bin).is_err());
+        assert!(json_to_bin(r#"[1]"#, &mut bin).is_err());
+        assert!(json_to_bin(r#"{"gid": 1"#, &mut bin).is_err());
+        assert!(json_to_bin(r#"{"gid": 9223372036854775808}"#, &mut bin).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn typed_json_test() -> Result<()> {
+
+        let text = concat!(
+            "GID = 5\n",
+            "NAME = \"caf\\xc3\\xa9\"\n",
+            "RAW = \"\\xff\\x01\"\n",
+            "PROS = list(Map) [\n",
+            "    {\n",
+            "        PNAM = \"a\"\n",
+            "    }\n",
+            "]\n",
+            "NEST = list(List) [\n",
+            "    list(Int) []\n",
+            "]\n",
+            "ATTR = pair_list(String, Float) {\n",
+            "    \"rate\" = float(0.1)\n",
+            "}\n",
+            "BLOB = blob(00ff)\n",
+            "@ADDR = union(2) VALU = {\n",
+            "    PORT = 3659\n",
+            "}\n",
+            "UNST = union(unset)\n",
+            "GEN = generic(42) VALU = 7\n",
+            "EGEN = generic(42)\n",
+            "IGEN = generic(invalid)\n",
+            "INTS = int_list(1, -2)\n",
+            "OBJT = object_type(4/1)\n",
+            "OBJI = object_id(4/1/9)\n",
+            "NAN = float(NaN)\n",
+            "TIME = time(1600000000)\n",
+        );
+
+        let mut bin = vec![];
+        text_to_bin(text, &mut bin)?;
+
+        let json = bin_to_typed_json(&mut Cursor::new(bin.clone()))?;
+        assert!(json.contains("\"RAW\": {\"$bytes\": \"ff01\"}"));
+        assert!(json.contains("\"NEST\": [\n        {\n            \"$list\": \"Int\",\n            \"items\": []\n        }\n    ]"));
+        assert!(json.contains("\"$map_union\": true,\n    \"ADDR\": {\n        \"$union\": 2,"));
+        assert!(json.contains("\"IGEN\": {\"$generic\": null}"));
+        assert!(json.contains("\"NAN\": {\"$f32bits\": \"7fc00000\"}"));
+
+        let mut round_trip = vec![];
+        typed_json_to_bin(&json, &mut round_trip)?;
+        assert_eq!(round_trip, bin);
+
+        let mut bin = vec![];
+        assert!(typed_json_to_bin(r#"{"LST": []}"#, &mut bin).is_err());
+        assert!(typed_json_to_bin(r#"{"LST": [1, "a"]}"#, &mut bin).is_err());
+        assert!(typed_json_to_bin(r#"{"VAL": {"$unknown": 1}}"#, &mut bin).is_err());
+        assert!(typed_json_to_bin(r#"{"UN": {"$union": 127, "VALU": 1}}"#, &mut bin).is_err());
+
+        // NaN payload is kept
+        typed_json_to_bin(r#"{"NAN": {"$f32bits": "7fc00001"}}"#, &mut bin)?;
+        assert_eq!(bin_to_value(&mut Cursor::new(bin.clone()))?["NAN"].as_f32().map(f32::to_bits), Some(0x7fc00001));
+        assert!(bin_to_typed_json(&mut Cursor::new(bin))?.contains("\"$f32bits\": \"7fc00001\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_options_test() -> Result<()> {
+
+        let mut bin = vec![];
+        text_to_bin(concat!(
+            "NAME = \"a\\x1b\\t\\\"\"\n",
+            "BLOB = blob(00ff10)\n",
+            "ATTR = pair_list(String, Int) {\n",
+            "    \"mode\" = 1\n",
+            "}\n",
+            "A_B = 1\n",
+            "AB = 2\n",
+            "AB = 3\n",
+            "FLT = float(NaN)\n",
+        ), &mut bin)?;
+
+        // Default output is valid json as well
+        let json = bin_to_json(&mut Cursor::new(bin.clone()))?;
+        assert!(json.contains("\"name\": \"a\\u001b\\t\\\"\""));
+        assert!(json.contains("\"a_b\": 1"));
+        assert!(json.contains("\"flt\": null"));
+
+        let options = JsonOptions {
+            indent: String::new(),
+            blob: BlobEncoding::Base64,
+            label_case: LabelCase::Upper,
+            pair_list_objects: true,
+            duplicate_labels: DuplicateLabels::Suffix,
+        };
+        let json = bin_to_json_with_options(&mut Cursor::new(bin.clone()), options.clone())?;
+        assert_eq!(json, r#"{"NAME":"a\u001b\t\"","BLOB":"AP8Q","ATTR":{"mode":1},"A_B":1,"AB":2,"AB_2":3,"FLT":null}"#);
+
+        // Base64 is read back from hinted blobs
+        for (text, blob) in [("AP8Q", vec![0, 255, 16]), ("AQ==", vec![1]), ("AQI", vec![1, 2]), ("", vec![])] {
+            let mut des = JsonDeserializer::new(&format!("{{\"BLOB\": {:?}}}", text))
+                .with_type("BLOB", TDFToken::BlobType)?
+                .with_blob_encoding(BlobEncoding::Base64);
+            des.des_json()?;
+            assert_eq!(des.stream.0[4], TDFToken::Blob(blob));
+        }
+
+        let hex = JsonOptions { blob: BlobEncoding::Hex, ..options.clone() };
+        assert!(bin_to_json_with_options(&mut Cursor::new(bin.clone()), hex)?.contains(r#""BLOB":"00ff10""#));
+        let array = JsonOptions { blob: BlobEncoding::Array, ..options.clone() };
+        assert!(bin_to_json_with_options(&mut Cursor::new(bin.clone()), array)?.contains(r#""BLOB":[0,255,16]"#));
+        let error = JsonOptions { duplicate_labels: DuplicateLabels::Error, ..options };
+        assert!(bin_to_json_with_options(&mut Cursor::new(bin), error).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn xml_test() -> Result<()> {
+
+        let text = concat!(
+            "GID = 5\n",
+            "NAME = \"<a & \\\"b\\\">\\r\\n\"\n",
+            "NUM = \"42\"\n",
+            "RAW = \"\\xff\\x01\"\n",
+            "PROS = list(Map) [\n",
+            "    {\n",
+            "        PNAM = \"a\"\n",
+            "    }\n",
+            "    {}\n",
+            "]\n",
+            "ATTR = pair_list(String, Float) {\n",
+            "    \"rate\" = float(0.1)\n",
+            "}\n",
+            "BLOB = blob(00ff)\n",
+            "@ADDR = union(2) VALU = {\n",
+            "    PORT = 3659\n",
+            "}\n",
+            "UNST = union(unset)\n",
+            "GEN = generic(42) VALU = 7\n",
+            "IGEN = generic(invalid)\n",
+            "INTS = int_list(1, -2)\n",
+            "OBJI = object_id(4/1/9)\n",
+            "EMPT = {}\n",
+            "TIME = time(1600000000)\n",
+        );
+
+        let mut bin = vec![];
+        text_to_bin(text, &mut bin)?;
+
+        let xml = bin_to_xml(&mut Cursor::new(bin.clone()))?;
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tdf>\n    <gid>5</gid>\n"));
+        assert!(xml.contains("<name>&lt;a &amp; \"b\"&gt;&#13;\n</name>"));
+        assert!(xml.contains("<num type=\"String\">42</num>"));
+        assert!(xml.contains("<raw type=\"String\" encoding=\"hex\">ff01</raw>"));
+        assert!(xml.contains("<pros type=\"List\" item=\"Map\">\n        <entry>\n            <pnam>a</pnam>\n        </entry>\n        <entry/>\n    </pros>"));
+        assert!(xml.contains("<attr type=\"PairList\" key=\"String\" value=\"Float\">\n        <entry key=\"rate\">0.1</entry>\n    </attr>"));
+        assert!(xml.contains("<addr type=\"Union\" map_union=\"true\" member=\"2\">\n        <valu>\n            <port>3659</port>"));
+        assert!(xml.contains("<igen type=\"Generic\"/>"));
+        assert!(xml.contains("<empt type=\"Map\"/>"));
+
+        let mut round_trip = vec![];
+        xml_to_bin(&xml, &mut round_trip)?;
+        assert_eq!(round_trip, bin);
+
+        // Hand written XML with comments and other layout
+        let mut bin = vec![];
+        xml_to_bin("<!-- response --><res><gid> 7 </gid><lst type='List' item='Int'><entry>1</entry></lst></res>", &mut bin)?;
+        assert_eq!(bin_to_value(&mut Cursor::new(bin))?, tdf!({ "GID": 7, "LST": [1] }));
+
+        let mut bin = vec![];
+        assert!(xml_to_bin("<tdf><gid>1</gid>", &mut bin).is_err());
+        assert!(xml_to_bin("<tdf><gid>1</pid></tdf>", &mut bin).is_err());
+        assert!(xml_to_bin("<tdf><lst type=\"List\"/></tdf>", &mut bin).is_err());
+        assert!(xml_to_bin("<tdf><longer>1</longer></tdf>", &mut bin).is_err());
+
+        // Labels XML names can't hold are kept in attribute
+        let mut stream = TDFTokenStream::new();
+        for token in [TDFToken::MapType, TDFToken::MapStart, TDFToken::Label("1A_B".into()), TDFToken::IntType, TDFToken::Int(1),
+            TDFToken::Label("A B ".into()), TDFToken::IntType, TDFToken::Int(2), TDFToken::Label("X<=?".into()), TDFToken::IntType, TDFToken::Int(3), TDFToken::MapEnd] {
+            stream.push(token);
+        }
+        let mut xml = String::new();
+        crate::xml::XmlSerializer::serialize(stream.clone(), &mut xml)?;
+        assert!(xml.contains("<field label=\"1A_B\">1</field>"));
+        assert!(xml.contains("<a_b>2</a_b>"));
+        assert!(xml.contains("<field label=\"X&lt;=?\">3</field>"));
+        assert_eq!(crate::xml::XmlDeserializer::deserialize(&mut xml)?.0, stream.0);
+
+        Ok(())
+    }
+
     #[test]
     fn hash_map_test() {
 
@@ -318,7 +1523,7 @@ mod tests {
                     b: vec![0xf, 0xf, 0xc],
                     c: vec![12, 34325, 0],
                     d: IntList(vec![675, 5, 6, -1]),
-                    e: Localization("enUS".to_string()),
+                    e: Localization::from_bytes(*b"enUS"),
                     f: ObjectType(34, 56),
                     g: ObjectId(1, 2, 3),
                     t: TestBasic::new(),
@@ -333,5 +1538,379 @@ mod tests {
         test_json(TestCustomJson::new()).unwrap();
     }
 
+    #[cfg(feature = "serde")]
+    #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+    struct SerdePlayer {
+        #[serde(rename = "PNAM")]
+        name: String,
+        kind: Kind,
+        oid: Option<ObjectId>,
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+    enum Kind {
+        Bot,
+        Human,
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(::serde::Serialize, ::serde::Deserialize, Debug, PartialEq)]
+    struct SerdeGame {
+        gid: u32,
+        open: bool,
+        rate: f32,
+        pros: Vec<SerdePlayer>,
+        attr: std::collections::BTreeMap<String, i64>,
+        ints: IntList,
+        obj: ObjectType,
+        addr: crate::serde_tdf::TdfUnion<HashMap<String, u16>>,
+        none: crate::serde_tdf::TdfUnion<i32>,
+        pair: (i32, i32),
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_test() -> Result<()> {
+        use crate::{serde_to_bin, bin_to_serde};
+        use crate::serde_tdf::TdfUnion;
+        use crate::token::UnionType;
+
+        let mut attr = std::collections::BTreeMap::new();
+        attr.insert("mode".to_string(), 3);
+        let mut addr = HashMap::new();
+        addr.insert("port".to_string(), 3659u16);
+
+        let game = SerdeGame {
+            gid: 5,
+            open: true,
+            rate: 0.5,
+            pros: vec![
+                SerdePlayer { name: "a".to_string(), kind: Kind::Human, oid: Some(ObjectId(4, 1, 9)) },
+                SerdePlayer { name: "b".to_string(), kind: Kind::Bot, oid: None },
+            ],
+            attr,
+            ints: IntList(vec![1, -2]),
+            obj: ObjectType(4, 1),
+            addr: TdfUnion::Member(UnionType::IpAddr, "VALU".to_string(), addr),
+            none: TdfUnion::Unset,
+            pair: (1, 2),
+        };
+
+        let mut bin = vec![];
+        serde_to_bin(&game, &mut bin)?;
+
+        let text = bin_to_text(&mut Cursor::new(bin.clone()))?;
+        assert!(text.contains("GID = 5\n"));
+        assert!(text.contains("OPEN = 1\n"));
+        assert!(text.contains("PNAM = \"a\""));
+        assert!(text.contains("OID = object_id(4/1/9)"));
+        assert!(text.contains("ATTR = pair_list(String, Int)"));
+        assert!(text.contains("INTS = int_list(1, -2)"));
+        assert!(text.contains("OBJ = object_type(4/1)"));
+        assert!(text.contains("ADDR = union(3) VALU = pair_list(String, Int)"));
+        assert!(text.contains("NONE = union(unset)"));
+        assert!(text.contains("PAIR = list(Int)"));
+
+        let back: SerdeGame = bin_to_serde(&mut Cursor::new(bin))?;
+        assert_eq!(back, game);
+
+        // Long field names have to be renamed
+        #[derive(::serde::Serialize)]
+        struct Long { players: i32 }
+        assert!(serde_to_bin(&Long { players: 1 }, &mut vec![]).is_err());
+
+        // Unsigned values above i64::MAX aren't wrapped
+        #[derive(::serde::Serialize)]
+        struct Big { big: u64 }
+        assert!(serde_to_bin(&Big { big: u64::MAX }, &mut vec![]).is_err());
+        serde_to_bin(&Big { big: i64::MAX as u64 }, &mut vec![])?;
+
+        // Enum index is not truncated to u32
+        let mut bin = vec![];
+        text_to_bin("NAME = \"a\"\nKIND = 4294967297\n", &mut bin)?;
+        assert!(bin_to_serde::<SerdePlayer, _>(&mut Cursor::new(bin)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(feature = "yaml", feature = "msgpack", feature = "cbor"))]
+    fn yaml_msgpack_cbor_test() -> Result<()> {
+
+        let text = concat!(
+            "GID = 5\n",
+            "NAME = \"game\"\n",
+            "RAW = \"\\xff\\x01\"\n",
+            "RATE = float(0.1)\n",
+            "PROS = list(Map) [\n",
+            "    {\n",
+            "        @PNAM = \"a\"\n",
+            "    }\n",
+            "]\n",
+            "ATTR = pair_list(Int, String) {\n",
+            "    1 = \"one\"\n",
+            "}\n",
+            "BLOB = blob(00ff)\n",
+            "ADDR = union(2) VALU = {\n",
+            "    PORT = 3659\n",
+            "}\n",
+            "UNST = union(unset)\n",
+            "GEN = generic(42) VALU = 7\n",
+            "IGEN = generic(invalid)\n",
+            "INTS = int_list(1, -2)\n",
+            "OBJT = object_type(4/1)\n",
+            "OBJI = object_id(4/1/9)\n",
+            "TIME = time(1600000000)\n",
+        );
+
+        let mut bin = vec![];
+        text_to_bin(text, &mut bin)?;
+
+        #[cfg(feature = "yaml")]
+        {
+            use crate::{bin_to_yaml, yaml_to_bin};
+            let yaml = bin_to_yaml(&mut Cursor::new(bin.clone()))?;
+            assert!(yaml.starts_with("GID: 5\nNAME: game\nRAW: !string ff01\nRATE: 0.1\n"));
+            assert!(yaml.contains("BLOB: !blob 00ff\n"));
+            assert!(yaml.contains("ATTR: !pair_list\n  1: one\n"));
+            assert!(yaml.contains("PROS:\n- !map\n  PNAM: a\n"));
+            assert!(yaml.contains("UNST: !union\n- 127\n"));
+            assert!(yaml.contains("OBJI: !object_id\n- 4\n- 1\n- 9\n"));
+            assert!(yaml.contains("TIME: !time 1600000000\n"));
+
+            let mut back = vec![];
+            yaml_to_bin(&yaml, &mut back)?;
+            assert_eq!(back, bin);
+
+            // Plain YAML fixture, labels are uppercased
+            let mut fixture = vec![];
+            yaml_to_bin("gid: 5\nopen: true\nrate: 0.5\npros: [{pnam: a}]\n", &mut fixture)?;
+            assert_eq!(bin_to_text(&mut Cursor::new(fixture))?, "GID = 5\nOPEN = 1\nRATE = float(0.5)\nPROS = list(Map) [\n    {\n        PNAM = \"a\"\n    }\n]\n");
+            assert!(yaml_to_bin("players: 1\n", &mut vec![]).is_err());
+            assert!(yaml_to_bin("gid: 18446744073709551615\n", &mut vec![]).is_err());
+        }
+
+        #[cfg(feature = "msgpack")]
+        {
+            use crate::{bin_to_msgpack, msgpack_to_bin};
+            let msgpack = bin_to_msgpack(&mut Cursor::new(bin.clone()))?;
+            let mut back = vec![];
+            msgpack_to_bin(&msgpack, &mut back)?;
+            assert_eq!(back, bin);
 
-}
\ No newline at end of file
+            // {"GID": u64::MAX}
+            let big = [&[0x81, 0xa3, b'G', b'I', b'D', 0xcf][..], &[0xff; 8]].concat();
+            assert!(msgpack_to_bin(&big, &mut vec![]).is_err());
+        }
+
+        #[cfg(feature = "cbor")]
+        {
+            use crate::{bin_to_cbor, cbor_to_bin};
+            let cbor = bin_to_cbor(&mut Cursor::new(bin.clone()))?;
+            let mut back = vec![];
+            cbor_to_bin(&cbor, &mut back)?;
+            assert_eq!(back, bin);
+
+            // {"GID": u64::MAX}
+            let big = [&[0xa1, 0x63, b'G', b'I', b'D', 0x1b][..], &[0xff; 8]].concat();
+            assert!(cbor_to_bin(&big, &mut vec![]).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn csv_test() -> Result<()> {
+        use crate::bin_to_csv;
+        use crate::csv::{CsvOptions, CsvBlob, CsvList, CsvUnion};
+
+        let text = concat!(
+            "GAME = {\n",
+            "    PROS = list(Map) [\n",
+            "        {\n",
+            "            PNAM = \"a, \\\"b\\\"\"\n",
+            "            STAT = {\n",
+            "                KILL = 3\n",
+            "            }\n",
+            "            DATA = blob(00ff)\n",
+            "            INTS = int_list(1, 2)\n",
+            "            ADDR = union(3) VALU = {\n",
+            "                PORT = 3659\n",
+            "            }\n",
+            "        }\n",
+            "        {\n",
+            "            PNAM = \"c\"\n",
+            "            TEAM = 1\n",
+            "            ATTR = pair_list(String, Int) {\n",
+            "                \"mode\" = 2\n",
+            "            }\n",
+            "            ADDR = union(unset)\n",
+            "        }\n",
+            "    ]\n",
+            "}\n",
+        );
+
+        let mut bin = vec![];
+        text_to_bin(text, &mut bin)?;
+
+        let csv = bin_to_csv(&mut Cursor::new(bin.clone()), "GAME.PROS", CsvOptions::default())?;
+        assert_eq!(csv, concat!(
+            "pnam,stat.kill,data,ints,addr.valu.port,team,attr.mode\n",
+            "\"a, \"\"b\"\"\",3,00ff,1;2,3659,,\n",
+            "c,,,,,1,2\n",
+        ));
+
+        let options = CsvOptions {
+            delimiter: ';',
+            header: false,
+            label_case: LabelCase::Upper,
+            blob: CsvBlob::Size,
+            list: CsvList::Columns,
+            union: CsvUnion::TypeAndMember,
+        };
+        let csv = bin_to_csv(&mut Cursor::new(bin.clone()), "GAME.PROS", options)?;
+        assert_eq!(csv, "\"a, \"\"b\"\"\";3;2;1;2;3;3659;;\nc;;;;;127;;1;2\n");
+
+        let options = CsvOptions { list: CsvList::Count, union: CsvUnion::Type, blob: CsvBlob::Base64, ..CsvOptions::default() };
+        let csv = bin_to_csv(&mut Cursor::new(bin.clone()), "GAME.PROS", options)?;
+        assert!(csv.starts_with("pnam,stat.kill,data,ints,addr,team,attr.mode\n\"a, \"\"b\"\"\",3,AP8=,2,3,,\n"));
+
+        assert!(bin_to_csv(&mut Cursor::new(bin), "GAME", CsvOptions::default()).is_err());
+
+        let text = concat!(
+            "PROS = list(Map) [\n",
+            "    {\n",
+            "        ATTR = pair_list(String, Int) {\n",
+            "            \"a.b\" = 1\n",
+            "        }\n",
+            "        ATTR = {\n",
+            "            A = {\n",
+            "                B = 2\n",
+            "            }\n",
+            "        }\n",
+            "    }\n",
+            "]\n",
+        );
+        let mut bin = vec![];
+        text_to_bin(text, &mut bin)?;
+        let err = bin_to_csv(&mut Cursor::new(bin), "PROS", CsvOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("attr.a.b"));
+        Ok(())
+    }
+
+    #[derive(Pack, Debug, PartialEq)]
+    struct JsonPlayer {
+        #[rename("PNAM")]
+        name: String,
+        #[rename("OID")]
+        object_id: ObjectId,
+    }
+
+    #[derive(Pack, Debug, PartialEq)]
+    struct JsonGame {
+        gid: u32,
+        #[rename("OPEN")]
+        is_open: bool,
+        #[rename("RATE")]
+        rate: f32,
+        #[rename("PROS")]
+        players: Vec<JsonPlayer>,
+        #[rename("HOST")]
+        host: Option<JsonPlayer>,
+        #[rename("ADDR")]
+        address: Union,
+    }
+
+    #[test]
+    fn struct_json_test() -> Result<()> {
+        let mut game = JsonGame {
+            gid: 5,
+            is_open: true,
+            rate: 1.0,
+            players: vec![JsonPlayer { name: "a".to_string(), object_id: ObjectId(4, 1, 9) }],
+            host: None,
+            address: Union::IpPairAddr { internal: IpAddress { ip: 1, maci: 0, port: 3659 }, external: IpAddress { ip: 2, maci: 0, port: 3659 }, mac_addr: 0 },
+        };
+
+        let json = struct_to_json(&mut game, false)?;
+        assert!(json.starts_with("{\n\t\"gid\": 5,\n\t\"open\": 1,\n\t\"rate\": 1.0,\n\t\"pros\": [{\n"));
+        assert_eq!(json_to_struct::<JsonGame>(&json)?, game);
+
+        let json = struct_to_json(&mut game, true)?;
+        assert!(json.contains("\"is_open\": 1"));
+        assert!(json.contains("\"players\": [{\n\t\t\"name\": \"a\",\n\t\t\"object_id\": \"4/1/9\""));
+        assert!(json.contains("\"address\": {\n\t\t\"type\": \"union\""));
+        assert!(json.contains("\"port\": 3659"));
+        assert!(!json.contains("host"));
+        assert_eq!(json_to_struct::<JsonGame>(&json)?, game);
+
+        // Types come from the struct, so floats can be written as integers and ids as arrays
+        let json = r#"{"gid": 7, "is_open": false, "RATE": 2, "players": [{"name": "b", "OID": [1, 2, 3]}],
+            "host": {"name": "h", "object_id": "0/0/1"}, "addr": {"type": "union", "union": 127}}"#;
+        let parsed: JsonGame = json_to_struct(json)?;
+        assert_eq!(parsed.rate, 2.0);
+        assert_eq!(parsed.players[0].object_id, ObjectId(1, 2, 3));
+        assert_eq!(parsed.host.map(|h| h.name), Some("h".to_string()));
+        assert_eq!(parsed.address, Union::Unset);
+        Ok(())
+    }
+
+    #[test]
+    fn json_schema_test() -> Result<()> {
+        let schema = json_schema::<JsonGame>(JsonOptions::default(), false)?;
+        assert!(schema.contains("\"$ref\": \"#/$defs/JsonGame\""));
+        assert!(schema.contains("\"open\": {\n\t\t\t\t\t\"type\": \"integer\",\n\t\t\t\t\t\"enum\": [0, 1]"));
+        assert!(schema.contains("\"required\": [\"gid\", \"open\", \"rate\", \"pros\", \"addr\"]"));
+        assert!(schema.contains("\"$ref\": \"#/$defs/JsonPlayer\""));
+        assert!(schema.contains("\"const\": \"union\""));
+
+        let schema = json_schema::<JsonGame>(JsonOptions::default(), true)?;
+        assert!(schema.contains("\"required\": [\"gid\", \"is_open\", \"rate\", \"players\", \"address\"]"));
+        assert!(schema.contains("\"object_id\": {\n\t\t\t\t\t\"type\": \"string\""));
+        Ok(())
+    }
+
+    #[test]
+    fn typescript_test() -> Result<()> {
+        let definitions = typescript_definitions::<JsonGame>(JsonOptions::default(), false)?;
+        assert!(definitions.starts_with("/** Object id written as \"component/type/id\" */\nexport type ObjectId = string;\n"));
+        assert!(definitions.contains("export interface JsonPlayer {\n    pnam: string;\n    oid: ObjectId;\n}\n"));
+        assert!(definitions.contains("export interface JsonGame {\n    gid: number;\n    open: 0 | 1;\n    rate: number | null;\n    pros: JsonPlayer[];\n    host?: JsonPlayer;\n    addr: TdfUnion;\n}\n"));
+
+        let definitions = typescript_definitions::<JsonGame>(JsonOptions::default(), true)?;
+        assert!(definitions.contains("    is_open: 0 | 1;\n"));
+        assert!(definitions.contains("    address: TdfUnion;\n"));
+        Ok(())
+    }
+
+    #[derive(Pack, Debug, PartialEq)]
+    struct JsonNode {
+        #[rename("NAME")]
+        node_name: String,
+        #[rename("KIDS")]
+        children: Vec<JsonNode>,
+    }
+
+    #[test]
+    fn recursive_schema_test() -> Result<()> {
+        let schema = JsonNode::schema();
+        let names: Vec<&str> = schema.structs().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["JsonNode"]);
+
+        // Names of nested nodes are found through the reference
+        let leaf = |name: &str| JsonNode { node_name: name.to_string(), children: vec![] };
+        let mut node = JsonNode { node_name: "root".to_string(), children: vec![JsonNode { node_name: "a".to_string(), children: vec![leaf("b")] }] };
+        let json = struct_to_json(&mut node, true)?;
+        assert!(json.contains("\"node_name\": \"b\""));
+        assert_eq!(json_to_struct::<JsonNode>(&json)?, node);
+
+        let schema = json_schema::<JsonNode>(JsonOptions::default(), false)?;
+        assert_eq!(schema.matches("\"$ref\": \"#/$defs/JsonNode\"").count(), 2);
+        let definitions = typescript_definitions::<JsonNode>(JsonOptions::default(), false)?;
+        assert!(definitions.contains("    kids: JsonNode[];\n"));
+        let script = crate::wireshark::LuaDissector::new("tree").body::<JsonNode>().generate();
+        assert!(script.contains("schema = { list = { struct = \"JsonNode\" } }"));
+        Ok(())
+    }
+
+
+}
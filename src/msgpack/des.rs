@@ -0,0 +1,68 @@
+use crate::token::*;
+use crate::value::{TdfValue, Tagged};
+use anyhow::{Result, bail};
+use rmpv::Value;
+
+
+/// Reads MessagePack into token stream
+pub struct MsgPackDeserializer {
+    pub stream: TDFTokenStream,
+    input: Vec<u8>,
+}
+
+fn read_value(mut bytes: &[u8]) -> Result<Value> {
+    let value = rmpv::decode::read_value(&mut bytes)?;
+    if !bytes.is_empty() {
+        bail!("Unexpected {} bytes after value", bytes.len());
+    }
+    Ok(value)
+}
+
+fn from_msgpack(value: Value) -> Result<Tagged> {
+    Ok(match value {
+        Value::Nil => bail!("TDF has no nil value"),
+        Value::Boolean(v) => Tagged::Bool(v),
+        Value::Integer(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(v), _) => Tagged::Int(v),
+            (None, Some(v)) => bail!("Integer {} doesn't fit into 64 bits", v),
+            _ => bail!("Invalid integer {}", n),
+        },
+        Value::F32(v) => Tagged::Float(v),
+        Value::F64(v) => Tagged::Float(v as f32),
+        Value::String(v) => match v.into_str() {
+            Some(v) => Tagged::String(v),
+            None => bail!("Invalid UTF-8 string"),
+        },
+        Value::Binary(v) => Tagged::Bytes(v),
+        Value::Array(items) => Tagged::Array(items.into_iter().map(from_msgpack).collect::<Result<_>>()?),
+        Value::Map(entries) => Tagged::Map(entries.into_iter()
+            .map(|(k, v)| Ok((from_msgpack(k)?, from_msgpack(v)?)))
+            .collect::<Result<_>>()?),
+        Value::Ext(tag, content) => Tagged::Tag(tag as u8, Box::new(from_msgpack(read_value(&content)?)?)),
+    })
+}
+
+impl MsgPackDeserializer {
+
+    pub fn new(input: &[u8]) -> Self {
+        Self {
+            stream: TDFTokenStream::new(),
+            input: input.to_vec(),
+        }
+    }
+
+    /// Parse whole input into the stream
+    pub fn des_msgpack(&mut self) -> Result<()> {
+        let value = read_value(&self.input)?;
+        self.stream = TdfValue::tagged_to_stream(from_msgpack(value)?)?;
+        Ok(())
+    }
+}
+
+impl TDFDeserializer<Vec<u8>> for MsgPackDeserializer {
+    fn deserialize(reader: &mut Vec<u8>) -> Result<TDFTokenStream> {
+        let mut des = Self::new(reader);
+        des.des_msgpack()?;
+        Ok(des.stream)
+    }
+}
@@ -0,0 +1,25 @@
+/*
+    MessagePack form of TDF, enabled by "msgpack" feature
+
+    Int, String, Float, Map and List are plain MessagePack values, Blob is
+    binary and labels are written trimmed. Other types are ext values with
+    TDF type byte as ext type, holding MessagePack of their content:
+
+    PairList    0x05  map of pairs
+    Union       0x06  [member, label, value] or [member] when unset
+    IntList     0x07  [ints]
+    ObjectType  0x08  [a, b]
+    ObjectId    0x09  [a, b, c]
+    Time        0x0B  int
+    Generic     0x0C  [id, label, value], [id] or [] when invalid
+    String      0x01  binary, for strings that aren't UTF-8
+    Map         0x03  map that starts with union marker
+
+    Types of empty List and PairList are not kept and are read back as Int
+*/
+
+mod ser;
+pub use ser::*;
+
+mod des;
+pub use des::*;
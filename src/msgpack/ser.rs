@@ -0,0 +1,52 @@
+use crate::token::*;
+use crate::value::{TdfValue, Tagged};
+use anyhow::Result;
+use rmpv::Value;
+
+
+/// Writes token stream as MessagePack
+pub struct MsgPackSerializer {
+    stream: TDFTokenStream,
+}
+
+fn to_msgpack(value: Tagged) -> Result<Value> {
+    Ok(match value {
+        Tagged::Int(v) => Value::from(v),
+        Tagged::Bool(v) => Value::Boolean(v),
+        Tagged::Float(v) => Value::F32(v),
+        Tagged::String(v) => Value::from(v),
+        Tagged::Bytes(v) => Value::Binary(v),
+        Tagged::Array(items) => Value::Array(items.into_iter().map(to_msgpack).collect::<Result<_>>()?),
+        Tagged::Map(entries) => Value::Map(entries.into_iter()
+            .map(|(k, v)| Ok((to_msgpack(k)?, to_msgpack(v)?)))
+            .collect::<Result<_>>()?),
+        Tagged::Tag(tag, value) => {
+            let mut content = Vec::new();
+            rmpv::encode::write_value(&mut content, &to_msgpack(*value)?)?;
+            Value::Ext(tag as i8, content)
+        },
+    })
+}
+
+impl MsgPackSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream
+        }
+    }
+
+    /// Write whole stream as MessagePack into the writer
+    pub fn write_msgpack(&mut self, writer: &mut Vec<u8>) -> Result<()> {
+        let stream = std::mem::replace(&mut self.stream, TDFTokenStream::new());
+        let value = to_msgpack(TdfValue::stream_to_tagged(stream)?)?;
+        rmpv::encode::write_value(writer, &value)?;
+        Ok(())
+    }
+}
+
+impl TDFSerializer<Vec<u8>> for MsgPackSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut Vec<u8>) -> Result<()> {
+        Self::new(stream).write_msgpack(writer)
+    }
+}
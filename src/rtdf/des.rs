@@ -1,11 +1,10 @@
 
 use crate::token::*;
-use crate::rtdf::{GenericContent, GenericType, ObjectId, ObjectType, IntList, Union, IpAddress, Localization, Generic, Serialize};
+use crate::rtdf::{GenericContent, GenericType, ObjectId, ObjectType, IntList, Union, IpAddress, Localization, Generic, Serialize, TdfSchema, StructSchema, FieldSchema};
 
 use anyhow::{Result, bail};
 use std::collections::HashMap;
 use std::fmt;
-use std::convert::TryInto;
 
 
 pub struct RTDFDeserializer {
@@ -37,6 +36,10 @@ impl RTDFDeserializer {
         }
     }
     pub fn des_field<S: AsRef<str>, D: Deserialize>(&mut self, label: S, value: &mut D) -> Result<()> {
+        // Absent fields are not written at all
+        if !value.is_present() {
+            return Ok(());
+        }
         self.stream.push(TDFToken::Label(label.as_ref().to_owned()));
         self.des_type::<D>()?;
         value.deserialize(self)?;
@@ -52,6 +55,14 @@ impl RTDFDeserializer {
 pub trait Deserialize {
     const TYPE: TDFToken;
     fn deserialize(&mut self, des: &mut RTDFDeserializer) -> Result<()>;
+    /// If false, map field holding this value is skipped
+    fn is_present(&self) -> bool {
+        true
+    }
+    /// Shape of the value on the wire
+    fn schema() -> TdfSchema {
+        TdfSchema::Value(Self::TYPE)
+    }
 }
 
 impl<D: Deserialize> TDFDeserializer<D> for RTDFDeserializer {
@@ -105,6 +116,9 @@ impl Deserialize for bool {
         des.stream.push(TDFToken::Int(if *self { 1 } else { 0 }));
         Ok(())
     }
+    fn schema() -> TdfSchema {
+        TdfSchema::Bool
+    }
 }
 
 impl Deserialize for String {
@@ -115,6 +129,22 @@ impl Deserialize for String {
     }
 }
 
+impl<D: Deserialize> Deserialize for Option<D> {
+    const TYPE: TDFToken = D::TYPE;
+    fn deserialize(&mut self, des: &mut RTDFDeserializer) -> Result<()> {
+        match self {
+            Some(value) => value.deserialize(des),
+            None => bail!("Attempt to write absent value outside of map field!"),
+        }
+    }
+    fn is_present(&self) -> bool {
+        self.is_some()
+    }
+    fn schema() -> TdfSchema {
+        TdfSchema::Optional(Box::new(D::schema()))
+    }
+}
+
 impl<D: Deserialize> Deserialize for Vec<D> {
     const TYPE: TDFToken = TDFToken::ListType;
     fn deserialize(&mut self, des: &mut RTDFDeserializer) -> Result<()> {
@@ -132,6 +162,10 @@ impl<D: Deserialize> Deserialize for Vec<D> {
         Ok(())
 
     }
+
+    fn schema() -> TdfSchema {
+        TdfSchema::List(Box::new(D::schema()))
+    }
 }
 
 impl<D: Deserialize, const N: usize> Deserialize for [D; N] {
@@ -151,6 +185,10 @@ impl<D: Deserialize, const N: usize> Deserialize for [D; N] {
         Ok(())
 
     }
+
+    fn schema() -> TdfSchema {
+        TdfSchema::List(Box::new(D::schema()))
+    }
 }
 
 impl Deserialize for IntList {
@@ -224,6 +262,10 @@ impl<K: Deserialize + Clone, V: Deserialize> Deserialize for HashMap<K, V> {
         Ok(())
 
     }
+
+    fn schema() -> TdfSchema {
+        TdfSchema::PairList(Box::new(K::schema()), Box::new(V::schema()))
+    }
 }
 
 impl<K: Deserialize, V: Deserialize> Deserialize for Vec<(K, V)> {
@@ -249,6 +291,10 @@ impl<K: Deserialize, V: Deserialize> Deserialize for Vec<(K, V)> {
         Ok(())
 
     }
+
+    fn schema() -> TdfSchema {
+        TdfSchema::PairList(Box::new(K::schema()), Box::new(V::schema()))
+    }
 }
 
 impl Deserialize for f32 {
@@ -384,6 +430,17 @@ impl Deserialize for IpAddress {
         Ok(())
     }
 
+    fn schema() -> TdfSchema {
+        TdfSchema::Struct(StructSchema {
+            name: "IpAddress".to_string(),
+            fields: vec![
+                FieldSchema::new("ip", "IP", u64::schema()),
+                FieldSchema::new("maci", "MACI", u64::schema()),
+                FieldSchema::new("port", "PORT", u64::schema()),
+            ],
+        })
+    }
+
 }
 
 impl Deserialize for Localization {
@@ -392,7 +449,7 @@ impl Deserialize for Localization {
 
     fn deserialize(&mut self, des: &mut RTDFDeserializer) -> Result<()> {
 
-        des.stream.push(TDFToken::Int(u32::from_be_bytes(self.0.as_bytes().try_into()?) as i64));
+        des.stream.push(TDFToken::Int(self.0 as i64));
 
         Ok(())
 
@@ -0,0 +1,92 @@
+/*
+    Blaze locale
+
+    Sent as an integer, where 4 big-endian bytes are ascii
+    language and country codes, like enUS.
+    Captures also contain 0 or other non-printable values,
+    so the raw integer is always kept.
+*/
+
+use anyhow::{Result, bail};
+use std::fmt;
+use std::str::FromStr;
+
+
+/// Locale as raw integer
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
+pub struct Localization(pub u32);
+
+impl Localization {
+
+    /// Locale from 4 ascii bytes, like *b"enUS"
+    pub const fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self(u32::from_be_bytes(bytes))
+    }
+
+    /// Locale from language and country codes
+    pub fn new(language: &str, country: &str) -> Result<Self> {
+        let (language, country) = (language.as_bytes(), country.as_bytes());
+        if language.len() != 2 || country.len() != 2 {
+            bail!("Expected 2 letter language and country, found {:?} and {:?}", language, country);
+        }
+        Ok(Self::from_bytes([language[0], language[1], country[0], country[1]]))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+
+    /// Language and country pair, if locale is made of letters
+    pub fn parts(&self) -> Option<(String, String)> {
+        let bytes = self.to_bytes();
+        if !bytes.iter().all(|b| b.is_ascii_alphabetic()) {
+            return None;
+        }
+        Some((
+            String::from_utf8_lossy(&bytes[..2]).to_string(),
+            String::from_utf8_lossy(&bytes[2..]).to_string(),
+        ))
+    }
+
+    pub fn language(&self) -> Option<String> {
+        self.parts().map(|(language, _)| language)
+    }
+
+    pub fn country(&self) -> Option<String> {
+        self.parts().map(|(_, country)| country)
+    }
+}
+
+impl From<u32> for Localization {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+/// Prints enUS like text, or hex integer if locale is not made of letters
+impl fmt::Display for Localization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.parts() {
+            Some((language, country)) => write!(f, "{}{}", language, country),
+            None => write!(f, "{:#010x}", self.0),
+        }
+    }
+}
+
+/// Parses enUS like text, hex or decimal integer
+impl FromStr for Localization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            return Ok(Self(u32::from_str_radix(hex, 16)?));
+        }
+        if let Ok(number) = s.parse::<u32>() {
+            return Ok(Self(number));
+        }
+        if s.len() == 4 && s.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Self::new(&s[..2], &s[2..]);
+        }
+        bail!("Invalid locale {:?}!", s)
+    }
+}
@@ -8,15 +8,15 @@ pub use ser::*;
 mod des;
 pub use des::*;
 
+mod object;
+pub use object::*;
 
+mod locale;
+pub use locale::*;
 
-/// TDF Object type
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub struct ObjectType(pub i64, pub i64);
+mod schema;
+pub use schema::*;
 
-/// TDF Object id
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub struct ObjectId(pub i64, pub i64, pub i64);
 
 /// TDF Integer list
 #[derive(Debug, PartialEq, Clone)]
@@ -53,10 +53,6 @@ pub enum Union {
 }
 
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Localization(pub String);
-
-
 /// Network IP address
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct IpAddress {
@@ -0,0 +1,211 @@
+/*
+    Blaze object types and ids
+
+    Both are sent as plain integers on the wire,
+    but in Blaze they are (component, type) and (component, type, entity id)
+    Text form follows Blaze notation: component/type/id
+*/
+
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+
+/// TDF Object type
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ObjectType(pub i64, pub i64);
+
+/// TDF Object id
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ObjectId(pub i64, pub i64, pub i64);
+
+impl ObjectType {
+
+    pub fn new(component: i64, type_id: i64) -> Self {
+        Self(component, type_id)
+    }
+
+    /// Blaze component the type belongs to
+    pub fn component(&self) -> i64 {
+        self.0
+    }
+
+    /// Type number inside of the component
+    pub fn type_id(&self) -> i64 {
+        self.1
+    }
+
+    /// Object id of this type with given entity id
+    pub fn with_id(&self, id: i64) -> ObjectId {
+        ObjectId(self.0, self.1, id)
+    }
+}
+
+impl ObjectId {
+
+    pub fn new(component: i64, type_id: i64, id: i64) -> Self {
+        Self(component, type_id, id)
+    }
+
+    /// Blaze component the object belongs to
+    pub fn component(&self) -> i64 {
+        self.0
+    }
+
+    /// Type number inside of the component
+    pub fn type_id(&self) -> i64 {
+        self.1
+    }
+
+    /// Entity id
+    pub fn id(&self) -> i64 {
+        self.2
+    }
+
+    /// Type part of this id
+    pub fn object_type(&self) -> ObjectType {
+        ObjectType(self.0, self.1)
+    }
+}
+
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.0, self.1)
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.0, self.1, self.2)
+    }
+}
+
+/// Parse decimal or 0x prefixed hex part of object notation
+fn parse_part(part: &str) -> Result<i64> {
+    let part = part.trim();
+    let parsed = match part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => part.parse::<i64>(),
+    };
+    match parsed {
+        Ok(number) => Ok(number),
+        Err(_) => bail!("Invalid object notation part {:?}!", part),
+    }
+}
+
+impl FromStr for ObjectType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 2 {
+            bail!("Expected Object type as component/type, found {:?}", s);
+        }
+        Ok(Self(parse_part(parts[0])?, parse_part(parts[1])?))
+    }
+}
+
+impl FromStr for ObjectId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 3 {
+            bail!("Expected Object id as component/type/id, found {:?}", s);
+        }
+        Ok(Self(parse_part(parts[0])?, parse_part(parts[1])?, parse_part(parts[2])?))
+    }
+}
+
+
+/// Names of Blaze components and their object types
+/// Used to print object types and ids in readable form
+#[derive(Debug, Clone, Default)]
+pub struct ObjectRegistry {
+    components: HashMap<i64, String>,
+    types: HashMap<(i64, i64), String>,
+}
+
+impl ObjectRegistry {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register name of the component
+    pub fn register_component<S: Into<String>>(&mut self, component: i64, name: S) -> &mut Self {
+        self.components.insert(component, name.into());
+        self
+    }
+
+    /// Register name of the type inside of component
+    pub fn register_type<S: Into<String>>(&mut self, component: i64, type_id: i64, name: S) -> &mut Self {
+        self.types.insert((component, type_id), name.into());
+        self
+    }
+
+    pub fn component_name(&self, component: i64) -> Option<&str> {
+        self.components.get(&component).map(|n| n.as_str())
+    }
+
+    pub fn type_name(&self, object_type: ObjectType) -> Option<&str> {
+        self.types.get(&(object_type.0, object_type.1)).map(|n| n.as_str())
+    }
+
+    /// Format object type, replacing known numbers with names
+    pub fn format_type(&self, object_type: ObjectType) -> String {
+        let component = match self.component_name(object_type.0) {
+            Some(name) => name.to_string(),
+            None => object_type.0.to_string(),
+        };
+        let type_id = match self.type_name(object_type) {
+            Some(name) => name.to_string(),
+            None => object_type.1.to_string(),
+        };
+        format!("{}/{}", component, type_id)
+    }
+
+    /// Format object id, replacing known numbers with names
+    pub fn format_id(&self, object_id: ObjectId) -> String {
+        format!("{}/{}", self.format_type(object_id.object_type()), object_id.2)
+    }
+
+    /// Parse object type given by names or numbers
+    pub fn parse_type(&self, s: &str) -> Result<ObjectType> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 2 {
+            bail!("Expected Object type as component/type, found {:?}", s);
+        }
+        let component = self.parse_component(parts[0])?;
+        let type_id = self.parse_type_id(component, parts[1])?;
+        Ok(ObjectType(component, type_id))
+    }
+
+    /// Parse object id given by names or numbers
+    pub fn parse_id(&self, s: &str) -> Result<ObjectId> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 3 {
+            bail!("Expected Object id as component/type/id, found {:?}", s);
+        }
+        let component = self.parse_component(parts[0])?;
+        let type_id = self.parse_type_id(component, parts[1])?;
+        Ok(ObjectId(component, type_id, parse_part(parts[2])?))
+    }
+
+    fn parse_component(&self, part: &str) -> Result<i64> {
+        let part = part.trim();
+        match self.components.iter().find(|(_, name)| name.as_str() == part) {
+            Some((component, _)) => Ok(*component),
+            None => parse_part(part),
+        }
+    }
+
+    fn parse_type_id(&self, component: i64, part: &str) -> Result<i64> {
+        let part = part.trim();
+        match self.types.iter().find(|((c, _), name)| *c == component && name.as_str() == part) {
+            Some(((_, type_id), _)) => Ok(*type_id),
+            None => parse_part(part),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/*
+    Schema of Rust types, as they are written in TDF
+
+    Derived structs describe their fields with Rust names and labels,
+    so tools can name values of decoded messages.
+    Struct nested in itself is referenced by name, so recursive structs have finite schema.
+*/
+
+use crate::token::TDFToken;
+use std::cell::RefCell;
+
+thread_local! {
+    /// Names of structs whose schema is being built
+    static BUILDING: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+
+/// Shape of TDF value
+#[derive(Debug, Clone, PartialEq)]
+pub enum TdfSchema {
+    /// Value described only by its type, like Int, String or Union
+    Value(TDFToken),
+    /// Int holding 0 or 1
+    Bool,
+    Struct(StructSchema),
+    /// Struct described higher up in the same schema, by its name
+    Ref(String),
+    List(Box<TdfSchema>),
+    PairList(Box<TdfSchema>, Box<TdfSchema>),
+    /// Map field that may be absent
+    Optional(Box<TdfSchema>),
+}
+
+/// Map with known fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    /// Name of Rust field
+    pub name: String,
+    /// Label on the wire, as it is written in struct
+    pub label: String,
+    pub schema: TdfSchema,
+}
+
+impl FieldSchema {
+    pub fn new<N: Into<String>, L: Into<String>>(name: N, label: L, schema: TdfSchema) -> Self {
+        Self {
+            name: name.into(),
+            label: label.into(),
+            schema,
+        }
+    }
+}
+
+impl TdfSchema {
+
+    /// Schema of struct with fields built by the closure.
+    /// Struct already being built higher up becomes a reference by name
+    pub fn named_struct<F: FnOnce() -> Vec<FieldSchema>>(name: &str, fields: F) -> Self {
+        if BUILDING.with(|building| building.borrow().iter().any(|n| n == name)) {
+            return Self::Ref(name.to_string());
+        }
+        BUILDING.with(|building| building.borrow_mut().push(name.to_string()));
+        let fields = fields();
+        BUILDING.with(|building| building.borrow_mut().pop());
+        Self::Struct(StructSchema {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    /// Type token written on the wire
+    pub fn type_token(&self) -> TDFToken {
+        match self {
+            Self::Value(token) => token.clone(),
+            Self::Bool => TDFToken::IntType,
+            Self::Struct(_) | Self::Ref(_) => TDFToken::MapType,
+            Self::List(_) => TDFToken::ListType,
+            Self::PairList(_, _) => TDFToken::PairListType,
+            Self::Optional(inner) => inner.type_token(),
+        }
+    }
+
+    /// Every struct used by this schema, each one once, nested ones first
+    pub fn structs(&self) -> Vec<&StructSchema> {
+        let mut out = Vec::new();
+        self.collect_structs(&mut out);
+        out
+    }
+
+    fn collect_structs<'a>(&'a self, out: &mut Vec<&'a StructSchema>) {
+        match self {
+            Self::Struct(schema) => {
+                for field in &schema.fields {
+                    field.schema.collect_structs(out);
+                }
+                if !out.iter().any(|s| s.name == schema.name) {
+                    out.push(schema);
+                }
+            },
+            Self::List(item) | Self::Optional(item) => item.collect_structs(out),
+            Self::PairList(key, value) => {
+                key.collect_structs(out);
+                value.collect_structs(out);
+            },
+            _ => {},
+        }
+    }
+}
@@ -80,20 +80,50 @@ impl RTDFSerializer {
         Ok((label_string, expected_type))
     }
 
-    pub fn ser_field_optional<T: Serialize>(&mut self, match_label: TDFToken) -> Result<Option<T>> {
-        let label = self.stream.next()?;
-
-        // Move cursor back
-        self.stream.1 -= 1;
+    /// Read field with given label
+    /// If the next field has other label or map ended, absent value of the type is used,
+    /// types without absent value are read from the next field in order
+    pub fn ser_named_field<T: Serialize>(&mut self, label: &str) -> Result<T> {
+
+        let matches = match self.peek_label()? {
+            Some(next_label) => normalize_label(&next_label) == normalize_label(label),
+            None => false,
+        };
 
-        if label != match_label {
-            return Ok(None)
+        if !matches {
+            if let Some(absent) = T::absent() {
+                return Ok(absent);
+            }
         }
 
         let (_, value) = self.ser_field::<T>()?;
 
-        Ok(Some(value))
+        Ok(value)
+    }
+
+    pub fn ser_field_optional<T: Serialize>(&mut self, match_label: TDFToken) -> Result<Option<T>> {
+        match match_label {
+            TDFToken::Label(label) => self.ser_named_field::<Option<T>>(&label),
+            _ => bail!(RTDFSerError::NotExpectedToken(TDFToken::Label(String::new()), match_label)),
+        }
+    }
+
+    /// Label of the next map field without moving cursor, None if map ends
+    pub fn peek_label(&self) -> Result<Option<String>> {
 
+        let mut position = self.stream.1;
+        let mut token = self.stream.get(position)?;
+
+        if token == TDFToken::MapUnion {
+            position += 1;
+            token = self.stream.get(position)?;
+        }
+
+        match token {
+            TDFToken::Label(label) => Ok(Some(label)),
+            TDFToken::MapEnd => Ok(None),
+            _ => bail!(RTDFSerError::NotExpectedToken(TDFToken::Label(String::new()), token)),
+        }
     }
 
     /// Get map start token
@@ -158,7 +188,16 @@ impl Serialize for u32 {
 impl Serialize for bool {
     fn serialize(ser: &mut RTDFSerializer) -> Result<Self> {
         let num = i64::serialize(ser)?;
-        Ok(num == 0)
+        Ok(num != 0)
+    }
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(ser: &mut RTDFSerializer) -> Result<Self> {
+        Ok(Some(T::serialize(ser)?))
+    }
+    fn absent() -> Option<Self> {
+        Some(None)
     }
 }
 
@@ -604,8 +643,7 @@ impl Serialize for IpAddress {
 impl Serialize for Localization {
     fn serialize(ser: &mut RTDFSerializer) -> Result<Self> {
         let num = u32::serialize(ser)?;
-        let loc = String::from_utf8(num.to_be_bytes().to_vec())?;
-        Ok(Localization(loc))
+        Ok(Localization(num))
     }
 }
 
@@ -619,6 +657,10 @@ pub trait RustSerialize {
 /// Provides possibility to ser this struct
 pub trait Serialize: Sized {
     fn serialize(ser: &mut RTDFSerializer) -> Result<Self>;
+    /// Value of the map field, which is not sent. None if field is required
+    fn absent() -> Option<Self> {
+        None
+    }
 }
 
 /// Constructor for Rust structs
@@ -0,0 +1,261 @@
+use super::SerdeError;
+use crate::token::{TDFToken, TDFTokenStream, UnionType, normalize_label};
+use ::serde::de::{self, Visitor, DeserializeSeed, MapAccess, SeqAccess, IntoDeserializer};
+use ::serde::forward_to_deserialize_any;
+use std::convert::TryFrom;
+
+type Result<T> = std::result::Result<T, SerdeError>;
+
+/// Serde deserializer reading one value of given type from token stream
+pub(crate) struct TokenDeserializer<'a> {
+    stream: &'a mut TDFTokenStream,
+    value_type: TDFToken,
+}
+
+impl<'a> TokenDeserializer<'a> {
+    pub(crate) fn new(stream: &'a mut TDFTokenStream, value_type: TDFToken) -> Self {
+        Self { stream, value_type }
+    }
+
+    fn expect(&mut self, expected: TDFToken) -> Result<()> {
+        let token = self.stream.next()?;
+        if token != expected {
+            return Err(SerdeError(format!("Expected {:?}, found {:?}", expected, token)));
+        }
+        Ok(())
+    }
+}
+
+fn unexpected(expected: &str, token: TDFToken) -> SerdeError {
+    SerdeError(format!("Expected {}, found {:?}", expected, token))
+}
+
+impl<'de, 'a> de::Deserializer<'de> for TokenDeserializer<'a> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        match self.value_type.clone() {
+            TDFToken::IntType | TDFToken::TimeType => match self.stream.next()? {
+                TDFToken::Int(n) => visitor.visit_i64(n),
+                token => Err(unexpected("Integer", token)),
+            },
+            TDFToken::StringType => match self.stream.next()? {
+                TDFToken::String(bytes) => match String::from_utf8(bytes) {
+                    Ok(s) => visitor.visit_string(s),
+                    Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+                },
+                token => Err(unexpected("String", token)),
+            },
+            TDFToken::BlobType => match self.stream.next()? {
+                TDFToken::Blob(blob) => visitor.visit_byte_buf(blob),
+                token => Err(unexpected("Blob", token)),
+            },
+            TDFToken::FloatType => match self.stream.next()? {
+                TDFToken::Float(f) => visitor.visit_f32(f),
+                token => Err(unexpected("Float", token)),
+            },
+            TDFToken::MapType => {
+                self.expect(TDFToken::MapStart)?;
+                visitor.visit_map(MapReader { stream: self.stream, fields: &[], value_type: None })
+            },
+            TDFToken::ListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::ListStart(size) => size,
+                    token => return Err(unexpected("List", token)),
+                };
+                let item_type = self.stream.next()?;
+                let value = visitor.visit_seq(SeqReader { stream: &mut *self.stream, remaining: size, item_type })?;
+                self.expect(TDFToken::ListEnd)?;
+                Ok(value)
+            },
+            TDFToken::IntListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::IntListStart(size) => size,
+                    token => return Err(unexpected("Int List", token)),
+                };
+                let value = visitor.visit_seq(SeqReader { stream: &mut *self.stream, remaining: size, item_type: TDFToken::IntType })?;
+                self.expect(TDFToken::IntListEnd)?;
+                Ok(value)
+            },
+            TDFToken::ObjectTypeType => visitor.visit_seq(SeqReader { stream: self.stream, remaining: 2, item_type: TDFToken::IntType }),
+            TDFToken::ObjectIdType => visitor.visit_seq(SeqReader { stream: self.stream, remaining: 3, item_type: TDFToken::IntType }),
+            TDFToken::PairListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::PairListStart(size) => size,
+                    token => return Err(unexpected("Pair list", token)),
+                };
+                let key_type = self.stream.next()?;
+                let value_type = self.stream.next()?;
+                let value = visitor.visit_map(PairListReader { stream: &mut *self.stream, remaining: size, key_type, value_type })?;
+                self.expect(TDFToken::PairListEnd)?;
+                Ok(value)
+            },
+            TDFToken::UnionType => {
+                let union_type = match self.stream.next()? {
+                    TDFToken::UnionStart(union_type) => union_type,
+                    token => return Err(unexpected("Union start", token)),
+                };
+                let value = visitor.visit_seq(UnionReader { stream: &mut *self.stream, union_type, stage: 0 })?;
+                self.expect(TDFToken::UnionEnd)?;
+                Ok(value)
+            },
+            token => Err(SerdeError(format!("{:?} can't be read by serde", token))),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.value_type != TDFToken::IntType {
+            return self.deserialize_any(visitor);
+        }
+        match self.stream.next()? {
+            TDFToken::Int(n) => visitor.visit_bool(n != 0),
+            token => Err(unexpected("Integer", token)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // Values in stream are always present, missing fields are None
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        if self.value_type != TDFToken::IntType {
+            return Err(SerdeError(format!("Expected Integer for enum {}, found {:?}", name, self.value_type)));
+        }
+        match self.stream.next()? {
+            TDFToken::Int(n) => match u32::try_from(n) {
+                Ok(n) => visitor.visit_enum(n.into_deserializer()),
+                Err(_) => Err(SerdeError(format!("Variant index {} of enum {} is out of range", n, name))),
+            },
+            token => Err(unexpected("Integer", token)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(mut self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        if self.value_type != TDFToken::MapType {
+            return self.deserialize_any(visitor);
+        }
+        self.expect(TDFToken::MapStart)?;
+        visitor.visit_map(MapReader { stream: self.stream, fields, value_type: None })
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+/// Fields of Map, labels are matched to struct field names
+struct MapReader<'a> {
+    stream: &'a mut TDFTokenStream,
+    fields: &'static [&'static str],
+    value_type: Option<TDFToken>,
+}
+
+impl<'de, 'a> MapAccess<'de> for MapReader<'a> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let label = loop {
+            match self.stream.next()? {
+                TDFToken::MapEnd => return Ok(None),
+                TDFToken::MapUnion => continue,
+                TDFToken::Label(label) => break label,
+                token => return Err(unexpected("Label", token)),
+            }
+        };
+        self.value_type = Some(self.stream.next()?);
+        let key = match self.fields.iter().find(|field| normalize_label(field) == label) {
+            Some(field) => field.to_string(),
+            None => label.trim_end().to_string(),
+        };
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value_type = self.value_type.take().ok_or_else(|| SerdeError("Value of Map without label".to_string()))?;
+        seed.deserialize(TokenDeserializer::new(&mut *self.stream, value_type))
+    }
+}
+
+/// Items of List, IntList, ObjectType or ObjectId
+struct SeqReader<'a> {
+    stream: &'a mut TDFTokenStream,
+    remaining: usize,
+    item_type: TDFToken,
+}
+
+impl<'de, 'a> SeqAccess<'de> for SeqReader<'a> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(TokenDeserializer::new(&mut *self.stream, self.item_type.clone())).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct PairListReader<'a> {
+    stream: &'a mut TDFTokenStream,
+    remaining: usize,
+    key_type: TDFToken,
+    value_type: TDFToken,
+}
+
+impl<'de, 'a> MapAccess<'de> for PairListReader<'a> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(TokenDeserializer::new(&mut *self.stream, self.key_type.clone())).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(TokenDeserializer::new(&mut *self.stream, self.value_type.clone()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Union read as (tag, label, value), or (tag,) when unset
+struct UnionReader<'a> {
+    stream: &'a mut TDFTokenStream,
+    union_type: UnionType,
+    stage: u8,
+}
+
+impl<'de, 'a> SeqAccess<'de> for UnionReader<'a> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.stage += 1;
+        match self.stage {
+            1 => seed.deserialize((self.union_type as u8).into_deserializer()).map(Some),
+            _ if self.union_type == UnionType::Unset => Ok(None),
+            2 => match self.stream.next()? {
+                TDFToken::Label(label) => seed.deserialize(label.trim_end().to_string().into_deserializer()).map(Some),
+                token => Err(unexpected("Label", token)),
+            },
+            3 => {
+                let value_type = self.stream.next()?;
+                seed.deserialize(TokenDeserializer::new(&mut *self.stream, value_type)).map(Some)
+            },
+            _ => Ok(None),
+        }
+    }
+}
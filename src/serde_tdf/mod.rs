@@ -0,0 +1,190 @@
+/*
+    Bridge between serde and TDF token stream, enabled by "serde" feature
+
+    Types deriving serde Serialize/Deserialize can be written to TDF
+    without implementing Pack:
+
+    #[derive(Serialize, Deserialize)]
+    struct Game {
+        gid: u32,
+        #[serde(rename = "PROS")]
+        players: Vec<Player>,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    }
+
+    Structs are Maps labeled by field names, names longer than 4 chars
+    have to be renamed. Sequences and tuples are Lists, maps are PairLists,
+    byte buffers are Blobs, bools and integers are Ints, floats are Floats.
+    None fields are left out, unit enum variants are Ints of variant index.
+    ObjectType, ObjectId, IntList and TdfUnion are written as their TDF types
+*/
+
+mod ser;
+mod des;
+
+use crate::token::{TDFTokenStream, TDFToken, UnionType};
+use crate::rtdf::{ObjectType, ObjectId, IntList};
+use ::serde::{Serialize, Serializer, Deserialize, Deserializer};
+use ::serde::de::{self, DeserializeOwned, Visitor, SeqAccess};
+use std::fmt;
+use std::marker::PhantomData;
+
+pub(crate) const OBJECT_TYPE: &str = "$tdf::ObjectType";
+pub(crate) const OBJECT_ID: &str = "$tdf::ObjectId";
+pub(crate) const INT_LIST: &str = "$tdf::IntList";
+pub(crate) const UNION: &str = "$tdf::Union";
+
+/// Error of serde bridge
+#[derive(Debug)]
+pub struct SerdeError(String);
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ::serde::ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+impl From<anyhow::Error> for SerdeError {
+    fn from(e: anyhow::Error) -> Self {
+        SerdeError(e.to_string())
+    }
+}
+
+/// Token stream of serde serializable struct
+pub fn to_stream<T: Serialize>(value: &T) -> anyhow::Result<TDFTokenStream> {
+    match value.serialize(ser::TokenSerializer)?.into_value()? {
+        Some((TDFToken::MapType, tokens)) => {
+            let mut stream = TDFTokenStream::new();
+            stream.push(TDFToken::MapType);
+            for token in tokens {
+                stream.push(token);
+            }
+            Ok(stream)
+        },
+        Some((value_type, _)) => anyhow::bail!("Expected struct or Map as root, found {:?}", value_type),
+        None => anyhow::bail!("Root value is None"),
+    }
+}
+
+/// Serde deserializable struct from token stream
+pub fn from_stream<T: DeserializeOwned>(mut stream: TDFTokenStream) -> anyhow::Result<T> {
+    let value_type = stream.next()?;
+    Ok(T::deserialize(des::TokenDeserializer::new(&mut stream, value_type))?)
+}
+
+/// Network union of serde value, written as TDF Union
+#[derive(Debug, Clone, PartialEq)]
+pub enum TdfUnion<T> {
+    /// Member of union type with label and value
+    Member(UnionType, String, T),
+    Unset,
+}
+
+/// Visitor of helper types, written as newtype structs with special names
+struct Newtype<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Newtype<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TDF value")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        T::deserialize(deserializer)
+    }
+}
+
+impl Serialize for ObjectType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(OBJECT_TYPE, &(self.0, self.1))
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (a, b) = deserializer.deserialize_newtype_struct(OBJECT_TYPE, Newtype::<(i64, i64)>(PhantomData))?;
+        Ok(ObjectType(a, b))
+    }
+}
+
+impl Serialize for ObjectId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(OBJECT_ID, &(self.0, self.1, self.2))
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (a, b, c) = deserializer.deserialize_newtype_struct(OBJECT_ID, Newtype::<(i64, i64, i64)>(PhantomData))?;
+        Ok(ObjectId(a, b, c))
+    }
+}
+
+impl Serialize for IntList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(INT_LIST, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for IntList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(IntList(deserializer.deserialize_newtype_struct(INT_LIST, Newtype::<Vec<i64>>(PhantomData))?))
+    }
+}
+
+/// Union is written as (tag, label, value), or (tag,) when unset
+impl<T: Serialize> Serialize for TdfUnion<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TdfUnion::Member(union_type, label, value) => serializer.serialize_newtype_struct(UNION, &(*union_type as u8, label, value)),
+            TdfUnion::Unset => serializer.serialize_newtype_struct(UNION, &(UnionType::Unset as u8,)),
+        }
+    }
+}
+
+struct UnionVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for UnionVisitor<T> {
+    type Value = TdfUnion<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TDF union")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let tag: u8 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let union_type = UnionType::from_tag(tag);
+        if union_type == UnionType::Unset {
+            return Ok(TdfUnion::Unset);
+        }
+        let label: String = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let value: T = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        Ok(TdfUnion::Member(union_type, label, value))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for TdfUnion<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(UNION, UnionVisitor(PhantomData))
+    }
+}
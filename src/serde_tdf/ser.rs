@@ -0,0 +1,334 @@
+use super::{SerdeError, OBJECT_TYPE, OBJECT_ID, INT_LIST, UNION};
+use crate::token::{TDFToken, UnionType, normalize_label};
+use ::serde::ser::{self, Serialize, Impossible};
+use std::convert::TryFrom;
+
+type Result<T> = std::result::Result<T, SerdeError>;
+
+/// Type and tokens of one value
+type Value = (TDFToken, Vec<TDFToken>);
+
+/// Value written by TokenSerializer
+pub(crate) enum Encoded {
+    /// None, left out of the map
+    Absent,
+    Value(TDFToken, Vec<TDFToken>),
+    /// Items of sequence or tuple, List unless read by helper type
+    Seq(Vec<Value>),
+}
+
+impl Encoded {
+    pub(crate) fn into_value(self) -> Result<Option<Value>> {
+        Ok(match self {
+            Encoded::Absent => None,
+            Encoded::Value(value_type, tokens) => Some((value_type, tokens)),
+            Encoded::Seq(items) => {
+                // Type of empty list is unknown, Int is used
+                let item_type = items.first().map(|(t, _)| t.clone()).unwrap_or(TDFToken::IntType);
+                let mut tokens = vec![TDFToken::ListStart(items.len()), item_type.clone()];
+                for (value_type, value) in items {
+                    if value_type != item_type {
+                        return Err(SerdeError(format!("List items have to be of one type, found {:?} and {:?}", item_type, value_type)));
+                    }
+                    tokens.extend(value);
+                }
+                tokens.push(TDFToken::ListEnd);
+                Some((TDFToken::ListType, tokens))
+            },
+        })
+    }
+}
+
+fn int(n: i64) -> Result<Encoded> {
+    Ok(Encoded::Value(TDFToken::IntType, vec![TDFToken::Int(n)]))
+}
+
+fn unsupported(what: &str) -> SerdeError {
+    SerdeError(format!("{} can't be written to TDF", what))
+}
+
+/// Items of helper type, written as tuple or sequence
+fn items(inner: Encoded, name: &str) -> Result<Vec<Value>> {
+    match inner {
+        Encoded::Seq(items) => Ok(items),
+        _ => Err(SerdeError(format!("Expected sequence in {}", name))),
+    }
+}
+
+/// Integers of ObjectType, ObjectId and IntList
+fn ints(items: Vec<Value>, name: &str) -> Result<Vec<TDFToken>> {
+    items.into_iter().map(|item| match item {
+        (TDFToken::IntType, mut tokens) if tokens.len() == 1 => Ok(tokens.remove(0)),
+        (value_type, _) => Err(SerdeError(format!("Expected Integer in {}, found {:?}", name, value_type))),
+    }).collect()
+}
+
+fn union(items: Vec<Value>) -> Result<Encoded> {
+    let mut items = items.into_iter();
+    let tag = match items.next() {
+        Some((TDFToken::IntType, tokens)) => match tokens.as_slice() {
+            [TDFToken::Int(tag)] if (0..=0x7F).contains(tag) => *tag as u8,
+            _ => return Err(SerdeError("Invalid Union tag".to_string())),
+        },
+        _ => return Err(SerdeError("Expected Union tag".to_string())),
+    };
+    let union_type = UnionType::from_tag(tag);
+    if union_type == UnionType::Unset && tag != UnionType::Unset as u8 {
+        return Err(SerdeError(format!("Unknown Union type {}", tag)));
+    }
+    let mut tokens = vec![TDFToken::UnionStart(union_type)];
+    if union_type != UnionType::Unset {
+        match (items.next(), items.next()) {
+            (Some((TDFToken::StringType, label)), Some((value_type, value))) => {
+                match label.as_slice() {
+                    [TDFToken::String(label)] => tokens.push(TDFToken::Label(normalize_label(&String::from_utf8_lossy(label)))),
+                    _ => return Err(SerdeError("Expected Union label".to_string())),
+                }
+                tokens.push(value_type);
+                tokens.extend(value);
+            },
+            _ => return Err(SerdeError("Expected label and value of Union".to_string())),
+        }
+    }
+    tokens.push(TDFToken::UnionEnd);
+    Ok(Encoded::Value(TDFToken::UnionType, tokens))
+}
+
+/// Serde serializer writing rust values as TDF tokens
+pub(crate) struct TokenSerializer;
+
+impl ser::Serializer for TokenSerializer {
+    type Ok = Encoded;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Impossible<Encoded, SerdeError>;
+    type SerializeMap = PairListSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = Impossible<Encoded, SerdeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Encoded> { int(v as i64) }
+    fn serialize_i8(self, v: i8) -> Result<Encoded> { int(v as i64) }
+    fn serialize_i16(self, v: i16) -> Result<Encoded> { int(v as i64) }
+    fn serialize_i32(self, v: i32) -> Result<Encoded> { int(v as i64) }
+    fn serialize_i64(self, v: i64) -> Result<Encoded> { int(v) }
+    fn serialize_u8(self, v: u8) -> Result<Encoded> { int(v as i64) }
+    fn serialize_u16(self, v: u16) -> Result<Encoded> { int(v as i64) }
+    fn serialize_u32(self, v: u32) -> Result<Encoded> { int(v as i64) }
+    fn serialize_u64(self, v: u64) -> Result<Encoded> {
+        match i64::try_from(v) {
+            Ok(n) => int(n),
+            Err(_) => Err(SerdeError(format!("Integer {} doesn't fit into TDF Int", v))),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Encoded> {
+        Ok(Encoded::Value(TDFToken::FloatType, vec![TDFToken::Float(v)]))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Encoded> {
+        self.serialize_f32(v as f32)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Encoded> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Encoded> {
+        Ok(Encoded::Value(TDFToken::StringType, vec![TDFToken::String(v.as_bytes().to_vec())]))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Encoded> {
+        Ok(Encoded::Value(TDFToken::BlobType, vec![TDFToken::Blob(v.to_vec())]))
+    }
+
+    fn serialize_none(self) -> Result<Encoded> {
+        Ok(Encoded::Absent)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Encoded> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Encoded> {
+        Err(unsupported("Unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Encoded> {
+        Ok(Encoded::Value(TDFToken::MapType, vec![TDFToken::MapStart, TDFToken::MapEnd]))
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<Encoded> {
+        int(index as i64)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T) -> Result<Encoded> {
+        let inner = value.serialize(TokenSerializer)?;
+        match name {
+            OBJECT_TYPE | OBJECT_ID => {
+                let (value_type, size) = if name == OBJECT_TYPE { (TDFToken::ObjectTypeType, 2) } else { (TDFToken::ObjectIdType, 3) };
+                let tokens = ints(items(inner, name)?, name)?;
+                if tokens.len() != size {
+                    return Err(SerdeError(format!("Expected {} integers in {}", size, name)));
+                }
+                Ok(Encoded::Value(value_type, tokens))
+            },
+            INT_LIST => {
+                let values = ints(items(inner, name)?, name)?;
+                let mut tokens = vec![TDFToken::IntListStart(values.len())];
+                tokens.extend(values);
+                tokens.push(TDFToken::IntListEnd);
+                Ok(Encoded::Value(TDFToken::IntListType, tokens))
+            },
+            UNION => union(items(inner, name)?),
+            _ => Ok(inner),
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, _index: u32, variant: &'static str, _value: &T) -> Result<Encoded> {
+        Err(unsupported(&format!("Enum variant with data {}::{}", name, variant)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer { items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant> {
+        Err(unsupported(&format!("Enum variant with data {}::{}", name, variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<PairListSerializer> {
+        Ok(PairListSerializer { pairs: Vec::new(), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapSerializer> {
+        Ok(MapSerializer { tokens: vec![TDFToken::MapStart] })
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant> {
+        Err(unsupported(&format!("Enum variant with data {}::{}", name, variant)))
+    }
+}
+
+/// Value that has to be present, like item of List
+fn present<T: ?Sized + Serialize>(value: &T, what: &str) -> Result<Value> {
+    value.serialize(TokenSerializer)?.into_value()?.ok_or_else(|| unsupported(&format!("None {}", what)))
+}
+
+pub(crate) struct SeqSerializer {
+    items: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Encoded;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.items.push(present(value, "item of List")?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Encoded> {
+        Ok(Encoded::Seq(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Encoded;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Encoded;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub(crate) struct PairListSerializer {
+    pairs: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for PairListSerializer {
+    type Ok = Encoded;
+    type Error = SerdeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(present(key, "key of Pair list")?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().ok_or_else(|| SerdeError("Value of Pair list without key".to_string()))?;
+        self.pairs.push((key, present(value, "value of Pair list")?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Encoded> {
+        // Types of empty pair list are unknown, Int is used
+        let (key_type, value_type) = match self.pairs.first() {
+            Some(((key_type, _), (value_type, _))) => (key_type.clone(), value_type.clone()),
+            None => (TDFToken::IntType, TDFToken::IntType),
+        };
+        let mut tokens = vec![TDFToken::PairListStart(self.pairs.len()), key_type.clone(), value_type.clone()];
+        for ((k_type, key), (v_type, value)) in self.pairs {
+            if k_type != key_type || v_type != value_type {
+                return Err(SerdeError(format!("Pair list entries have to be of one type, found {:?}: {:?} and {:?}: {:?}", key_type, value_type, k_type, v_type)));
+            }
+            tokens.extend(key);
+            tokens.extend(value);
+        }
+        tokens.push(TDFToken::PairListEnd);
+        Ok(Encoded::Value(TDFToken::PairListType, tokens))
+    }
+}
+
+pub(crate) struct MapSerializer {
+    tokens: Vec<TDFToken>,
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Encoded;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        if key.chars().count() > 4 {
+            return Err(SerdeError(format!("Field {} is longer than TDF label, use #[serde(rename = \"...\")]", key)));
+        }
+        if let Some((value_type, tokens)) = value.serialize(TokenSerializer)?.into_value()? {
+            self.tokens.push(TDFToken::Label(normalize_label(key)));
+            self.tokens.push(value_type);
+            self.tokens.extend(tokens);
+        }
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<Encoded> {
+        self.tokens.push(TDFToken::MapEnd);
+        Ok(Encoded::Value(TDFToken::MapType, self.tokens))
+    }
+}
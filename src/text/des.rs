@@ -0,0 +1,437 @@
+use crate::token::*;
+use crate::hex::unhex;
+use anyhow::{Result, bail};
+use std::convert::TryFrom;
+
+
+/// Parses text notation into token stream
+pub struct TextDeserializer {
+    pub stream: TDFTokenStream,
+    input: Vec<char>,
+    cursor: usize,
+}
+
+/// Type token by its name in text
+pub fn type_from_name(name: &str) -> Result<TDFToken> {
+    Ok(match name {
+        "Int"        => TDFToken::IntType,
+        "String"     => TDFToken::StringType,
+        "Blob"       => TDFToken::BlobType,
+        "Map"        => TDFToken::MapType,
+        "List"       => TDFToken::ListType,
+        "PairList"   => TDFToken::PairListType,
+        "Union"      => TDFToken::UnionType,
+        "IntList"    => TDFToken::IntListType,
+        "ObjectType" => TDFToken::ObjectTypeType,
+        "ObjectId"   => TDFToken::ObjectIdType,
+        "Float"      => TDFToken::FloatType,
+        "Time"       => TDFToken::TimeType,
+        "Generic"    => TDFToken::GenericType,
+        _ => bail!("Unknown type {:?}", name),
+    })
+}
+
+impl TextDeserializer {
+
+    pub fn new(input: &str) -> Self {
+        Self {
+            stream: TDFTokenStream::new(),
+            input: input.chars().collect(),
+            cursor: 0,
+        }
+    }
+
+    /// Parse whole input as fields of the root map
+    pub fn des_text(&mut self) -> Result<()> {
+        self.stream.push(TDFToken::MapType);
+        let tokens = self.des_fields(None)?;
+        for token in tokens {
+            self.stream.push(token);
+        }
+        Ok(())
+    }
+
+    /// Line and column of the cursor, for errors
+    fn position(&self) -> String {
+        let before = &self.input[..self.cursor.min(self.input.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        format!("{}:{}", line, column)
+    }
+
+    /// Skip spaces and comments, returns true if new line was passed
+    fn skip_space(&mut self) -> bool {
+        let mut new_line = false;
+        while let Some(c) = self.input.get(self.cursor) {
+            match c {
+                '#' => {
+                    while self.input.get(self.cursor).is_some_and(|c| *c != '\n') {
+                        self.cursor += 1;
+                    }
+                },
+                '\n' => {
+                    new_line = true;
+                    self.cursor += 1;
+                },
+                c if c.is_whitespace() => self.cursor += 1,
+                _ => break,
+            }
+        }
+        new_line
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.input.get(self.cursor).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.cursor += 1;
+                Ok(())
+            },
+            Some(c) => bail!("Expected {:?}, found {:?} at {}", expected, c, self.position()),
+            None => bail!("Expected {:?}, found end of text", expected),
+        }
+    }
+
+    /// Letters, digits and underscores
+    fn read_word(&mut self) -> Result<String> {
+        self.skip_space();
+        let start = self.cursor;
+        while self.input.get(self.cursor).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            self.cursor += 1;
+        }
+        if start == self.cursor {
+            bail!("Expected word at {}", self.position());
+        }
+        Ok(self.input[start..self.cursor].iter().collect())
+    }
+
+    fn read_label(&mut self) -> Result<TDFToken> {
+        let label = self.read_word()?;
+        if label.chars().count() > 4 {
+            bail!("Label {:?} is longer than 4 chars at {}", label, self.position());
+        }
+        Ok(TDFToken::Label(normalize_label(&label)))
+    }
+
+    /// Decimal or 0x hex number, may be negative
+    fn read_int(&mut self) -> Result<i64> {
+        self.skip_space();
+        let negative = self.input.get(self.cursor) == Some(&'-');
+        if negative {
+            self.cursor += 1;
+        }
+        let word = self.read_word()?;
+        if word.starts_with(['+', '-']) {
+            bail!("Expected integer, found {:?} at {}", word, self.position());
+        }
+        let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+            Some(hex) => i128::from_str_radix(hex, 16),
+            None => word.parse::<i128>(),
+        };
+        let value = match value {
+            Ok(value) if negative => -value,
+            Ok(value) => value,
+            Err(_) => bail!("Expected integer, found {:?} at {}", word, self.position()),
+        };
+        match i64::try_from(value) {
+            Ok(value) => Ok(value),
+            Err(_) => bail!("Integer {} doesn't fit into 64 bits at {}", value, self.position()),
+        }
+    }
+
+    fn read_float(&mut self) -> Result<f32> {
+        self.skip_space();
+        let start = self.cursor;
+        while self.input.get(self.cursor).is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')) {
+            self.cursor += 1;
+        }
+        let number: String = self.input[start..self.cursor].iter().collect();
+        match number.parse::<f32>() {
+            Ok(value) => Ok(value),
+            Err(_) => bail!("Expected float, found {:?} at {}", number, self.position()),
+        }
+    }
+
+    fn read_string(&mut self) -> Result<Vec<u8>> {
+        self.expect('"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let c = match self.input.get(self.cursor) {
+                Some(c) => *c,
+                None => bail!("Unterminated string"),
+            };
+            self.cursor += 1;
+            match c {
+                '"' => return Ok(bytes),
+                '\\' => {
+                    let escaped = match self.input.get(self.cursor) {
+                        Some(c) => *c,
+                        None => bail!("Unterminated string"),
+                    };
+                    self.cursor += 1;
+                    match escaped {
+                        'n' => bytes.push(b'\n'),
+                        'r' => bytes.push(b'\r'),
+                        't' => bytes.push(b'\t'),
+                        '0' => bytes.push(0),
+                        'x' => {
+                            let hex: String = self.input.iter().skip(self.cursor).take(2).collect();
+                            match u8::from_str_radix(&hex, 16) {
+                                Ok(b) if hex.len() == 2 => bytes.push(b),
+                                _ => bail!("Bad \\x escape {:?} at {}", hex, self.position()),
+                            }
+                            self.cursor += 2;
+                        },
+                        c => {
+                            let mut buf = [0; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                        },
+                    }
+                },
+                c => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                },
+            }
+        }
+    }
+
+    fn read_blob(&mut self) -> Result<Vec<u8>> {
+        self.expect('(')?;
+        self.skip_space();
+        let start = self.cursor;
+        while self.input.get(self.cursor).is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.cursor += 1;
+        }
+        let hex: String = self.input[start..self.cursor].iter().collect();
+        if self.peek() != Some(')') {
+            bail!("Blob must contain only hex digits at {}, truncated blobs can't be parsed", self.position());
+        }
+        self.cursor += 1;
+        if hex.len() % 2 != 0 {
+            bail!("Blob has odd number of hex digits at {}", self.position());
+        }
+        unhex(&hex)
+    }
+
+    fn read_type(&mut self) -> Result<TDFToken> {
+        type_from_name(&self.read_word()?)
+    }
+
+    /// Fields till closing brace, or till the end for root map
+    fn des_fields(&mut self, closing: Option<char>) -> Result<Vec<TDFToken>> {
+        let mut tokens = vec![TDFToken::MapStart];
+        loop {
+            match self.peek() {
+                None if closing.is_none() => break,
+                None => bail!("Map is not closed"),
+                Some(c) if Some(c) == closing => {
+                    self.cursor += 1;
+                    break;
+                },
+                Some('@') => {
+                    self.cursor += 1;
+                    tokens.push(TDFToken::MapUnion);
+                },
+                _ => {},
+            }
+            tokens.push(self.read_label()?);
+            self.expect('=')?;
+            let (value_type, value) = self.des_value()?;
+            tokens.push(value_type);
+            tokens.extend(value);
+        }
+        tokens.push(TDFToken::MapEnd);
+        Ok(tokens)
+    }
+
+    /// Label and typed value of union or generic member
+    fn des_member(&mut self, tokens: &mut Vec<TDFToken>) -> Result<()> {
+        tokens.push(self.read_label()?);
+        self.expect('=')?;
+        let (value_type, value) = self.des_value()?;
+        tokens.push(value_type);
+        tokens.extend(value);
+        Ok(())
+    }
+
+    fn des_of_type(&mut self, expected: &TDFToken, tokens: &mut Vec<TDFToken>) -> Result<()> {
+        let (value_type, value) = self.des_value()?;
+        if value_type != *expected {
+            bail!("Expected {:?}, found {:?} at {}", expected, value_type, self.position());
+        }
+        tokens.extend(value);
+        Ok(())
+    }
+
+    /// Type of the value and its tokens
+    pub fn des_value(&mut self) -> Result<(TDFToken, Vec<TDFToken>)> {
+
+        match self.peek() {
+            Some('"') => return Ok((TDFToken::StringType, vec![TDFToken::String(self.read_string()?)])),
+            Some('{') => {
+                self.cursor += 1;
+                return Ok((TDFToken::MapType, self.des_fields(Some('}'))?));
+            },
+            Some(c) if c == '-' || c.is_ascii_digit() => return Ok((TDFToken::IntType, vec![TDFToken::Int(self.read_int()?)])),
+            Some(_) => {},
+            None => bail!("Expected value, found end of text"),
+        }
+
+        let keyword = self.read_word()?;
+        let mut tokens = Vec::new();
+
+        let value_type = match keyword.as_str() {
+            "blob" => {
+                tokens.push(TDFToken::Blob(self.read_blob()?));
+                TDFToken::BlobType
+            },
+            "float" => {
+                self.expect('(')?;
+                tokens.push(TDFToken::Float(self.read_float()?));
+                self.expect(')')?;
+                TDFToken::FloatType
+            },
+            "time" => {
+                self.expect('(')?;
+                tokens.push(TDFToken::Int(self.read_int()?));
+                self.expect(')')?;
+                TDFToken::TimeType
+            },
+            "object_type" => {
+                self.expect('(')?;
+                tokens.push(TDFToken::Int(self.read_int()?));
+                self.expect('/')?;
+                tokens.push(TDFToken::Int(self.read_int()?));
+                self.expect(')')?;
+                TDFToken::ObjectTypeType
+            },
+            "object_id" => {
+                self.expect('(')?;
+                tokens.push(TDFToken::Int(self.read_int()?));
+                self.expect('/')?;
+                tokens.push(TDFToken::Int(self.read_int()?));
+                self.expect('/')?;
+                tokens.push(TDFToken::Int(self.read_int()?));
+                self.expect(')')?;
+                TDFToken::ObjectIdType
+            },
+            "int_list" => {
+                self.expect('(')?;
+                let mut items = Vec::new();
+                while self.peek() != Some(')') {
+                    if !items.is_empty() {
+                        self.expect(',')?;
+                    }
+                    items.push(TDFToken::Int(self.read_int()?));
+                }
+                self.cursor += 1;
+                tokens.push(TDFToken::IntListStart(items.len()));
+                tokens.extend(items);
+                tokens.push(TDFToken::IntListEnd);
+                TDFToken::IntListType
+            },
+            "list" => {
+                self.expect('(')?;
+                let item_type = self.read_type()?;
+                self.expect(')')?;
+                self.expect('[')?;
+                let mut items = Vec::new();
+                let mut size = 0;
+                while self.peek() != Some(']') {
+                    self.des_of_type(&item_type, &mut items)?;
+                    size += 1;
+                }
+                self.cursor += 1;
+                tokens.push(TDFToken::ListStart(size));
+                tokens.push(item_type);
+                tokens.extend(items);
+                tokens.push(TDFToken::ListEnd);
+                TDFToken::ListType
+            },
+            "pair_list" => {
+                self.expect('(')?;
+                let key_type = self.read_type()?;
+                self.expect(',')?;
+                let value_type = self.read_type()?;
+                self.expect(')')?;
+                self.expect('{')?;
+                let mut pairs = Vec::new();
+                let mut size = 0;
+                while self.peek() != Some('}') {
+                    self.des_of_type(&key_type, &mut pairs)?;
+                    self.expect('=')?;
+                    self.des_of_type(&value_type, &mut pairs)?;
+                    size += 1;
+                }
+                self.cursor += 1;
+                tokens.push(TDFToken::PairListStart(size));
+                tokens.push(key_type);
+                tokens.push(value_type);
+                tokens.extend(pairs);
+                tokens.push(TDFToken::PairListEnd);
+                TDFToken::PairListType
+            },
+            "union" => {
+                self.expect('(')?;
+                if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    let word = self.read_word()?;
+                    if word != "unset" {
+                        bail!("Expected union index or unset, found {:?} at {}", word, self.position());
+                    }
+                    self.expect(')')?;
+                    tokens.push(TDFToken::UnionStart(UnionType::Unset));
+                } else {
+                    let index = self.read_int()?;
+                    self.expect(')')?;
+                    let union_type = UnionType::from_tag(index as u8);
+                    if union_type == UnionType::Unset {
+                        bail!("Unknown union index {} at {}", index, self.position());
+                    }
+                    tokens.push(TDFToken::UnionStart(union_type));
+                    self.des_member(&mut tokens)?;
+                }
+                tokens.push(TDFToken::UnionEnd);
+                TDFToken::UnionType
+            },
+            "generic" => {
+                self.expect('(')?;
+                if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    let word = self.read_word()?;
+                    if word != "invalid" {
+                        bail!("Expected tdf id or invalid, found {:?} at {}", word, self.position());
+                    }
+                    self.expect(')')?;
+                    tokens.push(TDFToken::GenericStart(false));
+                } else {
+                    tokens.push(TDFToken::GenericStart(true));
+                    tokens.push(TDFToken::Int(self.read_int()?));
+                    self.expect(')')?;
+                    // Member is written on the same line
+                    let new_line = self.skip_space();
+                    let has_member = !new_line && self.input.get(self.cursor).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_');
+                    if has_member {
+                        self.des_member(&mut tokens)?;
+                    }
+                }
+                tokens.push(TDFToken::GenericEnd);
+                TDFToken::GenericType
+            },
+            _ => bail!("Unknown value {:?} at {}", keyword, self.position()),
+        };
+
+        Ok((value_type, tokens))
+    }
+}
+
+impl TDFDeserializer<String> for TextDeserializer {
+    fn deserialize(reader: &mut String) -> Result<TDFTokenStream> {
+        let mut des = Self::new(reader);
+        des.des_text()?;
+        Ok(des.stream)
+    }
+}
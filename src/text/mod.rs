@@ -0,0 +1,41 @@
+/*
+    Human readable text form of TDF, in the style of Blaze debug prints
+
+    GID = 5
+    NAME = "game"
+    PROS = list(Map) [
+        {
+            PNAM = "player"
+        }
+    ]
+    ADDR = union(2) VALU = {
+        IP = 2130706433
+    }
+
+    Notation, which is parsed back as well:
+
+    LABL = value            field, label is up to 4 letters, digits or _ (space)
+    @LABL = value           field preceded by map union marker
+    # comment               till the end of line
+    5, -5, 0x1F             Int
+    "text\n\x00"            String, escapes \" \\ \n \r \t \0 and raw bytes \xNN
+    blob(01ff)              Blob in hex, truncated blob(01...5 bytes) can't be parsed
+    { LABL = value ... }    Map
+    list(Type) [ v ... ]    List of values of given type
+    pair_list(K, V) { k = v ... }
+    union(2) LABL = value   Union with type index and its member
+    union(unset)
+    generic(42) LABL = value, generic(42), generic(invalid)
+                            Generic with tdf id, member must be on the same line
+    int_list(1, 2, 3)
+    object_type(1/2), object_id(1/2/3)
+    float(1.5), time(1600000000)
+
+    Types are Int, String, Blob, Map, List, PairList, Union, IntList,
+    ObjectType, ObjectId, Float, Time and Generic.
+*/
+
+mod ser;
+pub use ser::*;
+mod des;
+pub use des::*;
@@ -0,0 +1,377 @@
+use crate::token::*;
+use crate::hex::hex;
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+
+/// Look of the text output
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// Spaces per nesting level
+    pub indent: usize,
+    /// Longer blobs are cut, such output can't be parsed back
+    pub max_blob: Option<usize>,
+    /// Comment with TDF type after every field
+    pub type_hints: bool,
+    /// Long names of labels, written as comments
+    pub labels: HashMap<String, String>,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            max_blob: None,
+            type_hints: false,
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl TextOptions {
+    /// Add long name of the label
+    pub fn label<L: AsRef<str>, N: Into<String>>(mut self, label: L, name: N) -> Self {
+        self.labels.insert(normalize_label(label.as_ref()), name.into());
+        self
+    }
+}
+
+/// Name of type as it is written in text
+pub fn type_name(token: &TDFToken) -> Result<&'static str> {
+    Ok(match token {
+        TDFToken::IntType        => "Int",
+        TDFToken::StringType     => "String",
+        TDFToken::BlobType       => "Blob",
+        TDFToken::MapType        => "Map",
+        TDFToken::ListType       => "List",
+        TDFToken::PairListType   => "PairList",
+        TDFToken::UnionType      => "Union",
+        TDFToken::IntListType    => "IntList",
+        TDFToken::ObjectTypeType => "ObjectType",
+        TDFToken::ObjectIdType   => "ObjectId",
+        TDFToken::FloatType      => "Float",
+        TDFToken::TimeType       => "Time",
+        TDFToken::GenericType    => "Generic",
+        _ => bail!("Expected type token, found {:?}", token),
+    })
+}
+
+/// Writes token stream as Blaze-like text, one field per line
+pub struct TextSerializer {
+    stream: TDFTokenStream,
+    options: TextOptions,
+}
+
+impl TextSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream,
+            options: TextOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: TextOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Write whole stream as text into the writer, root map is written without braces
+    pub fn write_text(&mut self, writer: &mut String) -> Result<()> {
+        let token = self.stream.next()?;
+        if token != TDFToken::MapType {
+            bail!("Expected root Map, found {:?}", token);
+        }
+        self.expect(TDFToken::MapStart)?;
+        writer.push_str(&self.ser_fields(0)?);
+        Ok(())
+    }
+
+    fn expect(&mut self, expected: TDFToken) -> Result<()> {
+        let token = self.stream.next()?;
+        if token != expected {
+            bail!("Expected {:?}, found {:?}", expected, token);
+        }
+        Ok(())
+    }
+
+    fn indent(&self, level: usize) -> String {
+        " ".repeat(level * self.options.indent)
+    }
+
+    pub fn ser_token(&mut self, token_type: TDFToken, level: usize) -> Result<String> {
+        match token_type {
+            TDFToken::IntType        => Ok(self.read_int()?.to_string()),
+            TDFToken::StringType     => self.ser_string(),
+            TDFToken::BlobType       => self.ser_blob(),
+            TDFToken::MapType        => self.ser_map(level),
+            TDFToken::ListType       => self.ser_list(level),
+            TDFToken::PairListType   => self.ser_pair_list(level),
+            TDFToken::UnionType      => self.ser_union(level),
+            TDFToken::IntListType    => self.ser_int_list(),
+            TDFToken::ObjectTypeType => Ok(format!("object_type({}/{})", self.read_int()?, self.read_int()?)),
+            TDFToken::ObjectIdType   => Ok(format!("object_id({}/{}/{})", self.read_int()?, self.read_int()?, self.read_int()?)),
+            TDFToken::FloatType      => self.ser_float(),
+            TDFToken::TimeType       => Ok(format!("time({})", self.read_int()?)),
+            TDFToken::GenericType    => self.ser_generic(level),
+            _ => bail!("Trying to parse type token, but found {:?}", token_type)
+        }
+    }
+
+    fn read_int(&mut self) -> Result<i64> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::Int(number) => Ok(number),
+            _ => bail!("Expected Integer, found {:?}", token),
+        }
+    }
+
+    pub fn ser_string(&mut self) -> Result<String> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::String(string) => Ok(write_string(&string)),
+            _ => bail!("Expected String, found {:?}", token),
+        }
+    }
+
+    pub fn ser_blob(&mut self) -> Result<String> {
+        let token = self.stream.next()?;
+        let blob = match token {
+            TDFToken::Blob(blob) => blob,
+            _ => bail!("Expected Blob, found {:?}", token),
+        };
+        let shown = self.options.max_blob.unwrap_or(blob.len()).min(blob.len());
+        let hex = hex(&blob[..shown], "");
+        if shown < blob.len() {
+            return Ok(format!("blob({}...{} bytes)", hex, blob.len()));
+        }
+        Ok(format!("blob({})", hex))
+    }
+
+    pub fn ser_float(&mut self) -> Result<String> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::Float(float) => Ok(format!("float({:?})", float)),
+            _ => bail!("Expected Float, found {:?}", token),
+        }
+    }
+
+    pub fn ser_map(&mut self, level: usize) -> Result<String> {
+        self.expect(TDFToken::MapStart)?;
+        let fields = self.ser_fields(level + 1)?;
+        if fields.is_empty() {
+            return Ok("{}".to_string());
+        }
+        Ok(format!("{{\n{}{}}}", fields, self.indent(level)))
+    }
+
+    /// Fields of map till its end, each one on its own line
+    fn ser_fields(&mut self, level: usize) -> Result<String> {
+        let mut output = String::new();
+        loop {
+            let mut token = self.stream.next()?;
+            let mut marker = "";
+            match token {
+                TDFToken::MapEnd => return Ok(output),
+                TDFToken::MapUnion => {
+                    marker = "@";
+                    token = self.stream.next()?;
+                },
+                _ => {},
+            }
+            let label = match token {
+                TDFToken::Label(label) => label,
+                _ => bail!("Expected Label in Map, found {:?}", token),
+            };
+            let value_type = self.stream.next()?;
+            let value = self.ser_token(value_type.clone(), level)?;
+            let comment = self.comment(&label, &value_type)?;
+            output.push_str(&self.indent(level));
+            output.push_str(marker);
+            output.push_str(&write_label(&label));
+            output.push_str(" = ");
+            output.push_str(&with_comment(&value, &comment));
+            output.push('\n');
+        }
+    }
+
+    /// Type hint and long name of the field
+    fn comment(&self, label: &str, value_type: &TDFToken) -> Result<String> {
+        let mut parts = Vec::new();
+        if self.options.type_hints {
+            parts.push(type_name(value_type)?.to_string());
+        }
+        if let Some(name) = self.options.labels.get(&normalize_label(label)) {
+            parts.push(name.clone());
+        }
+        Ok(parts.join(", "))
+    }
+
+    pub fn ser_list(&mut self, level: usize) -> Result<String> {
+        let token = self.stream.next()?;
+        let size = match token {
+            TDFToken::ListStart(size) => size,
+            _ => bail!("Expected List start, found {:?}", token),
+        };
+        let item_type = self.stream.next()?;
+        let mut output = format!("list({}) [", type_name(&item_type)?);
+        if size != 0 {
+            output.push('\n');
+        }
+        for _ in 0..size {
+            output.push_str(&self.indent(level + 1));
+            output.push_str(&self.ser_token(item_type.clone(), level + 1)?);
+            output.push('\n');
+        }
+        self.expect(TDFToken::ListEnd)?;
+        if size != 0 {
+            output.push_str(&self.indent(level));
+        }
+        output.push(']');
+        Ok(output)
+    }
+
+    pub fn ser_pair_list(&mut self, level: usize) -> Result<String> {
+        let token = self.stream.next()?;
+        let size = match token {
+            TDFToken::PairListStart(size) => size,
+            _ => bail!("Expected Pair List start, found {:?}", token),
+        };
+        let key_type = self.stream.next()?;
+        let value_type = self.stream.next()?;
+        let mut output = format!("pair_list({}, {}) {{", type_name(&key_type)?, type_name(&value_type)?);
+        if size != 0 {
+            output.push('\n');
+        }
+        for _ in 0..size {
+            output.push_str(&self.indent(level + 1));
+            output.push_str(&self.ser_token(key_type.clone(), level + 1)?);
+            output.push_str(" = ");
+            output.push_str(&self.ser_token(value_type.clone(), level + 1)?);
+            output.push('\n');
+        }
+        self.expect(TDFToken::PairListEnd)?;
+        if size != 0 {
+            output.push_str(&self.indent(level));
+        }
+        output.push('}');
+        Ok(output)
+    }
+
+    /// Member of union or generic, written inline after the header
+    fn ser_member(&mut self, label: String, level: usize) -> Result<String> {
+        let value_type = self.stream.next()?;
+        let value = self.ser_token(value_type, level)?;
+        Ok(format!("{} = {}", write_label(&label), value))
+    }
+
+    pub fn ser_union(&mut self, level: usize) -> Result<String> {
+        let token = self.stream.next()?;
+        let union_type = match token {
+            TDFToken::UnionStart(union_type) => union_type,
+            _ => bail!("Expected Union start, found {:?}", token),
+        };
+        if union_type == UnionType::Unset {
+            self.expect(TDFToken::UnionEnd)?;
+            return Ok("union(unset)".to_string());
+        }
+        let token = self.stream.next()?;
+        let label = match token {
+            TDFToken::Label(label) => label,
+            _ => bail!("Expected Label in Union, found {:?}", token),
+        };
+        let member = self.ser_member(label, level)?;
+        self.expect(TDFToken::UnionEnd)?;
+        Ok(format!("union({}) {}", union_type as u8, member))
+    }
+
+    pub fn ser_generic(&mut self, level: usize) -> Result<String> {
+        let token = self.stream.next()?;
+        let exists = match token {
+            TDFToken::GenericStart(exists) => exists,
+            _ => bail!("Expected Generic start, found {:?}", token),
+        };
+        if !exists {
+            self.expect(TDFToken::GenericEnd)?;
+            return Ok("generic(invalid)".to_string());
+        }
+        let tdf_id = self.read_int()?;
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::GenericEnd => Ok(format!("generic({})", tdf_id)),
+            TDFToken::Label(label) => {
+                let member = self.ser_member(label, level)?;
+                self.expect(TDFToken::GenericEnd)?;
+                Ok(format!("generic({}) {}", tdf_id, member))
+            },
+            _ => bail!("Expected Label in Generic, found {:?}", token),
+        }
+    }
+
+    pub fn ser_int_list(&mut self) -> Result<String> {
+        let token = self.stream.next()?;
+        let size = match token {
+            TDFToken::IntListStart(size) => size,
+            _ => bail!("Expected Int List start, found {:?}", token),
+        };
+        let mut items = Vec::with_capacity(size);
+        for _ in 0..size {
+            items.push(self.read_int()?.to_string());
+        }
+        self.expect(TDFToken::IntListEnd)?;
+        Ok(format!("int_list({})", items.join(", ")))
+    }
+}
+
+/// Label without trailing spaces, inner spaces are written as _
+pub fn write_label(label: &str) -> String {
+    label.trim_end().replace(' ', "_")
+}
+
+/// Quoted string, bytes that are not printable or not UTF-8 are escaped
+pub fn write_string(string: &[u8]) -> String {
+    let mut output = String::from("\"");
+    let mut rest = string;
+    while !rest.is_empty() {
+        // Ends of valid UTF-8 prefix and of invalid bytes after it
+        let (valid, invalid) = match std::str::from_utf8(rest) {
+            Ok(_) => (rest.len(), rest.len()),
+            Err(e) => (e.valid_up_to(), e.valid_up_to() + e.error_len().unwrap_or(rest.len() - e.valid_up_to())),
+        };
+        for c in std::str::from_utf8(&rest[..valid]).unwrap_or_default().chars() {
+            match c {
+                '"' => output.push_str("\\\""),
+                '\\' => output.push_str("\\\\"),
+                '\n' => output.push_str("\\n"),
+                '\r' => output.push_str("\\r"),
+                '\t' => output.push_str("\\t"),
+                c if (c as u32) < 0x20 || c as u32 == 0x7F => output.push_str(&format!("\\x{:02x}", c as u32)),
+                c => output.push(c),
+            }
+        }
+        for b in &rest[valid..invalid] {
+            output.push_str(&format!("\\x{:02x}", b));
+        }
+        rest = &rest[invalid..];
+    }
+    output.push('"');
+    output
+}
+
+/// Put comment at the end of the first line of value
+fn with_comment(value: &str, comment: &str) -> String {
+    if comment.is_empty() {
+        return value.to_string();
+    }
+    match value.split_once('\n') {
+        Some((first, rest)) => format!("{}  # {}\n{}", first, comment, rest),
+        None => format!("{}  # {}", value, comment),
+    }
+}
+
+impl TDFSerializer<String> for TextSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut String) -> Result<()> {
+        let mut ser = Self::new(stream);
+        ser.write_text(writer)
+    }
+}
@@ -11,6 +11,7 @@
 
 use anyhow::{Result, bail};
 use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
 
 #[derive(Debug, Clone)]
 pub struct TDFTokenStream(pub Vec<TDFToken>, pub usize);
@@ -98,6 +99,8 @@ pub enum TDFToken {
     FloatType,
     /// Float number
     Float(f32),
+    /// Indicates Time type, value is Int
+    TimeType,
     /// Indicates Generic type
     GenericType,
     /// Indicates if Generic exists
@@ -124,6 +127,23 @@ pub enum UnionType {
     Unset          = 0x7F,
 }
 
+impl UnionType {
+    /// Union type from its wire tag, unknown tags are Unset
+    pub fn from_tag(tag: u8) -> Self {
+        FromPrimitive::from_u8(tag).unwrap_or(UnionType::Unset)
+    }
+}
+
+/// Label as it is written on the wire:
+/// uppercase, 4 chars long, with spaces in place of underscores
+pub fn normalize_label(label: &str) -> String {
+    let mut label_string: String = label.to_uppercase().replace("_", " ").chars().take(4).collect();
+    while label_string.chars().count() < 4 {
+        label_string.push(' ');
+    }
+    label_string
+}
+
 /// Serializer writes into stream or data given TDFToken
 pub trait TDFSerializer<W> {
     fn serialize(stream: TDFTokenStream, writer: &mut W) -> Result<()>;
@@ -150,7 +170,7 @@ impl TDFToken {
             Self::ObjectTypeType => 8,
             Self::ObjectIdType   => 9,
             Self::FloatType      => 10,
-            // time here also
+            Self::TimeType       => 11,
             Self::GenericType    => 12,
             _ => bail!("Attempt to get tag of non-type token!")
         })
@@ -170,6 +190,7 @@ impl TDFToken {
             8 => Self::ObjectTypeType,
             9 => Self::ObjectIdType,
             10 => Self::FloatType,
+            11 => Self::TimeType,
             12 => Self::GenericType,
             _ => bail!("Tag {} doesn't match any known type!", tag)
         })
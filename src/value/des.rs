@@ -0,0 +1,141 @@
+use crate::token::*;
+use anyhow::{Result, bail};
+
+use super::{TdfGeneric, TdfMap, TdfValue};
+
+
+/// Produces token stream from TdfValue tree
+pub struct ValueDeserializer {
+    pub stream: TDFTokenStream,
+}
+
+impl ValueDeserializer {
+
+    pub fn new() -> Self {
+        Self {
+            stream: TDFTokenStream::new()
+        }
+    }
+
+    /// Push type token and value tokens
+    pub fn des_typed(&mut self, value: &TdfValue) -> Result<()> {
+        self.stream.push(value.type_token());
+        self.des_value(value)
+    }
+
+    /// Push value tokens, checking that elements match declared types
+    pub fn des_value(&mut self, value: &TdfValue) -> Result<()> {
+        match value {
+            TdfValue::Int(v) | TdfValue::Time(v) => self.stream.push(TDFToken::Int(*v)),
+            TdfValue::String(v) => self.stream.push(TDFToken::String(v.clone())),
+            TdfValue::Blob(v) => self.stream.push(TDFToken::Blob(v.clone())),
+            TdfValue::Map(map) => self.des_map(map)?,
+            TdfValue::List(item_type, items) => {
+                self.stream.push(TDFToken::ListStart(items.len()));
+                self.stream.push(item_type.clone());
+                for item in items {
+                    self.des_of_type(item_type, item)?;
+                }
+                self.stream.push(TDFToken::ListEnd);
+            },
+            TdfValue::PairList(key_type, value_type, pairs) => {
+                self.stream.push(TDFToken::PairListStart(pairs.len()));
+                self.stream.push(key_type.clone());
+                self.stream.push(value_type.clone());
+                for (k, v) in pairs {
+                    self.des_of_type(key_type, k)?;
+                    self.des_of_type(value_type, v)?;
+                }
+                self.stream.push(TDFToken::PairListEnd);
+            },
+            TdfValue::Union(union_type, member) => {
+                self.stream.push(TDFToken::UnionStart(*union_type));
+                match (union_type, member) {
+                    (UnionType::Unset, Some(_)) => bail!("Unset Union can't have a member!"),
+                    (_, Some((label, value))) => {
+                        self.stream.push(TDFToken::Label(label.clone()));
+                        self.des_typed(value)?;
+                    },
+                    (UnionType::Unset, None) => {},
+                    (_, None) => bail!("Union {:?} must have a member!", union_type),
+                }
+                self.stream.push(TDFToken::UnionEnd);
+            },
+            TdfValue::Generic(generic) => {
+                match generic {
+                    TdfGeneric::Valid(tdf_id, member) => {
+                        self.stream.push(TDFToken::GenericStart(true));
+                        self.stream.push(TDFToken::Int(*tdf_id));
+                        if let Some((label, value)) = member {
+                            self.stream.push(TDFToken::Label(label.clone()));
+                            self.des_typed(value)?;
+                        }
+                    },
+                    TdfGeneric::Invalid => self.stream.push(TDFToken::GenericStart(false)),
+                }
+                self.stream.push(TDFToken::GenericEnd);
+            },
+            TdfValue::IntList(items) => {
+                self.stream.push(TDFToken::IntListStart(items.len()));
+                for item in items {
+                    self.stream.push(TDFToken::Int(*item));
+                }
+                self.stream.push(TDFToken::IntListEnd);
+            },
+            TdfValue::ObjectType(object_type) => {
+                self.stream.push(TDFToken::Int(object_type.0));
+                self.stream.push(TDFToken::Int(object_type.1));
+            },
+            TdfValue::ObjectId(object_id) => {
+                self.stream.push(TDFToken::Int(object_id.0));
+                self.stream.push(TDFToken::Int(object_id.1));
+                self.stream.push(TDFToken::Int(object_id.2));
+            },
+            TdfValue::Float(v) => self.stream.push(TDFToken::Float(*v)),
+        }
+        Ok(())
+    }
+
+    pub fn des_map(&mut self, map: &TdfMap) -> Result<()> {
+        self.stream.push(TDFToken::MapStart);
+        if map.union {
+            self.stream.push(TDFToken::MapUnion);
+        }
+        for (label, value) in &map.entries {
+            self.stream.push(TDFToken::Label(label.clone()));
+            self.des_typed(value)?;
+        }
+        self.stream.push(TDFToken::MapEnd);
+        Ok(())
+    }
+
+    fn des_of_type(&mut self, expected_type: &TDFToken, value: &TdfValue) -> Result<()> {
+        if value.type_token() != *expected_type {
+            bail!("Expected element of {:?}, found {:?}", expected_type, value.type_token());
+        }
+        self.des_value(value)
+    }
+}
+
+impl TdfValue {
+    /// Token stream of this value, starting with its type token
+    pub fn to_stream(&self) -> Result<TDFTokenStream> {
+        let mut des = ValueDeserializer::new();
+        des.des_typed(self)?;
+        Ok(des.stream)
+    }
+}
+
+impl Default for ValueDeserializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TDFDeserializer<TdfValue> for ValueDeserializer {
+    fn deserialize(reader: &mut TdfValue) -> Result<TDFTokenStream> {
+        let mut des = Self::new();
+        des.des_typed(reader)?;
+        Ok(des.stream)
+    }
+}
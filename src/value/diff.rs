@@ -0,0 +1,247 @@
+/*
+    Structural diff of two TDF values
+
+    Differences are reported by path in query notation,
+    map fields are matched by label, so their order doesn't matter.
+    Pair lists are compared in order, or by key if unordered comparison is set.
+*/
+
+use crate::token::{TDFToken, TDFTokenStream, TDFSerializer, normalize_label};
+use anyhow::Result;
+use std::fmt;
+
+use super::{TdfGeneric, TdfValue, ValueSerializer, join, label_step, key_step};
+
+
+/// Kind of single difference
+#[derive(Debug, PartialEq, Clone)]
+pub enum DiffKind {
+    /// Value exists only in the new message
+    Added(TdfValue),
+    /// Value exists only in the old message
+    Removed(TdfValue),
+    /// Old and new value of the same type
+    Changed(TdfValue, TdfValue),
+    /// Old and new type, or element types of lists
+    TypeChanged(TDFToken, TDFToken),
+    /// Old and new length of list or pair list
+    LengthChanged(usize, usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiffEntry {
+    pub path: String,
+    pub kind: DiffKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Match pair list entries by key instead of position
+    pub unordered_pair_lists: bool,
+}
+
+/// All differences between two values
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TdfDiff(pub Vec<DiffEntry>);
+
+impl TdfDiff {
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn push(&mut self, path: &str, kind: DiffKind) {
+        let path = if path.is_empty() { ".".to_string() } else { path.to_string() };
+        self.0.push(DiffEntry { path, kind });
+    }
+}
+
+/// Human readable report, one difference per line
+impl fmt::Display for TdfDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.0 {
+            match &entry.kind {
+                DiffKind::Added(value) => writeln!(f, "+ {} = {}", entry.path, value)?,
+                DiffKind::Removed(value) => writeln!(f, "- {} = {}", entry.path, value)?,
+                DiffKind::Changed(old, new) => writeln!(f, "~ {}: {} -> {}", entry.path, old, new)?,
+                DiffKind::TypeChanged(old, new) => writeln!(f, "! {}: type {:?} -> {:?}", entry.path, old, new)?,
+                DiffKind::LengthChanged(old, new) => writeln!(f, "# {}: length {} -> {}", entry.path, old, new)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Step of any pair list key, keys not addressable by query are still readable
+fn pair_step(key: &TdfValue) -> String {
+    key_step(key).unwrap_or_else(|| format!("[{}]", key))
+}
+
+/// Compare old and new value
+pub fn diff_values(old: &TdfValue, new: &TdfValue, options: &DiffOptions) -> TdfDiff {
+    let mut diff = TdfDiff::default();
+    diff_at(old, new, "", options, &mut diff);
+    diff
+}
+
+/// Compare old and new token streams
+pub fn diff_streams(old: TDFTokenStream, new: TDFTokenStream, options: &DiffOptions) -> Result<TdfDiff> {
+    let mut old_value = TdfValue::map();
+    ValueSerializer::serialize(old, &mut old_value)?;
+    let mut new_value = TdfValue::map();
+    ValueSerializer::serialize(new, &mut new_value)?;
+    Ok(diff_values(&old_value, &new_value, options))
+}
+
+fn diff_at(old: &TdfValue, new: &TdfValue, path: &str, options: &DiffOptions, diff: &mut TdfDiff) {
+
+    if old.type_token() != new.type_token() {
+        diff.push(path, DiffKind::TypeChanged(old.type_token(), new.type_token()));
+        return;
+    }
+
+    match (old, new) {
+        (TdfValue::Map(old_map), TdfValue::Map(new_map)) => {
+            for (label, old_value) in &old_map.entries {
+                let field_path = join(path, &label_step(label));
+                match new_map.get(label) {
+                    Some(new_value) => diff_at(old_value, new_value, &field_path, options, diff),
+                    None => diff.push(&field_path, DiffKind::Removed(old_value.clone())),
+                }
+            }
+            for (label, new_value) in &new_map.entries {
+                if old_map.get(label).is_none() {
+                    diff.push(&join(path, &label_step(label)), DiffKind::Added(new_value.clone()));
+                }
+            }
+        },
+        (TdfValue::List(old_type, old_items), TdfValue::List(new_type, new_items)) => {
+            if old_type != new_type {
+                diff.push(&join(path, "[*]"), DiffKind::TypeChanged(old_type.clone(), new_type.clone()));
+                return;
+            }
+            diff_sequence(old_items, new_items, path, options, diff);
+        },
+        (TdfValue::IntList(old_items), TdfValue::IntList(new_items)) => {
+            let old_items: Vec<TdfValue> = old_items.iter().map(|v| TdfValue::Int(*v)).collect();
+            let new_items: Vec<TdfValue> = new_items.iter().map(|v| TdfValue::Int(*v)).collect();
+            diff_sequence(&old_items, &new_items, path, options, diff);
+        },
+        (TdfValue::PairList(old_key, old_value, old_pairs), TdfValue::PairList(new_key, new_value, new_pairs)) => {
+            if old_key != new_key || old_value != new_value {
+                if old_key != new_key {
+                    diff.push(path, DiffKind::TypeChanged(old_key.clone(), new_key.clone()));
+                }
+                if old_value != new_value {
+                    diff.push(&join(path, "[*]"), DiffKind::TypeChanged(old_value.clone(), new_value.clone()));
+                }
+                return;
+            }
+            if old_pairs.len() != new_pairs.len() {
+                diff.push(path, DiffKind::LengthChanged(old_pairs.len(), new_pairs.len()));
+            }
+            if options.unordered_pair_lists {
+                diff_pairs_by_key(old_pairs, new_pairs, path, options, diff);
+            } else {
+                diff_pairs_in_order(old_pairs, new_pairs, path, options, diff);
+            }
+        },
+        (TdfValue::Union(old_type, old_member), TdfValue::Union(new_type, new_member)) => {
+            if old_type != new_type {
+                diff.push(path, DiffKind::Changed(old.clone(), new.clone()));
+                return;
+            }
+            diff_member(old_member, new_member, path, options, diff);
+        },
+        (TdfValue::Generic(TdfGeneric::Valid(old_id, old_member)), TdfValue::Generic(TdfGeneric::Valid(new_id, new_member))) => {
+            if old_id != new_id {
+                diff.push(path, DiffKind::Changed(old.clone(), new.clone()));
+                return;
+            }
+            diff_member(old_member, new_member, path, options, diff);
+        },
+        _ => {
+            if old != new {
+                diff.push(path, DiffKind::Changed(old.clone(), new.clone()));
+            }
+        }
+    }
+}
+
+fn diff_sequence(old_items: &[TdfValue], new_items: &[TdfValue], path: &str, options: &DiffOptions, diff: &mut TdfDiff) {
+    if old_items.len() != new_items.len() {
+        diff.push(path, DiffKind::LengthChanged(old_items.len(), new_items.len()));
+    }
+    for (i, old_item) in old_items.iter().enumerate() {
+        let item_path = join(path, &format!("[{}]", i));
+        match new_items.get(i) {
+            Some(new_item) => diff_at(old_item, new_item, &item_path, options, diff),
+            None => diff.push(&item_path, DiffKind::Removed(old_item.clone())),
+        }
+    }
+    for (i, new_item) in new_items.iter().enumerate().skip(old_items.len()) {
+        diff.push(&join(path, &format!("[{}]", i)), DiffKind::Added(new_item.clone()));
+    }
+}
+
+fn diff_pairs_in_order(old_pairs: &[(TdfValue, TdfValue)], new_pairs: &[(TdfValue, TdfValue)], path: &str, options: &DiffOptions, diff: &mut TdfDiff) {
+    for i in 0..old_pairs.len().max(new_pairs.len()) {
+        match (old_pairs.get(i), new_pairs.get(i)) {
+            (Some((old_key, old_value)), Some((new_key, new_value))) if old_key == new_key => {
+                diff_at(old_value, new_value, &join(path, &pair_step(old_key)), options, diff);
+            },
+            (old_pair, new_pair) => {
+                if let Some((old_key, old_value)) = old_pair {
+                    diff.push(&join(path, &pair_step(old_key)), DiffKind::Removed(old_value.clone()));
+                }
+                if let Some((new_key, new_value)) = new_pair {
+                    diff.push(&join(path, &pair_step(new_key)), DiffKind::Added(new_value.clone()));
+                }
+            }
+        }
+    }
+}
+
+fn diff_pairs_by_key(old_pairs: &[(TdfValue, TdfValue)], new_pairs: &[(TdfValue, TdfValue)], path: &str, options: &DiffOptions, diff: &mut TdfDiff) {
+    for (old_key, old_value) in old_pairs {
+        let pair_path = join(path, &pair_step(old_key));
+        match new_pairs.iter().find(|(k, _)| k == old_key) {
+            Some((_, new_value)) => diff_at(old_value, new_value, &pair_path, options, diff),
+            None => diff.push(&pair_path, DiffKind::Removed(old_value.clone())),
+        }
+    }
+    for (new_key, new_value) in new_pairs {
+        if !old_pairs.iter().any(|(k, _)| k == new_key) {
+            diff.push(&join(path, &pair_step(new_key)), DiffKind::Added(new_value.clone()));
+        }
+    }
+}
+
+type Member = Option<(String, Box<TdfValue>)>;
+
+fn diff_member(old_member: &Member, new_member: &Member, path: &str, options: &DiffOptions, diff: &mut TdfDiff) {
+    match (old_member, new_member) {
+        (Some((old_label, old_value)), Some((new_label, new_value))) if normalize_label(old_label) == normalize_label(new_label) => {
+            diff_at(old_value, new_value, &join(path, &label_step(old_label)), options, diff);
+        },
+        (old_member, new_member) => {
+            if let Some((label, value)) = old_member {
+                diff.push(&join(path, &label_step(label)), DiffKind::Removed(value.as_ref().clone()));
+            }
+            if let Some((label, value)) = new_member {
+                diff.push(&join(path, &label_step(label)), DiffKind::Added(value.as_ref().clone()));
+            }
+        }
+    }
+}
+
+impl TdfValue {
+    /// Differences from this value to the new one
+    pub fn diff(&self, new: &TdfValue, options: &DiffOptions) -> TdfDiff {
+        diff_values(self, new, options)
+    }
+}
@@ -0,0 +1,148 @@
+/*
+    tdf! literal macro
+
+    Builds TdfValue inline, in json-like syntax:
+
+    tdf!({
+        "GID": 5,
+        "NAME": "x",
+        "PLST": [ { "PNAM": "player" } ],
+        "ADDR": union(2, "VALU", { "IP": 34 }),
+    })
+
+    Wire types without json form are written as calls:
+    blob(0x1, 0x2), object_type(4, 1), object_id(4, 1, 5), float(1.5), time(10),
+    int_list(1, 2, 3), pair_list(1 => "one", 2 => "two"), list(StringType),
+    union(), union(2, "VALU", {..}), generic(), generic(5), generic(5, "DATA", ..)
+    Any other expression is converted with TdfValue::from, put it in parentheses.
+    Lists and pair lists take element types from their first element.
+*/
+
+
+#[macro_export]
+macro_rules! tdf {
+
+    // Map body
+    (@map $map:ident ()) => {};
+    (@map $map:ident ($label:literal : $tag:ident ( $($args:tt)* ) $(, $($rest:tt)*)?)) => {
+        $map.insert($label, $crate::tdf!($tag ( $($args)* )));
+        $crate::tdf!(@map $map ($($($rest)*)?));
+    };
+    (@map $map:ident ($label:literal : - $value:literal $(, $($rest:tt)*)?)) => {
+        $map.insert($label, $crate::tdf!(- $value));
+        $crate::tdf!(@map $map ($($($rest)*)?));
+    };
+    (@map $map:ident ($label:literal : $value:tt $(, $($rest:tt)*)?)) => {
+        $map.insert($label, $crate::tdf!($value));
+        $crate::tdf!(@map $map ($($($rest)*)?));
+    };
+
+    // List body
+    (@list $items:ident ()) => {};
+    (@list $items:ident ($tag:ident ( $($args:tt)* ) $(, $($rest:tt)*)?)) => {
+        $items.push($crate::tdf!($tag ( $($args)* )));
+        $crate::tdf!(@list $items ($($($rest)*)?));
+    };
+    (@list $items:ident (- $value:literal $(, $($rest:tt)*)?)) => {
+        $items.push($crate::tdf!(- $value));
+        $crate::tdf!(@list $items ($($($rest)*)?));
+    };
+    (@list $items:ident ($value:tt $(, $($rest:tt)*)?)) => {
+        $items.push($crate::tdf!($value));
+        $crate::tdf!(@list $items ($($($rest)*)?));
+    };
+
+    // Pair list body, keys and values are single tokens
+    (@pairs $pairs:ident ()) => {};
+    (@pairs $pairs:ident ($key:tt => $tag:ident ( $($args:tt)* ) $(, $($rest:tt)*)?)) => {
+        $pairs.push(($crate::tdf!($key), $crate::tdf!($tag ( $($args)* ))));
+        $crate::tdf!(@pairs $pairs ($($($rest)*)?));
+    };
+    (@pairs $pairs:ident ($key:tt => $value:tt $(, $($rest:tt)*)?)) => {
+        $pairs.push(($crate::tdf!($key), $crate::tdf!($value)));
+        $crate::tdf!(@pairs $pairs ($($($rest)*)?));
+    };
+
+    // Wire types
+    (blob ( $($byte:expr),* $(,)? )) => {
+        $crate::value::TdfValue::Blob(vec![ $($byte as u8),* ])
+    };
+    (object_type ( $component:expr, $type_id:expr )) => {
+        $crate::value::TdfValue::ObjectType($crate::rtdf::ObjectType($component, $type_id))
+    };
+    (object_id ( $component:expr, $type_id:expr, $id:expr )) => {
+        $crate::value::TdfValue::ObjectId($crate::rtdf::ObjectId($component, $type_id, $id))
+    };
+    (float ( $value:expr )) => {
+        $crate::value::TdfValue::Float(($value) as f32)
+    };
+    (time ( $value:expr )) => {
+        $crate::value::TdfValue::Time(($value) as i64)
+    };
+    (int_list ( $($value:expr),* $(,)? )) => {
+        $crate::value::TdfValue::IntList(vec![ $(($value) as i64),* ])
+    };
+    (list ( $item_type:ident )) => {
+        $crate::value::TdfValue::List($crate::token::TDFToken::$item_type, vec![])
+    };
+    (pair_list ( $key_type:ident, $value_type:ident )) => {
+        $crate::value::TdfValue::PairList($crate::token::TDFToken::$key_type, $crate::token::TDFToken::$value_type, vec![])
+    };
+    (pair_list ( $($pairs:tt)* )) => {{
+        #[allow(clippy::vec_init_then_push)]
+        let pairs = {
+            #[allow(unused_mut)]
+            let mut pairs = Vec::new();
+            $crate::tdf!(@pairs pairs ($($pairs)*));
+            pairs
+        };
+        $crate::value::TdfValue::pair_list(pairs)
+    }};
+    (union ()) => {
+        $crate::value::TdfValue::Union($crate::token::UnionType::Unset, None)
+    };
+    (union ( $union_type:expr, $label:literal, $($value:tt)+ )) => {
+        $crate::value::TdfValue::Union(
+            $crate::token::UnionType::from_tag($union_type),
+            Some(($crate::token::normalize_label($label), Box::new($crate::tdf!($($value)+)))),
+        )
+    };
+    (generic ()) => {
+        $crate::value::TdfValue::Generic($crate::value::TdfGeneric::Invalid)
+    };
+    (generic ( $tdf_id:expr )) => {
+        $crate::value::TdfValue::Generic($crate::value::TdfGeneric::Valid($tdf_id, None))
+    };
+    (generic ( $tdf_id:expr, $label:literal, $($value:tt)+ )) => {
+        $crate::value::TdfValue::Generic($crate::value::TdfGeneric::Valid(
+            $tdf_id,
+            Some(($crate::token::normalize_label($label), Box::new($crate::tdf!($($value)+)))),
+        ))
+    };
+
+    // Containers
+    ({ $($body:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut map = $crate::value::TdfMap::new();
+        $crate::tdf!(@map map ($($body)*));
+        $crate::value::TdfValue::Map(map)
+    }};
+    ([ $($body:tt)* ]) => {{
+        #[allow(clippy::vec_init_then_push)]
+        let items = {
+            #[allow(unused_mut)]
+            let mut items = Vec::new();
+            $crate::tdf!(@list items ($($body)*));
+            items
+        };
+        $crate::value::TdfValue::list(items)
+    }};
+
+    // Primitives and expressions
+    (- $value:literal) => {
+        $crate::value::TdfValue::from(- $value)
+    };
+    ($value:expr) => {
+        $crate::value::TdfValue::from($value)
+    };
+}
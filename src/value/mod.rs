@@ -0,0 +1,496 @@
+/*
+    Dynamic TDF value tree
+
+    Owned representation of any TDF message,
+    for working with data without declaring Rust structs
+*/
+
+mod ser;
+pub use ser::*;
+
+mod des;
+pub use des::*;
+
+mod literal;
+
+#[cfg(any(feature = "yaml", feature = "msgpack", feature = "cbor"))]
+mod tagged;
+#[cfg(any(feature = "yaml", feature = "msgpack", feature = "cbor"))]
+pub(crate) use tagged::*;
+
+mod query;
+pub use query::*;
+
+mod diff;
+pub use diff::*;
+
+mod patch;
+pub use patch::*;
+
+use crate::token::{TDFToken, UnionType, normalize_label};
+use crate::rtdf::{GenericTdfId, IntList, Label, ObjectId, ObjectType};
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+
+/// Any TDF value
+#[derive(Debug, PartialEq, Clone)]
+pub enum TdfValue {
+    Int(i64),
+    /// Bytes of string, not always valid utf-8
+    String(Vec<u8>),
+    Blob(Vec<u8>),
+    Map(TdfMap),
+    /// Element type and elements
+    List(TDFToken, Vec<TdfValue>),
+    /// Key type, value type and pairs
+    PairList(TDFToken, TDFToken, Vec<(TdfValue, TdfValue)>),
+    /// Network union with optional labeled member
+    Union(UnionType, Option<(Label, Box<TdfValue>)>),
+    Generic(TdfGeneric),
+    IntList(Vec<i64>),
+    ObjectType(ObjectType),
+    ObjectId(ObjectId),
+    Float(f32),
+    Time(i64),
+}
+
+/// Labeled fields in order of appearance
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TdfMap {
+    /// Map starts with union marker
+    pub union: bool,
+    pub entries: Vec<(Label, TdfValue)>,
+}
+
+/// Content of Generic value
+#[derive(Debug, PartialEq, Clone)]
+pub enum TdfGeneric {
+    Valid(GenericTdfId, Option<(Label, Box<TdfValue>)>),
+    Invalid,
+}
+
+impl TdfMap {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, label: &str) -> Option<usize> {
+        let label = normalize_label(label);
+        self.entries.iter().position(|(l, _)| normalize_label(l) == label)
+    }
+
+    /// Field by label, labels are compared the way they are written on the wire
+    pub fn get(&self, label: &str) -> Option<&TdfValue> {
+        self.position(label).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, label: &str) -> Option<&mut TdfValue> {
+        match self.position(label) {
+            Some(i) => Some(&mut self.entries[i].1),
+            None => None,
+        }
+    }
+
+    /// Replace field with the same label or append new one.
+    /// Label is stored the way it is written on the wire. Returns previous value
+    pub fn insert<S: AsRef<str>>(&mut self, label: S, value: TdfValue) -> Option<TdfValue> {
+        let label = normalize_label(label.as_ref());
+        match self.position(&label) {
+            Some(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.entries.push((label, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, label: &str) -> Option<TdfValue> {
+        self.position(label).map(|i| self.entries.remove(i).1)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &Label> {
+        self.entries.iter().map(|(l, _)| l)
+    }
+}
+
+impl TdfValue {
+
+    /// Empty map
+    pub fn map() -> Self {
+        Self::Map(TdfMap::new())
+    }
+
+    /// List typed by its first element, empty list is Int list
+    pub fn list(items: Vec<TdfValue>) -> Self {
+        let item_type = match items.first() {
+            Some(item) => item.type_token(),
+            None => TDFToken::IntType,
+        };
+        Self::List(item_type, items)
+    }
+
+    /// Pair list typed by its first pair, empty pair list is Int to Int
+    pub fn pair_list(pairs: Vec<(TdfValue, TdfValue)>) -> Self {
+        let (key_type, value_type) = match pairs.first() {
+            Some((k, v)) => (k.type_token(), v.type_token()),
+            None => (TDFToken::IntType, TDFToken::IntType),
+        };
+        Self::PairList(key_type, value_type, pairs)
+    }
+
+    /// Type token of this value
+    pub fn type_token(&self) -> TDFToken {
+        match self {
+            Self::Int(_)        => TDFToken::IntType,
+            Self::String(_)     => TDFToken::StringType,
+            Self::Blob(_)       => TDFToken::BlobType,
+            Self::Map(_)        => TDFToken::MapType,
+            Self::List(..)      => TDFToken::ListType,
+            Self::PairList(..)  => TDFToken::PairListType,
+            Self::Union(..)     => TDFToken::UnionType,
+            Self::Generic(_)    => TDFToken::GenericType,
+            Self::IntList(_)    => TDFToken::IntListType,
+            Self::ObjectType(_) => TDFToken::ObjectTypeType,
+            Self::ObjectId(_)   => TDFToken::ObjectIdType,
+            Self::Float(_)      => TDFToken::FloatType,
+            Self::Time(_)       => TDFToken::TimeType,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(v) | Self::Time(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Self::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// String value, if it is valid utf-8
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(v) => std::str::from_utf8(v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Raw bytes of String or Blob
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::String(v) | Self::Blob(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&TdfMap> {
+        match self {
+            Self::Map(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    pub fn as_map_mut(&mut self) -> Option<&mut TdfMap> {
+        match self {
+            Self::Map(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<TdfValue>> {
+        match self {
+            Self::List(_, items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<TdfValue>> {
+        match self {
+            Self::List(_, items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_pair_list(&self) -> Option<&Vec<(TdfValue, TdfValue)>> {
+        match self {
+            Self::PairList(_, _, pairs) => Some(pairs),
+            _ => None,
+        }
+    }
+
+    /// Field of map, or labeled member of union or generic
+    pub fn get(&self, label: &str) -> Option<&TdfValue> {
+        match self {
+            Self::Map(m) => m.get(label),
+            Self::Union(_, Some((l, value))) | Self::Generic(TdfGeneric::Valid(_, Some((l, value)))) => {
+                if normalize_label(l) == normalize_label(label) {
+                    Some(value)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, label: &str) -> Option<&mut TdfValue> {
+        match self {
+            Self::Map(m) => m.get_mut(label),
+            Self::Union(_, Some((l, value))) | Self::Generic(TdfGeneric::Valid(_, Some((l, value)))) => {
+                if normalize_label(l) == normalize_label(label) {
+                    Some(value)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Insert field into map, returns previous value.
+    /// Panics if value is not a map
+    pub fn insert<S: AsRef<str>>(&mut self, label: S, value: TdfValue) -> Option<TdfValue> {
+        match self {
+            Self::Map(m) => m.insert(label, value),
+            _ => panic!("Attempt to insert field into {:?}!", self.type_token()),
+        }
+    }
+
+    /// Remove field from map
+    pub fn remove(&mut self, label: &str) -> Option<TdfValue> {
+        match self {
+            Self::Map(m) => m.remove(label),
+            _ => None,
+        }
+    }
+}
+
+/// Path with step appended, steps at root drop their leading dot
+pub(crate) fn join(path: &str, step: &str) -> String {
+    match step.strip_prefix('.') {
+        Some(step) if path.is_empty() => step.to_string(),
+        _ => format!("{}{}", path, step),
+    }
+}
+
+/// Path step of map label or union member
+pub(crate) fn label_step(label: &str) -> String {
+    format!(".{}", label.trim_end())
+}
+
+/// Path step of pair list key, only Int and String keys are addressable
+pub(crate) fn key_step(key: &TdfValue) -> Option<String> {
+    match key {
+        TdfValue::Int(k) => Some(format!("[{}]", k)),
+        TdfValue::String(k) => Some(format!("[{:?}]", String::from_utf8_lossy(k))),
+        _ => None,
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Single line in tdf! literal notation
+impl fmt::Display for TdfValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(v) => write!(f, "{}", v),
+            Self::String(v) => write!(f, "{:?}", String::from_utf8_lossy(v)),
+            Self::Blob(v) => {
+                write!(f, "blob(")?;
+                let bytes: Vec<String> = v.iter().map(|b| format!("{:#04x}", b)).collect();
+                write_list(f, &bytes)?;
+                write!(f, ")")
+            },
+            Self::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (label, value)) in map.entries.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: {}", label.trim_end(), value)?;
+                }
+                write!(f, "}}")
+            },
+            Self::List(item_type, items) => {
+                if items.is_empty() {
+                    return write!(f, "list({:?})", item_type);
+                }
+                write!(f, "[")?;
+                write_list(f, items)?;
+                write!(f, "]")
+            },
+            Self::PairList(key_type, value_type, pairs) => {
+                if pairs.is_empty() {
+                    return write!(f, "pair_list({:?}, {:?})", key_type, value_type);
+                }
+                let pairs: Vec<String> = pairs.iter().map(|(k, v)| format!("{} => {}", k, v)).collect();
+                write!(f, "pair_list(")?;
+                write_list(f, &pairs)?;
+                write!(f, ")")
+            },
+            Self::Union(union_type, member) => match member {
+                Some((label, value)) => write!(f, "union({}, {:?}, {})", *union_type as u8, label.trim_end(), value),
+                None => write!(f, "union()"),
+            },
+            Self::Generic(generic) => match generic {
+                TdfGeneric::Valid(tdf_id, Some((label, value))) => write!(f, "generic({}, {:?}, {})", tdf_id, label.trim_end(), value),
+                TdfGeneric::Valid(tdf_id, None) => write!(f, "generic({})", tdf_id),
+                TdfGeneric::Invalid => write!(f, "generic()"),
+            },
+            Self::IntList(items) => {
+                write!(f, "int_list(")?;
+                write_list(f, items)?;
+                write!(f, ")")
+            },
+            Self::ObjectType(v) => write!(f, "object_type({}, {})", v.0, v.1),
+            Self::ObjectId(v) => write!(f, "object_id({}, {}, {})", v.0, v.1, v.2),
+            Self::Float(v) => write!(f, "float({:?})", v),
+            Self::Time(v) => write!(f, "time({})", v),
+        }
+    }
+}
+
+impl Index<&str> for TdfValue {
+    type Output = TdfValue;
+    fn index(&self, label: &str) -> &TdfValue {
+        match self.get(label) {
+            Some(value) => value,
+            None => panic!("No label {:?} in TDF value!", label),
+        }
+    }
+}
+
+impl IndexMut<&str> for TdfValue {
+    fn index_mut(&mut self, label: &str) -> &mut TdfValue {
+        match self.get_mut(label) {
+            Some(value) => value,
+            None => panic!("No label {:?} in TDF value!", label),
+        }
+    }
+}
+
+impl Index<usize> for TdfValue {
+    type Output = TdfValue;
+    fn index(&self, index: usize) -> &TdfValue {
+        match self {
+            Self::List(_, items) => &items[index],
+            _ => panic!("Attempt to index {:?} by number!", self.type_token()),
+        }
+    }
+}
+
+impl IndexMut<usize> for TdfValue {
+    fn index_mut(&mut self, index: usize) -> &mut TdfValue {
+        match self {
+            Self::List(_, items) => &mut items[index],
+            _ => panic!("Attempt to index {:?} by number!", self.type_token()),
+        }
+    }
+}
+
+impl From<i64> for TdfValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<u64> for TdfValue {
+    fn from(v: u64) -> Self {
+        Self::Int(v as i64)
+    }
+}
+
+impl From<i32> for TdfValue {
+    fn from(v: i32) -> Self {
+        Self::Int(v as i64)
+    }
+}
+
+impl From<u32> for TdfValue {
+    fn from(v: u32) -> Self {
+        Self::Int(v as i64)
+    }
+}
+
+impl From<bool> for TdfValue {
+    fn from(v: bool) -> Self {
+        Self::Int(if v { 1 } else { 0 })
+    }
+}
+
+impl From<&str> for TdfValue {
+    fn from(v: &str) -> Self {
+        Self::String(v.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for TdfValue {
+    fn from(v: String) -> Self {
+        Self::String(v.into_bytes())
+    }
+}
+
+/// Bytes are Blob, the same way as in Rust structs
+impl From<Vec<u8>> for TdfValue {
+    fn from(v: Vec<u8>) -> Self {
+        Self::Blob(v)
+    }
+}
+
+impl From<f32> for TdfValue {
+    fn from(v: f32) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<f64> for TdfValue {
+    fn from(v: f64) -> Self {
+        Self::Float(v as f32)
+    }
+}
+
+impl From<IntList> for TdfValue {
+    fn from(v: IntList) -> Self {
+        Self::IntList(v.0)
+    }
+}
+
+impl From<ObjectType> for TdfValue {
+    fn from(v: ObjectType) -> Self {
+        Self::ObjectType(v)
+    }
+}
+
+impl From<ObjectId> for TdfValue {
+    fn from(v: ObjectId) -> Self {
+        Self::ObjectId(v)
+    }
+}
+
+impl From<TdfMap> for TdfValue {
+    fn from(v: TdfMap) -> Self {
+        Self::Map(v)
+    }
+}
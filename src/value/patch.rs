@@ -0,0 +1,202 @@
+/*
+    Patches of TDF values, in the manner of JSON Patch
+
+    Operations are applied in order and address values by concrete paths:
+    GAME.GID, GAME.PROS[2], GAME.ATTR["mode"].
+    Patch is applied as a whole, if any operation fails value stays untouched.
+    TdfPatch::from_json and to_json read and write its JSON form.
+*/
+
+use crate::token::{TDFToken, TDFTokenStream, TDFSerializer};
+use anyhow::{Result, bail};
+
+use super::{PathSegment, TdfPath, TdfValue, ValueSerializer};
+
+
+/// Single patch operation
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatchOp {
+    /// New map field, list element (index up to list length) or pair list entry
+    Add { path: String, value: TdfValue },
+    /// Remove map field, list element or pair list entry
+    Remove { path: String },
+    /// Replace existing value with value of the same type
+    Replace { path: String, value: TdfValue },
+}
+
+/// Ordered list of operations
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TdfPatch(pub Vec<PatchOp>);
+
+impl TdfPatch {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<S: Into<String>, V: Into<TdfValue>>(mut self, path: S, value: V) -> Self {
+        self.0.push(PatchOp::Add { path: path.into(), value: value.into() });
+        self
+    }
+
+    pub fn remove<S: Into<String>>(mut self, path: S) -> Self {
+        self.0.push(PatchOp::Remove { path: path.into() });
+        self
+    }
+
+    pub fn replace<S: Into<String>, V: Into<TdfValue>>(mut self, path: S, value: V) -> Self {
+        self.0.push(PatchOp::Replace { path: path.into(), value: value.into() });
+        self
+    }
+
+    /// Apply all operations to the value
+    pub fn apply(&self, value: &mut TdfValue) -> Result<()> {
+        let mut patched = value.clone();
+        for op in &self.0 {
+            match op {
+                PatchOp::Add { path, value } => add(&mut patched, path, value.clone())?,
+                PatchOp::Remove { path } => remove(&mut patched, path)?,
+                PatchOp::Replace { path, value } => replace(&mut patched, path, value.clone())?,
+            }
+        }
+        *value = patched;
+        Ok(())
+    }
+
+    /// Apply all operations to the token stream, producing new one
+    pub fn apply_stream(&self, stream: TDFTokenStream) -> Result<TDFTokenStream> {
+        let mut value = TdfValue::map();
+        ValueSerializer::serialize(stream, &mut value)?;
+        self.apply(&mut value)?;
+        value.to_stream()
+    }
+}
+
+/// Check that new value can take place of the old one
+fn check_type(path: &str, old: &TdfValue, new: &TdfValue) -> Result<()> {
+    if old.type_token() != new.type_token() {
+        bail!("Type mismatch at {}: expected {:?}, found {:?}", path, old.type_token(), new.type_token());
+    }
+    match (old, new) {
+        (TdfValue::List(old_type, _), TdfValue::List(new_type, _)) if old_type != new_type => {
+            bail!("List type mismatch at {}: expected {:?}, found {:?}", path, old_type, new_type);
+        },
+        (TdfValue::PairList(old_key, old_value, _), TdfValue::PairList(new_key, new_value, _)) if old_key != new_key || old_value != new_value => {
+            bail!("Pair list type mismatch at {}: expected {:?} to {:?}, found {:?} to {:?}", path, old_key, old_value, new_key, new_value);
+        },
+        _ => Ok(()),
+    }
+}
+
+fn check_element(path: &str, expected: &TDFToken, value: &TdfValue) -> Result<()> {
+    if value.type_token() != *expected {
+        bail!("Type mismatch at {}: expected {:?}, found {:?}", path, expected, value.type_token());
+    }
+    Ok(())
+}
+
+/// Parent value and the last step of path
+fn split<'a>(value: &'a mut TdfValue, path: &str) -> Result<(&'a mut TdfValue, PathSegment)> {
+    let parsed = TdfPath::parse(path)?;
+    if !parsed.is_concrete() {
+        bail!("Patch path {} must not contain wildcards", path);
+    }
+    let mut segments = parsed.0;
+    let last = match segments.pop() {
+        Some(last) => last,
+        None => bail!("Patch path must not be empty"),
+    };
+    match TdfPath(segments).get_mut(value) {
+        Some(parent) => Ok((parent, last)),
+        None => bail!("Parent of {} doesn't exist", path),
+    }
+}
+
+fn pair_key(key_type: &TDFToken, segment: &PathSegment, path: &str) -> Result<TdfValue> {
+    let key = match segment {
+        PathSegment::Index(k) => TdfValue::Int(*k),
+        PathSegment::Key(k) => TdfValue::from(k.as_str()),
+        _ => bail!("Expected pair list key at {}", path),
+    };
+    check_element(path, key_type, &key)?;
+    Ok(key)
+}
+
+fn add(root: &mut TdfValue, path: &str, value: TdfValue) -> Result<()> {
+    let (parent, last) = split(root, path)?;
+    match (parent, &last) {
+        (TdfValue::Map(map), PathSegment::Label(label)) => {
+            if map.get(label).is_some() {
+                bail!("Field {} already exists, use replace", path);
+            }
+            map.insert(label, value);
+        },
+        (TdfValue::List(item_type, items), PathSegment::Index(index)) => {
+            check_element(path, item_type, &value)?;
+            if *index < 0 || *index as usize > items.len() {
+                bail!("Index {} is out of list bounds", path);
+            }
+            items.insert(*index as usize, value);
+        },
+        (TdfValue::PairList(key_type, value_type, pairs), segment) => {
+            let key = pair_key(key_type, segment, path)?;
+            check_element(path, value_type, &value)?;
+            if pairs.iter().any(|(k, _)| *k == key) {
+                bail!("Key {} already exists, use replace", path);
+            }
+            pairs.push((key, value));
+        },
+        (parent, _) => bail!("Unable to add {} into {:?}", path, parent.type_token()),
+    }
+    Ok(())
+}
+
+fn remove(root: &mut TdfValue, path: &str) -> Result<()> {
+    let (parent, last) = split(root, path)?;
+    match (parent, &last) {
+        (TdfValue::Map(map), PathSegment::Label(label)) => {
+            if map.remove(label).is_none() {
+                bail!("Field {} doesn't exist", path);
+            }
+        },
+        (TdfValue::List(_, items), PathSegment::Index(index)) => {
+            if *index < 0 || *index as usize >= items.len() {
+                bail!("Index {} is out of list bounds", path);
+            }
+            items.remove(*index as usize);
+        },
+        (TdfValue::PairList(key_type, _, pairs), segment) => {
+            let key = pair_key(key_type, segment, path)?;
+            match pairs.iter().position(|(k, _)| *k == key) {
+                Some(i) => {
+                    pairs.remove(i);
+                },
+                None => bail!("Key {} doesn't exist", path),
+            }
+        },
+        (parent, _) => bail!("Unable to remove {} from {:?}", path, parent.type_token()),
+    }
+    Ok(())
+}
+
+fn replace(root: &mut TdfValue, path: &str, value: TdfValue) -> Result<()> {
+    let parsed = TdfPath::parse(path)?;
+    if !parsed.is_concrete() {
+        bail!("Patch path {} must not contain wildcards", path);
+    }
+    match parsed.get_mut(root) {
+        Some(old) => {
+            check_type(path, old, &value)?;
+            *old = value;
+            Ok(())
+        },
+        None => bail!("Value {} doesn't exist", path),
+    }
+}
+
+impl TdfValue {
+    /// Apply patch to this value
+    pub fn patch(&mut self, patch: &TdfPatch) -> Result<()> {
+        patch.apply(self)
+    }
+}
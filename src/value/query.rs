@@ -0,0 +1,313 @@
+/*
+    Path queries over TDF values
+
+    GAME.PROS[2].PNAM   - labels and list indices
+    MAP[5], MAP["name"] - pair list lookup by Int or String key
+    PLST[*].PNAM        - every element of list or pair list
+    GAME.*              - every field of map
+    **.GID              - GID at any depth
+
+    Labels are compared the way they are written on the wire,
+    so case and trailing spaces don't matter.
+    Union and Generic members are reached by their label, like ADDR.VALU.IP
+*/
+
+use crate::token::{TDFTokenStream, TDFSerializer, normalize_label};
+use anyhow::{Result, bail};
+use std::fmt;
+
+use super::{TdfGeneric, TdfValue, ValueSerializer, join, label_step, key_step};
+
+
+/// Single step of the path
+#[derive(Debug, PartialEq, Clone)]
+pub enum PathSegment {
+    /// Map field or union member
+    Label(String),
+    /// Any field or member, *
+    AnyLabel,
+    /// Zero or more levels of any children, **
+    Descendants,
+    /// List element, or pair list value by Int key
+    Index(i64),
+    /// Pair list value by String key
+    Key(String),
+    /// Any element of list or pair list, [*]
+    AnyIndex,
+}
+
+/// Parsed path query
+#[derive(Debug, PartialEq, Clone)]
+pub struct TdfPath(pub Vec<PathSegment>);
+
+/// Value found by query with its full path
+#[derive(Debug, PartialEq, Clone)]
+pub struct QueryMatch {
+    pub path: String,
+    pub value: TdfValue,
+}
+
+impl TdfPath {
+
+    pub fn parse(query: &str) -> Result<Self> {
+
+        let chars: Vec<char> = query.chars().collect();
+        let mut segments = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '.' => {
+                    i += 1;
+                },
+                '[' => {
+                    let (segment, next) = Self::parse_brackets(&chars, i + 1)?;
+                    segments.push(segment);
+                    i = next;
+                },
+                _ => {
+                    let start = i;
+                    while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                        i += 1;
+                    }
+                    let label: String = chars[start..i].iter().collect();
+                    segments.push(match label.trim() {
+                        "*" => PathSegment::AnyLabel,
+                        "**" => PathSegment::Descendants,
+                        "" => bail!("Empty label in query {:?}", query),
+                        label => PathSegment::Label(label.to_string()),
+                    });
+                }
+            }
+        }
+
+        Ok(Self(segments))
+    }
+
+    fn parse_brackets(chars: &[char], mut i: usize) -> Result<(PathSegment, usize)> {
+
+        if chars.get(i) == Some(&'"') {
+            let mut key = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some('\\') => {
+                        match chars.get(i + 1) {
+                            Some(c) => key.push(*c),
+                            None => bail!("Unterminated key in query"),
+                        }
+                        i += 2;
+                    },
+                    Some('"') => break,
+                    Some(c) => {
+                        key.push(*c);
+                        i += 1;
+                    },
+                    None => bail!("Unterminated key in query"),
+                }
+            }
+            if chars.get(i + 1) != Some(&']') {
+                bail!("Expected ] after key {:?}", key);
+            }
+            return Ok((PathSegment::Key(key), i + 2));
+        }
+
+        let start = i;
+        while i < chars.len() && chars[i] != ']' {
+            i += 1;
+        }
+        if i == chars.len() {
+            bail!("Expected ] in query");
+        }
+
+        let inner: String = chars[start..i].iter().collect();
+        let segment = match inner.trim() {
+            "*" => PathSegment::AnyIndex,
+            number => match number.parse::<i64>() {
+                Ok(index) => PathSegment::Index(index),
+                Err(_) => bail!("Expected index, \"key\" or * in brackets, found {:?}", number),
+            },
+        };
+
+        Ok((segment, i + 1))
+    }
+
+    /// True if path has no wildcards and points to a single place
+    pub fn is_concrete(&self) -> bool {
+        self.0.iter().all(|s| matches!(s, PathSegment::Label(_) | PathSegment::Index(_) | PathSegment::Key(_)))
+    }
+
+    /// Every value matching the path, in order of appearance
+    pub fn evaluate(&self, value: &TdfValue) -> Vec<QueryMatch> {
+        let mut out = Vec::new();
+        Self::evaluate_at(&self.0, value, String::new(), &mut out);
+        out
+    }
+
+    fn evaluate_at(segments: &[PathSegment], value: &TdfValue, path: String, out: &mut Vec<QueryMatch>) {
+
+        let (segment, rest) = match segments.split_first() {
+            Some(s) => s,
+            None => {
+                out.push(QueryMatch { path, value: value.clone() });
+                return;
+            }
+        };
+
+        match segment {
+            PathSegment::Descendants => {
+                Self::evaluate_at(rest, value, path.clone(), out);
+                for (step, child) in children(value) {
+                    Self::evaluate_at(segments, child, join(&path, &step), out);
+                }
+            },
+            PathSegment::AnyLabel => {
+                for (step, child) in children(value) {
+                    if !step.starts_with('[') {
+                        Self::evaluate_at(rest, child, join(&path, &step), out);
+                    }
+                }
+            },
+            PathSegment::AnyIndex => {
+                for (step, child) in children(value) {
+                    if step.starts_with('[') {
+                        Self::evaluate_at(rest, child, join(&path, &step), out);
+                    }
+                }
+            },
+            _ => {
+                if let Some((step, child)) = child(value, segment) {
+                    Self::evaluate_at(rest, child, join(&path, &step), out);
+                }
+            }
+        }
+    }
+
+    /// Value at concrete path
+    pub fn get<'a>(&self, value: &'a TdfValue) -> Option<&'a TdfValue> {
+        let mut current = value;
+        for segment in &self.0 {
+            current = child(current, segment)?.1;
+        }
+        Some(current)
+    }
+
+    /// Mutable value at concrete path
+    pub fn get_mut<'a>(&self, value: &'a mut TdfValue) -> Option<&'a mut TdfValue> {
+        let mut current = value;
+        for segment in &self.0 {
+            current = child_mut(current, segment)?;
+        }
+        Some(current)
+    }
+}
+
+impl fmt::Display for TdfPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            let dot = if i == 0 { "" } else { "." };
+            match segment {
+                PathSegment::Label(label) => write!(f, "{}{}", dot, label.trim_end())?,
+                PathSegment::AnyLabel => write!(f, "{}*", dot)?,
+                PathSegment::Descendants => write!(f, "{}**", dot)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+                PathSegment::Key(key) => write!(f, "[{:?}]", key)?,
+                PathSegment::AnyIndex => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for TdfPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn key_matches(key: &TdfValue, segment: &PathSegment) -> bool {
+    match (key, segment) {
+        (TdfValue::Int(k), PathSegment::Index(i)) => k == i,
+        (TdfValue::String(k), PathSegment::Key(s)) => k == s.as_bytes(),
+        _ => false,
+    }
+}
+
+/// Direct children of value with their path steps
+pub fn children(value: &TdfValue) -> Vec<(String, &TdfValue)> {
+    match value {
+        TdfValue::Map(map) => map.entries.iter()
+            .map(|(label, v)| (label_step(label), v))
+            .collect(),
+        TdfValue::List(_, items) => items.iter()
+            .enumerate()
+            .map(|(i, v)| (format!("[{}]", i), v))
+            .collect(),
+        TdfValue::PairList(_, _, pairs) => pairs.iter()
+            .filter_map(|(k, v)| key_step(k).map(|step| (step, v)))
+            .collect(),
+        TdfValue::Union(_, Some((label, v))) | TdfValue::Generic(TdfGeneric::Valid(_, Some((label, v)))) => {
+            vec![(label_step(label), v.as_ref())]
+        },
+        _ => vec![],
+    }
+}
+
+/// Child addressed by concrete segment
+fn child<'a>(value: &'a TdfValue, segment: &PathSegment) -> Option<(String, &'a TdfValue)> {
+    match (value, segment) {
+        (_, PathSegment::Label(label)) => {
+            value.get(label).map(|v| (label_step(&normalize_label(label)), v))
+        },
+        (TdfValue::List(_, items), PathSegment::Index(index)) => {
+            items.get(*index as usize).map(|v| (format!("[{}]", index), v))
+        },
+        (TdfValue::PairList(_, _, pairs), PathSegment::Index(_)) | (TdfValue::PairList(_, _, pairs), PathSegment::Key(_)) => {
+            pairs.iter()
+                .find(|(k, _)| key_matches(k, segment))
+                .and_then(|(k, v)| key_step(k).map(|step| (step, v)))
+        },
+        _ => None,
+    }
+}
+
+fn child_mut<'a>(value: &'a mut TdfValue, segment: &PathSegment) -> Option<&'a mut TdfValue> {
+    match (value, segment) {
+        (value, PathSegment::Label(label)) => value.get_mut(label),
+        (TdfValue::List(_, items), PathSegment::Index(index)) => items.get_mut(*index as usize),
+        (TdfValue::PairList(_, _, pairs), segment) => {
+            pairs.iter_mut()
+                .find(|(k, _)| key_matches(k, segment))
+                .map(|(_, v)| v)
+        },
+        _ => None,
+    }
+}
+
+impl TdfValue {
+    /// Values matching the path query
+    pub fn query(&self, query: &str) -> Result<Vec<QueryMatch>> {
+        Ok(TdfPath::parse(query)?.evaluate(self))
+    }
+
+    /// Value at concrete path, like GAME.PROS[2].PNAM
+    pub fn pointer(&self, path: &str) -> Option<&TdfValue> {
+        TdfPath::parse(path).ok()?.get(self)
+    }
+
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut TdfValue> {
+        TdfPath::parse(path).ok()?.get_mut(self)
+    }
+}
+
+/// Evaluate query over the token stream.
+/// Whole stream is decoded into TdfValue first, project_bin skips
+/// unselected values of binary but takes only concrete paths
+pub fn query_stream(stream: TDFTokenStream, query: &str) -> Result<Vec<QueryMatch>> {
+    let path = TdfPath::parse(query)?;
+    let mut value = TdfValue::map();
+    ValueSerializer::serialize(stream, &mut value)?;
+    Ok(path.evaluate(&value))
+}
@@ -0,0 +1,246 @@
+use crate::token::*;
+use crate::rtdf::{ObjectId, ObjectType};
+use anyhow::{Result, bail};
+
+use super::{TdfGeneric, TdfMap, TdfValue};
+
+
+/// Builds TdfValue tree from the token stream
+pub struct ValueSerializer {
+    stream: TDFTokenStream,
+}
+
+impl ValueSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream
+        }
+    }
+
+    pub fn ser_token(&mut self, token_type: TDFToken) -> Result<TdfValue> {
+        match token_type {
+            TDFToken::IntType        => Ok(TdfValue::Int(self.ser_int()?)),
+            TDFToken::StringType     => self.ser_string(),
+            TDFToken::BlobType       => self.ser_blob(),
+            TDFToken::MapType        => Ok(TdfValue::Map(self.ser_map()?)),
+            TDFToken::ListType       => self.ser_list(),
+            TDFToken::PairListType   => self.ser_pair_list(),
+            TDFToken::UnionType      => self.ser_union(),
+            TDFToken::IntListType    => self.ser_int_list(),
+            TDFToken::ObjectTypeType => Ok(TdfValue::ObjectType(ObjectType(self.ser_int()?, self.ser_int()?))),
+            TDFToken::ObjectIdType   => Ok(TdfValue::ObjectId(ObjectId(self.ser_int()?, self.ser_int()?, self.ser_int()?))),
+            TDFToken::FloatType      => self.ser_float(),
+            TDFToken::TimeType       => Ok(TdfValue::Time(self.ser_int()?)),
+            TDFToken::GenericType    => self.ser_generic(),
+            _ => bail!("Trying to parse type token, but found {:?}", token_type)
+        }
+    }
+
+    pub fn ser_int(&mut self) -> Result<i64> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::Int(number) => Ok(number),
+            _=> bail!("Expected Integer, found {:?}", token),
+        }
+    }
+
+    pub fn ser_string(&mut self) -> Result<TdfValue> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::String(string) => Ok(TdfValue::String(string)),
+            _=> bail!("Expected String, found {:?}", token),
+        }
+    }
+
+    pub fn ser_blob(&mut self) -> Result<TdfValue> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::Blob(blob) => Ok(TdfValue::Blob(blob)),
+            _=> bail!("Expected Blob, found {:?}", token),
+        }
+    }
+
+    pub fn ser_float(&mut self) -> Result<TdfValue> {
+        let token = self.stream.next()?;
+        match token {
+            TDFToken::Float(number) => Ok(TdfValue::Float(number)),
+            _=> bail!("Expected Float, found {:?}", token),
+        }
+    }
+
+    pub fn ser_map(&mut self) -> Result<TdfMap> {
+
+        let token = self.stream.next()?;
+        if token != TDFToken::MapStart {
+            bail!("Expected Map, found {:?}", token);
+        }
+
+        let mut map = TdfMap::new();
+
+        loop {
+
+            let mut label = self.stream.next()?;
+
+            if label == TDFToken::MapEnd {
+                return Ok(map);
+            } else if label == TDFToken::MapUnion {
+                map.union = true;
+                label = self.stream.next()?;
+                if label == TDFToken::MapEnd {
+                    return Ok(map);
+                }
+            }
+
+            let label_string = match label {
+                TDFToken::Label(label_string) => label_string,
+                _ => bail!("Expected Label in Map, found {:?}", label),
+            };
+
+            let value_type = self.stream.next()?;
+            let value = self.ser_token(value_type)?;
+
+            map.entries.push((label_string, value));
+        }
+
+    }
+
+    pub fn ser_list(&mut self) -> Result<TdfValue> {
+
+        let token = self.stream.next()?;
+        let size = match token {
+            TDFToken::ListStart(s) => s,
+            _ => bail!("Expected List, found {:?}", token),
+        };
+
+        let inner_type = self.stream.next()?;
+
+        let mut items = Vec::with_capacity(size);
+        for _ in 0..size {
+            items.push(self.ser_token(inner_type.clone())?);
+        }
+
+        let end_token = self.stream.next()?;
+        if end_token != TDFToken::ListEnd {
+            bail!("Expected End of list, found {:?}", end_token)
+        }
+
+        Ok(TdfValue::List(inner_type, items))
+    }
+
+    pub fn ser_pair_list(&mut self) -> Result<TdfValue> {
+
+        let token = self.stream.next()?;
+        let size = match token {
+            TDFToken::PairListStart(s) => s,
+            _ => bail!("Expected Pair list, found {:?}", token),
+        };
+
+        let k_type = self.stream.next()?;
+        let v_type = self.stream.next()?;
+
+        let mut pairs = Vec::with_capacity(size);
+        for _ in 0..size {
+            let key = self.ser_token(k_type.clone())?;
+            let value = self.ser_token(v_type.clone())?;
+            pairs.push((key, value));
+        }
+
+        let end_token = self.stream.next()?;
+        if end_token != TDFToken::PairListEnd {
+            bail!("Expected End of Pair list, found {:?}", end_token)
+        }
+
+        Ok(TdfValue::PairList(k_type, v_type, pairs))
+    }
+
+    pub fn ser_int_list(&mut self) -> Result<TdfValue> {
+
+        let token = self.stream.next()?;
+        let size = match token {
+            TDFToken::IntListStart(s) => s,
+            _ => bail!("Expected Int List start, found {:?}", token),
+        };
+
+        let mut items = Vec::with_capacity(size);
+        for _ in 0..size {
+            items.push(self.ser_int()?);
+        }
+
+        let end_token = self.stream.next()?;
+        if end_token != TDFToken::IntListEnd {
+            bail!("Expected End of Int List, found {:?}", end_token)
+        }
+
+        Ok(TdfValue::IntList(items))
+    }
+
+    pub fn ser_union(&mut self) -> Result<TdfValue> {
+
+        let token = self.stream.next()?;
+        let union_type = match token {
+            TDFToken::UnionStart(t) => t,
+            _ => bail!("Expected Union start, found {:?}", token),
+        };
+
+        let member = match self.stream.next()? {
+            TDFToken::UnionEnd => return Ok(TdfValue::Union(union_type, None)),
+            TDFToken::Label(label) => {
+                let value_type = self.stream.next()?;
+                (label, Box::new(self.ser_token(value_type)?))
+            },
+            token => bail!("Expected Label in Union, found {:?}", token),
+        };
+
+        let end_token = self.stream.next()?;
+        if end_token != TDFToken::UnionEnd {
+            bail!("Expected End of Union, found {:?}", end_token)
+        }
+
+        Ok(TdfValue::Union(union_type, Some(member)))
+    }
+
+    pub fn ser_generic(&mut self) -> Result<TdfValue> {
+
+        let token = self.stream.next()?;
+        let exist = match token {
+            TDFToken::GenericStart(t) => t,
+            _ => bail!("Expected Generic start, found {:?}", token),
+        };
+
+        if !exist {
+            let end_token = self.stream.next()?;
+            if end_token != TDFToken::GenericEnd {
+                bail!("Expected End of Generic, found {:?}", end_token)
+            }
+            return Ok(TdfValue::Generic(TdfGeneric::Invalid));
+        }
+
+        let tdf_id = self.ser_int()?;
+
+        let member = match self.stream.next()? {
+            TDFToken::GenericEnd => return Ok(TdfValue::Generic(TdfGeneric::Valid(tdf_id, None))),
+            TDFToken::Label(label) => {
+                let value_type = self.stream.next()?;
+                (label, Box::new(self.ser_token(value_type)?))
+            },
+            token => bail!("Expected Label in Generic, found {:?}", token),
+        };
+
+        let end_token = self.stream.next()?;
+        if end_token != TDFToken::GenericEnd {
+            bail!("Expected End of Generic, found {:?}", end_token)
+        }
+
+        Ok(TdfValue::Generic(TdfGeneric::Valid(tdf_id, Some(member))))
+    }
+}
+
+impl TDFSerializer<TdfValue> for ValueSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut TdfValue) -> Result<()> {
+        let mut ser = Self::new(stream);
+        let token = ser.stream.next()?;
+        *writer = ser.ser_token(token)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use crate::token::{TDFToken, TDFTokenStream, TDFSerializer, UnionType, normalize_label};
+use crate::rtdf::{Label, ObjectId, ObjectType};
+use anyhow::{Result, bail};
+
+use super::{TdfGeneric, TdfMap, TdfValue, ValueSerializer};
+
+
+/// Value of data format with tags, shared by YAML, MessagePack and CBOR.
+/// Types these formats don't have are tagged with TDF type byte
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Tagged {
+    Int(i64),
+    /// Only read, written as Int
+    Bool(bool),
+    Float(f32),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Tagged>),
+    Map(Vec<(Tagged, Tagged)>),
+    Tag(u8, Box<Tagged>),
+}
+
+pub(crate) const TAG_STRING: u8 = 0x1;
+pub(crate) const TAG_MAP: u8 = 0x3;
+pub(crate) const TAG_PAIR_LIST: u8 = 0x5;
+pub(crate) const TAG_UNION: u8 = 0x6;
+pub(crate) const TAG_INT_LIST: u8 = 0x7;
+pub(crate) const TAG_OBJECT_TYPE: u8 = 0x8;
+pub(crate) const TAG_OBJECT_ID: u8 = 0x9;
+pub(crate) const TAG_TIME: u8 = 0xB;
+pub(crate) const TAG_GENERIC: u8 = 0xC;
+
+fn tag(tag: u8, value: Tagged) -> Tagged {
+    Tagged::Tag(tag, Box::new(value))
+}
+
+fn member(label: &str, value: &TdfValue) -> [Tagged; 2] {
+    [Tagged::String(label.trim_end().to_string()), value.to_tagged()]
+}
+
+impl TdfValue {
+    /// Value in formats with tags
+    pub(crate) fn to_tagged(&self) -> Tagged {
+        match self {
+            TdfValue::Int(v) => Tagged::Int(*v),
+            TdfValue::String(v) => match String::from_utf8(v.clone()) {
+                Ok(s) => Tagged::String(s),
+                Err(e) => tag(TAG_STRING, Tagged::Bytes(e.into_bytes())),
+            },
+            TdfValue::Blob(v) => Tagged::Bytes(v.clone()),
+            TdfValue::Map(map) => {
+                let entries = map.entries.iter()
+                    .map(|(label, value)| (Tagged::String(label.trim_end().to_string()), value.to_tagged()))
+                    .collect();
+                if map.union { tag(TAG_MAP, Tagged::Map(entries)) } else { Tagged::Map(entries) }
+            },
+            TdfValue::List(_, items) => Tagged::Array(items.iter().map(|item| item.to_tagged()).collect()),
+            TdfValue::PairList(_, _, pairs) => tag(TAG_PAIR_LIST, Tagged::Map(pairs.iter().map(|(k, v)| (k.to_tagged(), v.to_tagged())).collect())),
+            TdfValue::Union(union_type, value) => {
+                let mut items = vec![Tagged::Int(*union_type as i64)];
+                if let Some((label, value)) = value {
+                    items.extend(member(label, value));
+                }
+                tag(TAG_UNION, Tagged::Array(items))
+            },
+            TdfValue::Generic(TdfGeneric::Valid(id, value)) => {
+                let mut items = vec![Tagged::Int(*id)];
+                if let Some((label, value)) = value {
+                    items.extend(member(label, value));
+                }
+                tag(TAG_GENERIC, Tagged::Array(items))
+            },
+            TdfValue::Generic(TdfGeneric::Invalid) => tag(TAG_GENERIC, Tagged::Array(Vec::new())),
+            TdfValue::IntList(items) => tag(TAG_INT_LIST, Tagged::Array(items.iter().map(|v| Tagged::Int(*v)).collect())),
+            TdfValue::ObjectType(v) => tag(TAG_OBJECT_TYPE, Tagged::Array(vec![Tagged::Int(v.0), Tagged::Int(v.1)])),
+            TdfValue::ObjectId(v) => tag(TAG_OBJECT_ID, Tagged::Array(vec![Tagged::Int(v.0), Tagged::Int(v.1), Tagged::Int(v.2)])),
+            TdfValue::Float(v) => Tagged::Float(*v),
+            TdfValue::Time(v) => tag(TAG_TIME, Tagged::Int(*v)),
+        }
+    }
+
+    /// Value from formats with tags, types of empty lists and pair lists are Int
+    pub(crate) fn from_tagged(tagged: Tagged) -> Result<Self> {
+        Ok(match tagged {
+            Tagged::Int(v) => TdfValue::Int(v),
+            Tagged::Bool(v) => TdfValue::Int(v as i64),
+            Tagged::Float(v) => TdfValue::Float(v),
+            Tagged::String(v) => TdfValue::String(v.into_bytes()),
+            Tagged::Bytes(v) => TdfValue::Blob(v),
+            Tagged::Array(items) => TdfValue::list(items.into_iter().map(Self::from_tagged).collect::<Result<_>>()?),
+            Tagged::Map(entries) => TdfValue::Map(map_from_tagged(entries)?),
+            Tagged::Tag(tag, value) => match (tag, *value) {
+                (TAG_STRING, Tagged::Bytes(v)) => TdfValue::String(v),
+                (TAG_STRING, Tagged::String(v)) => TdfValue::String(v.into_bytes()),
+                (TAG_MAP, Tagged::Map(entries)) => {
+                    let mut map = map_from_tagged(entries)?;
+                    map.union = true;
+                    TdfValue::Map(map)
+                },
+                (TAG_PAIR_LIST, Tagged::Map(pairs)) => TdfValue::pair_list(pairs.into_iter()
+                    .map(|(k, v)| Ok((Self::from_tagged(k)?, Self::from_tagged(v)?)))
+                    .collect::<Result<_>>()?),
+                (TAG_UNION, Tagged::Array(items)) => {
+                    let (union_type, value) = match member_from_tagged(items)? {
+                        (Some(tag), value) if (0..=0x7F).contains(&tag) => (UnionType::from_tag(tag as u8), value),
+                        _ => bail!("Expected union type"),
+                    };
+                    TdfValue::Union(union_type, value)
+                },
+                (TAG_GENERIC, Tagged::Array(items)) => match member_from_tagged(items)? {
+                    (Some(id), value) => TdfValue::Generic(TdfGeneric::Valid(id, value)),
+                    (None, _) => TdfValue::Generic(TdfGeneric::Invalid),
+                },
+                (TAG_INT_LIST, Tagged::Array(items)) => TdfValue::IntList(ints(items)?),
+                (TAG_OBJECT_TYPE, Tagged::Array(items)) => match ints(items)?.as_slice() {
+                    [a, b] => TdfValue::ObjectType(ObjectType(*a, *b)),
+                    _ => bail!("Expected 2 integers of Object type"),
+                },
+                (TAG_OBJECT_ID, Tagged::Array(items)) => match ints(items)?.as_slice() {
+                    [a, b, c] => TdfValue::ObjectId(ObjectId(*a, *b, *c)),
+                    _ => bail!("Expected 3 integers of Object id"),
+                },
+                (TAG_TIME, Tagged::Int(v)) => TdfValue::Time(v),
+                (tag, value) => bail!("Unexpected value {:?} with tag {}", value, tag),
+            },
+        })
+    }
+
+    /// Root map of token stream in formats with tags
+    pub(crate) fn stream_to_tagged(stream: TDFTokenStream) -> Result<Tagged> {
+        let mut value = TdfValue::map();
+        ValueSerializer::serialize(stream, &mut value)?;
+        Ok(value.to_tagged())
+    }
+
+    /// Root map as token stream
+    pub(crate) fn tagged_to_stream(tagged: Tagged) -> Result<TDFTokenStream> {
+        let value = Self::from_tagged(tagged)?;
+        if value.type_token() != TDFToken::MapType {
+            bail!("Expected Map as root, found {:?}", value.type_token());
+        }
+        value.to_stream()
+    }
+}
+
+/// Labeled value of Union or Generic
+type Member = (Label, Box<TdfValue>);
+
+fn map_from_tagged(entries: Vec<(Tagged, Tagged)>) -> Result<TdfMap> {
+    let mut map = TdfMap::new();
+    for (key, value) in entries {
+        let label = label(key)?;
+        if map.get(&label).is_some() {
+            bail!("Duplicate label {}", label.trim_end());
+        }
+        map.entries.push((label, TdfValue::from_tagged(value)?));
+    }
+    Ok(map)
+}
+
+fn label(key: Tagged) -> Result<String> {
+    match key {
+        Tagged::String(key) if key.chars().count() <= 4 => Ok(normalize_label(&key)),
+        key => bail!("Expected label of at most 4 chars, found {:?}", key),
+    }
+}
+
+fn ints(items: Vec<Tagged>) -> Result<Vec<i64>> {
+    items.into_iter().map(|item| match item {
+        Tagged::Int(v) => Ok(v),
+        item => bail!("Expected Integer, found {:?}", item),
+    }).collect()
+}
+
+/// Union type or generic id with optional labeled value
+fn member_from_tagged(items: Vec<Tagged>) -> Result<(Option<i64>, Option<Member>)> {
+    let mut items = items.into_iter();
+    let id = match items.next() {
+        Some(Tagged::Int(id)) => Some(id),
+        Some(item) => bail!("Expected Integer, found {:?}", item),
+        None => None,
+    };
+    let value = match (items.next(), items.next()) {
+        (Some(key), Some(value)) => Some((label(key)?, Box::new(TdfValue::from_tagged(value)?))),
+        (None, None) => None,
+        _ => bail!("Expected label and value"),
+    };
+    if items.next().is_some() {
+        bail!("Unexpected item after value");
+    }
+    Ok((id, value))
+}
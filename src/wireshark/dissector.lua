@@ -0,0 +1,411 @@
+-- TDF dissector, generated by tdf crate
+--
+-- Load it into Wireshark as a plugin, or run it with plain lua
+-- to decode bundled samples.
+
+local PROTO_NAME = {{PROTO_NAME}}
+local PROTO_DESCRIPTION = {{PROTO_DESCRIPTION}}
+local PORTS = { {{PORTS}} }
+local HEADER_SIZE = {{HEADER_SIZE}}
+
+-- Structs by name, fields by 4 char label
+local STRUCTS = {
+{{STRUCTS}}}
+
+-- Structs that may be the body of a message
+local BODIES = { {{BODIES}} }
+
+local SAMPLES = {
+{{SAMPLES}}}
+
+local tdf = {}
+
+tdf.TYPES = {
+    [0] = "Int", [1] = "String", [2] = "Blob", [3] = "Map", [4] = "List",
+    [5] = "PairList", [6] = "Union", [7] = "IntList", [8] = "ObjectType",
+    [9] = "ObjectId", [10] = "Float", [11] = "Time", [12] = "Generic",
+}
+
+tdf.UNIONS = {
+    [0] = "XboxClientAddr", [1] = "XboxServerAddr", [2] = "IpPairAddr",
+    [3] = "IpAddr", [4] = "HostnameAddr", [127] = "Unset",
+}
+
+-- EA encodes value type of pair lists in these fields incorrectly
+tdf.PAIR_LIST_OVERRIDES = { ["GBRA"] = 5, ["MSID"] = 5, ["PELM"] = 4 }
+
+-- Bits of byte without bitwise operators, to run on any lua version
+local function bits(b, shift, count)
+    return math.floor(b / 2 ^ shift) % 2 ^ count
+end
+
+local function reader(data, pos)
+    return { data = data, pos = pos or 1 }
+end
+
+local function offset(r)
+    return r.pos - 1
+end
+
+local function u8(r)
+    local b = r.data:byte(r.pos)
+    if b == nil then
+        error(string.format("unexpected end of data at offset %d", offset(r)), 0)
+    end
+    r.pos = r.pos + 1
+    return b
+end
+
+local function peek(r)
+    return r.data:byte(r.pos)
+end
+
+local function bytes(r, size)
+    if r.pos + size - 1 > #r.data then
+        error(string.format("%d bytes at offset %d are out of data", size, offset(r)), 0)
+    end
+    local s = r.data:sub(r.pos, r.pos + size - 1)
+    r.pos = r.pos + size
+    return s
+end
+
+function tdf.varint(r)
+    local b = u8(r)
+    local negative = bits(b, 6, 1) == 1
+    local value = b % 64
+    local multiplier = 64
+    while b >= 128 do
+        b = u8(r)
+        value = value + (b % 128) * multiplier
+        multiplier = multiplier * 128
+    end
+    if negative then
+        value = -value
+    end
+    return value
+end
+
+local function label_char(m, c)
+    if m == 0 and c == 0 then
+        return " "
+    elseif m == 0 then
+        return string.char(48 + c % 16)
+    end
+    return string.char(64 + c)
+end
+
+function tdf.label(r)
+    local b0, b1, b2 = u8(r), u8(r), u8(r)
+    return label_char(bits(b0, 7, 1), bits(b0, 2, 5))
+        .. label_char(bits(b0, 1, 1), bits(b0, 0, 1) * 16 + bits(b1, 4, 4))
+        .. label_char(bits(b1, 3, 1), bits(b1, 0, 3) * 4 + bits(b2, 6, 2))
+        .. label_char(bits(b2, 5, 1), bits(b2, 0, 5))
+end
+
+local function type_tag(r)
+    local tag = u8(r)
+    if tdf.TYPES[tag] == nil then
+        error(string.format("unknown type tag %d at offset %d", tag, offset(r) - 1), 0)
+    end
+    return tag
+end
+
+local function float(r)
+    local b1, b2, b3, b4 = u8(r), u8(r), u8(r), u8(r)
+    local sign = b1 >= 128 and -1 or 1
+    local exponent = (b1 % 128) * 2 + bits(b2, 7, 1)
+    local mantissa = ((b2 % 128) * 256 + b3) * 256 + b4
+    if exponent == 0 then
+        return sign * mantissa * 2 ^ -149
+    elseif exponent == 255 then
+        return mantissa == 0 and sign * math.huge or 0 / 0
+    end
+    return sign * (1 + mantissa / 2 ^ 23) * 2 ^ (exponent - 127)
+end
+
+local function quote(s)
+    return string.format("%q", s):gsub("\\\n", "\\n")
+end
+
+local function node(tag, start)
+    return { type = tag, offset = start, length = 0, children = {} }
+end
+
+local function close(n, r)
+    n.length = offset(r) - n.offset
+    return n
+end
+
+-- Schema of map field by label
+local function field_of(schema, label)
+    if schema == nil or schema.struct == nil or STRUCTS[schema.struct] == nil then
+        return nil
+    end
+    return STRUCTS[schema.struct].fields[label]
+end
+
+local decode_value
+
+local function decode_map(r, n, schema, is_root)
+    while true do
+        local b = peek(r)
+        if b == nil then
+            if is_root then
+                return
+            end
+            error(string.format("map is not terminated at offset %d", offset(r)), 0)
+        end
+        if b == 0 then
+            u8(r)
+            return
+        end
+        if b <= 2 then
+            u8(r)
+        end
+        local start = offset(r)
+        local label = tdf.label(r)
+        local tag = type_tag(r)
+        local field = field_of(schema, label)
+        local child = decode_value(r, tag, label, field and field.schema, start)
+        child.label = label
+        child.name = field and field.name
+        table.insert(n.children, child)
+    end
+end
+
+local function decode_member(r, n)
+    local start = offset(r)
+    local label = tdf.label(r)
+    local tag = type_tag(r)
+    local child = decode_value(r, tag, label, nil, start)
+    child.label = label
+    table.insert(n.children, child)
+end
+
+-- Decode value of type tag, node starts at given offset, which includes label
+decode_value = function(r, tag, label, schema, start)
+    local n = node(tag, start)
+    local name = tdf.TYPES[tag]
+    if name == "Int" or name == "Time" then
+        n.value = tostring(tdf.varint(r))
+    elseif name == "String" then
+        local size = tdf.varint(r)
+        if size > 0 then
+            n.value = quote(bytes(r, size - 1))
+            u8(r)
+        elseif size < 0 then
+            local s = {}
+            local b = u8(r)
+            while b ~= 0 do
+                table.insert(s, string.char(b))
+                b = u8(r)
+            end
+            n.value = quote(table.concat(s))
+        else
+            n.value = '""'
+        end
+    elseif name == "Blob" then
+        local blob = bytes(r, tdf.varint(r))
+        n.value = string.format("%d bytes: %s", #blob, (blob:gsub(".", function(c) return string.format("%02x", c:byte()) end)))
+    elseif name == "Map" then
+        decode_map(r, n, schema, false)
+    elseif name == "List" then
+        local item = type_tag(r)
+        local size = tdf.varint(r)
+        n.value = string.format("%d x %s", size, tdf.TYPES[item])
+        for i = 0, size - 1 do
+            local child = decode_value(r, item, nil, schema and schema.list, offset(r))
+            child.label = string.format("[%d]", i)
+            table.insert(n.children, child)
+        end
+    elseif name == "PairList" then
+        local key = type_tag(r)
+        local value = type_tag(r)
+        if label ~= nil and tdf.PAIR_LIST_OVERRIDES[label] ~= nil then
+            value = tdf.PAIR_LIST_OVERRIDES[label]
+        end
+        local size = tdf.varint(r)
+        n.value = string.format("%d x %s to %s", size, tdf.TYPES[key], tdf.TYPES[value])
+        for _ = 1, size do
+            local k = decode_value(r, key, nil, schema and schema.key, offset(r))
+            local v = decode_value(r, value, nil, schema and schema.value, offset(r))
+            v.label = "[" .. (k.value or tdf.TYPES[key]) .. "]"
+            table.insert(n.children, v)
+        end
+    elseif name == "Union" then
+        local union = u8(r)
+        n.value = tdf.UNIONS[union] or tostring(union)
+        if union ~= 127 then
+            decode_member(r, n)
+        end
+    elseif name == "IntList" then
+        local size = tdf.varint(r)
+        local values = {}
+        for _ = 1, size do
+            table.insert(values, tostring(tdf.varint(r)))
+        end
+        n.value = "[" .. table.concat(values, ", ") .. "]"
+    elseif name == "ObjectType" then
+        n.value = tdf.varint(r) .. "/" .. tdf.varint(r)
+    elseif name == "ObjectId" then
+        n.value = tdf.varint(r) .. "/" .. tdf.varint(r) .. "/" .. tdf.varint(r)
+    elseif name == "Float" then
+        n.value = tostring(float(r))
+    elseif name == "Generic" then
+        if u8(r) ~= 0 then
+            n.value = "tdf id " .. tdf.varint(r)
+            if peek(r) ~= 0 then
+                decode_member(r, n)
+            end
+            u8(r)
+        else
+            n.value = "invalid"
+        end
+    end
+    return close(n, r)
+end
+
+-- Labels of root map, decoding stops at the first error
+local function root_labels(data)
+    local labels = {}
+    local r = reader(data)
+    pcall(function()
+        while peek(r) ~= nil do
+            if peek(r) <= 2 then
+                u8(r)
+            end
+            local label = tdf.label(r)
+            labels[label] = true
+            decode_value(r, type_tag(r), label, nil, 0)
+        end
+    end)
+    return labels
+end
+
+-- Body struct with the most fields present in the message
+function tdf.guess_body(data)
+    local labels = root_labels(data)
+    local best, best_score = nil, 0
+    for _, name in ipairs(BODIES) do
+        local score = 0
+        for label, _ in pairs(STRUCTS[name].fields) do
+            if labels[label] then
+                score = score + 1
+            end
+        end
+        if score > best_score then
+            best, best_score = name, score
+        end
+    end
+    return best
+end
+
+-- Decode body into the tree of nodes, error is returned along with decoded part
+function tdf.decode(data, body)
+    local root = node(3, 0)
+    root.name = body
+    local r = reader(data)
+    local ok, err = pcall(decode_map, r, root, body and { struct = body }, true)
+    close(root, r)
+    if not ok then
+        table.insert(root.children, {
+            offset = offset(r),
+            length = #data - offset(r),
+            error = err,
+            children = {},
+        })
+    end
+    return root, ok and nil or err
+end
+
+function tdf.describe(n)
+    if n.error ~= nil then
+        return "Undecodable: " .. n.error
+    end
+    local text = n.label or "Body"
+    if n.name ~= nil then
+        text = n.name .. " (" .. text .. ")"
+    end
+    text = text .. ": " .. tdf.TYPES[n.type]
+    if n.value ~= nil then
+        text = text .. " = " .. n.value
+    end
+    return text
+end
+
+function tdf.print(n, indent)
+    indent = indent or ""
+    print(string.format("%06x  %s%s", n.offset, indent, tdf.describe(n)))
+    for _, child in ipairs(n.children) do
+        tdf.print(child, indent .. "  ")
+    end
+end
+
+local function unhex(hex)
+    return (hex:gsub("%x%x", function(h) return string.char(tonumber(h, 16)) end))
+end
+
+if Proto ~= nil then
+
+    local proto = Proto(PROTO_NAME, PROTO_DESCRIPTION)
+    proto.prefs.header_size = Pref.uint("Header size", HEADER_SIZE, "Bytes before TDF body")
+
+    local function add_tree(tree, tvb, base, n)
+        local item
+        if n.length > 0 then
+            item = tree:add(tvb:range(base + n.offset, n.length), tdf.describe(n))
+        else
+            item = tree:add(tdf.describe(n))
+        end
+        if n.error ~= nil then
+            item:add_expert_info(PI_MALFORMED, PI_ERROR, n.error)
+        end
+        for _, child in ipairs(n.children) do
+            add_tree(item, tvb, base, child)
+        end
+    end
+
+    function proto.dissector(tvb, pinfo, tree)
+        local header = proto.prefs.header_size
+        if tvb:len() < header then
+            return 0
+        end
+        pinfo.cols.protocol = PROTO_NAME
+        local subtree = tree:add(proto, tvb(), PROTO_DESCRIPTION)
+        if header > 0 then
+            subtree:add(tvb:range(0, header), "Header")
+        end
+        local data = tvb:raw(header)
+        local root = tdf.decode(data, tdf.guess_body(data))
+        for _, child in ipairs(root.children) do
+            add_tree(subtree, tvb, header, child)
+        end
+        return tvb:len()
+    end
+
+    local tcp_port = DissectorTable.get("tcp.port")
+    for _, port in ipairs(PORTS) do
+        tcp_port:add(port, proto)
+    end
+
+else
+
+    -- Standalone run decodes bundled samples
+    local failed = 0
+    for _, sample in ipairs(SAMPLES) do
+        local data = unhex(sample.hex)
+        local body = sample.body or tdf.guess_body(data)
+        print("== " .. sample.name .. (body and (" as " .. body) or ""))
+        local root, err = tdf.decode(data, body)
+        tdf.print(root)
+        if err ~= nil then
+            failed = failed + 1
+        end
+    end
+    if failed > 0 then
+        os.exit(1)
+    end
+
+end
+
+return tdf
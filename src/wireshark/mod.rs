@@ -0,0 +1,163 @@
+/*
+    Generator of Wireshark Lua dissectors for TDF
+
+    Dissector implements TDF wire rules on its own,
+    struct schemas are used to name fields of known bodies.
+    Outside of Wireshark the script decodes bundled samples,
+    so it can be checked with plain lua interpreter.
+*/
+
+use crate::rtdf::{Deserialize, StructSchema, TdfSchema};
+use crate::hex::hex;
+use std::fmt::Write;
+
+const TEMPLATE: &str = include_str!("dissector.lua");
+
+
+/// Lua dissector builder
+#[derive(Debug, Clone)]
+pub struct LuaDissector {
+    name: String,
+    description: String,
+    ports: Vec<u16>,
+    header_size: usize,
+    structs: Vec<StructSchema>,
+    bodies: Vec<String>,
+    samples: Vec<(String, Option<String>, Vec<u8>)>,
+}
+
+/// Lua string literal
+fn lua_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7E => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Lua table describing the schema of field value
+fn lua_schema(schema: &TdfSchema) -> String {
+    match schema {
+        TdfSchema::Struct(StructSchema { name, .. }) | TdfSchema::Ref(name) => format!("{{ struct = {} }}", lua_string(name)),
+        TdfSchema::List(item) => format!("{{ list = {} }}", lua_schema(item)),
+        TdfSchema::PairList(key, value) => format!("{{ key = {}, value = {} }}", lua_schema(key), lua_schema(value)),
+        TdfSchema::Optional(inner) => lua_schema(inner),
+        _ => "{}".to_string(),
+    }
+}
+
+/// Label the way it is decoded from the wire
+fn wire_label(label: &str) -> String {
+    crate::token::normalize_label(label)
+}
+
+impl LuaDissector {
+
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        let name = name.into();
+        Self {
+            description: format!("{} TDF body", name),
+            name,
+            ports: Vec::new(),
+            header_size: 0,
+            structs: Vec::new(),
+            bodies: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// TCP port to register dissector on
+    pub fn port(mut self, port: u16) -> Self {
+        self.ports.push(port);
+        self
+    }
+
+    /// Bytes of packet header before TDF body, can be changed in Wireshark preferences
+    pub fn header_size(mut self, size: usize) -> Self {
+        self.header_size = size;
+        self
+    }
+
+    /// Struct that may be the body of a message, nested structs are added as well
+    pub fn body<T: Deserialize>(mut self) -> Self {
+        let schema = T::schema();
+        if let TdfSchema::Struct(body) = &schema {
+            self.bodies.push(body.name.clone());
+        }
+        for s in schema.structs() {
+            if !self.structs.iter().any(|known| known.name == s.name) {
+                self.structs.push(s.clone());
+            }
+        }
+        self
+    }
+
+    /// Body bytes decoded when script is run outside of Wireshark
+    pub fn sample<S: Into<String>>(mut self, name: S, body: Vec<u8>) -> Self {
+        self.samples.push((name.into(), None, body));
+        self
+    }
+
+    /// Sample decoded as given body struct, instead of guessed one
+    pub fn sample_of<T: Deserialize, S: Into<String>>(mut self, name: S, body: Vec<u8>) -> Self {
+        let struct_name = match T::schema() {
+            TdfSchema::Struct(s) => Some(s.name),
+            _ => None,
+        };
+        self = self.body::<T>();
+        self.samples.push((name.into(), struct_name, body));
+        self
+    }
+
+    /// Lua source of the dissector
+    pub fn generate(&self) -> String {
+
+        let mut structs = String::new();
+        for s in &self.structs {
+            let _ = writeln!(structs, "    [{}] = {{ fields = {{", lua_string(&s.name));
+            for field in &s.fields {
+                let _ = writeln!(
+                    structs,
+                    "        [{}] = {{ name = {}, schema = {} }},",
+                    lua_string(&wire_label(&field.label)),
+                    lua_string(&field.name),
+                    lua_schema(&field.schema),
+                );
+            }
+            structs.push_str("    } },\n");
+        }
+
+        let mut samples = String::new();
+        for (name, body, bytes) in &self.samples {
+            let hex = hex(bytes, "");
+            let body = match body {
+                Some(body) => format!(", body = {}", lua_string(body)),
+                None => String::new(),
+            };
+            let _ = writeln!(samples, "    {{ name = {}{}, hex = \"{}\" }},", lua_string(name), body, hex);
+        }
+
+        let ports: Vec<String> = self.ports.iter().map(|p| p.to_string()).collect();
+        let bodies: Vec<String> = self.bodies.iter().map(|b| lua_string(b)).collect();
+
+        TEMPLATE
+            .replace("{{PROTO_NAME}}", &lua_string(&self.name))
+            .replace("{{PROTO_DESCRIPTION}}", &lua_string(&self.description))
+            .replace("{{PORTS}}", &ports.join(", "))
+            .replace("{{HEADER_SIZE}}", &self.header_size.to_string())
+            .replace("{{STRUCTS}}", &structs)
+            .replace("{{BODIES}}", &bodies.join(", "))
+            .replace("{{SAMPLES}}", &samples)
+    }
+}
@@ -0,0 +1,370 @@
+use crate::token::*;
+use crate::text::type_from_name;
+use crate::rtdf::{ObjectType, ObjectId};
+use crate::hex::unhex;
+use anyhow::{Result, bail};
+
+
+/// Parsed XML element
+#[derive(Debug, Clone, PartialEq)]
+struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+/// Reads XML document into token stream
+pub struct XmlDeserializer {
+    pub stream: TDFTokenStream,
+    input: Vec<char>,
+    cursor: usize,
+}
+
+impl XmlElement {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+fn parse_int(text: &str) -> Result<i64> {
+    match text.trim().parse::<i64>() {
+        Ok(n) => Ok(n),
+        Err(_) => bail!("Expected integer, found {:?}", text),
+    }
+}
+
+/// Tokens of scalar value written as text
+fn des_scalar(value_type: &TDFToken, text: &str, hex: bool, out: &mut Vec<TDFToken>) -> Result<()> {
+    match value_type {
+        TDFToken::IntType | TDFToken::TimeType => out.push(TDFToken::Int(parse_int(text)?)),
+        TDFToken::StringType if hex => out.push(TDFToken::String(unhex(text)?)),
+        TDFToken::StringType => out.push(TDFToken::String(text.as_bytes().to_vec())),
+        TDFToken::BlobType => out.push(TDFToken::Blob(unhex(text)?)),
+        TDFToken::FloatType => match text.trim().parse::<f32>() {
+            Ok(f) => out.push(TDFToken::Float(f)),
+            Err(_) => bail!("Expected float, found {:?}", text),
+        },
+        TDFToken::ObjectTypeType => {
+            let ObjectType(component, type_id) = text.trim().parse()?;
+            out.push(TDFToken::Int(component));
+            out.push(TDFToken::Int(type_id));
+        },
+        TDFToken::ObjectIdType => {
+            let ObjectId(component, type_id, id) = text.trim().parse()?;
+            out.push(TDFToken::Int(component));
+            out.push(TDFToken::Int(type_id));
+            out.push(TDFToken::Int(id));
+        },
+        _ => bail!("Expected scalar type, found {:?}", value_type),
+    }
+    Ok(())
+}
+
+fn attribute_type(element: &XmlElement, name: &str) -> Result<TDFToken> {
+    match element.attribute(name) {
+        Some(type_name) => type_from_name(type_name),
+        None => bail!("Expected {} attribute in <{}>", name, element.name),
+    }
+}
+
+/// Type of element by its type attribute, or by its content
+fn element_type(element: &XmlElement) -> Result<TDFToken> {
+    Ok(match element.attribute("type") {
+        Some(type_name) => type_from_name(type_name)?,
+        None if !element.children.is_empty() => TDFToken::MapType,
+        None if element.text.trim().parse::<i64>().is_ok() => TDFToken::IntType,
+        None => TDFToken::StringType,
+    })
+}
+
+/// Label, type and value of map field or union/generic member
+fn des_field(element: &XmlElement, out: &mut Vec<TDFToken>) -> Result<()> {
+    // Label that can't be element name is kept as it is on the wire
+    let (label, raw) = match (element.name.as_str(), element.attribute("label")) {
+        ("field", Some(label)) => (label, true),
+        (name, _) => (name, false),
+    };
+    if label.chars().count() > 4 {
+        bail!("Label {:?} is longer than 4 chars", label);
+    }
+    let value_type = element_type(element)?;
+    out.push(TDFToken::Label(if raw { format!("{:<4}", label) } else { normalize_label(label) }));
+    out.push(value_type.clone());
+    des_value(element, &value_type, out)
+}
+
+fn des_value(element: &XmlElement, value_type: &TDFToken, out: &mut Vec<TDFToken>) -> Result<()> {
+    match value_type {
+        TDFToken::MapType => {
+            out.push(TDFToken::MapStart);
+            for child in &element.children {
+                if child.attribute("map_union") == Some("true") {
+                    out.push(TDFToken::MapUnion);
+                }
+                des_field(child, out)?;
+            }
+            out.push(TDFToken::MapEnd);
+        },
+        TDFToken::ListType => {
+            let item_type = attribute_type(element, "item")?;
+            out.push(TDFToken::ListStart(element.children.len()));
+            out.push(item_type.clone());
+            for child in &element.children {
+                des_value(child, &item_type, out)?;
+            }
+            out.push(TDFToken::ListEnd);
+        },
+        TDFToken::PairListType => {
+            let key_type = attribute_type(element, "key")?;
+            let item_type = attribute_type(element, "value")?;
+            out.push(TDFToken::PairListStart(element.children.len()));
+            out.push(key_type.clone());
+            out.push(item_type.clone());
+            for child in &element.children {
+                let key = match child.attribute("key") {
+                    Some(key) => key,
+                    None => bail!("Expected key attribute in pair list <{}>", child.name),
+                };
+                des_scalar(&key_type, key, child.attribute("key_encoding") == Some("hex"), out)?;
+                des_value(child, &item_type, out)?;
+            }
+            out.push(TDFToken::PairListEnd);
+        },
+        TDFToken::UnionType => {
+            let tag = match element.attribute("member").map(|m| m.trim().parse::<u8>()) {
+                Some(Ok(tag)) => tag,
+                _ => bail!("Expected member attribute in union <{}>", element.name),
+            };
+            let union_type = UnionType::from_tag(tag);
+            if union_type == UnionType::Unset && tag != UnionType::Unset as u8 {
+                bail!("Unknown union member {}", tag);
+            }
+            out.push(TDFToken::UnionStart(union_type));
+            match (union_type, &element.children[..]) {
+                (UnionType::Unset, []) => {},
+                (UnionType::Unset, _) => bail!("Unset union <{}> can't have a member", element.name),
+                (_, [member]) => des_field(member, out)?,
+                _ => bail!("Expected single member in union <{}>", element.name),
+            }
+            out.push(TDFToken::UnionEnd);
+        },
+        TDFToken::GenericType => {
+            match element.attribute("id") {
+                Some(id) => {
+                    out.push(TDFToken::GenericStart(true));
+                    out.push(TDFToken::Int(parse_int(id)?));
+                    match &element.children[..] {
+                        [] => {},
+                        [member] => des_field(member, out)?,
+                        _ => bail!("Expected at most one member in generic <{}>", element.name),
+                    }
+                },
+                None => out.push(TDFToken::GenericStart(false)),
+            }
+            out.push(TDFToken::GenericEnd);
+        },
+        TDFToken::IntListType => {
+            out.push(TDFToken::IntListStart(element.children.len()));
+            for child in &element.children {
+                out.push(TDFToken::Int(parse_int(&child.text)?));
+            }
+            out.push(TDFToken::IntListEnd);
+        },
+        scalar => {
+            if !element.children.is_empty() {
+                bail!("Expected {:?} in <{}>, found elements", scalar, element.name);
+            }
+            des_scalar(scalar, &element.text, element.attribute("encoding") == Some("hex"), out)?;
+        },
+    }
+    Ok(())
+}
+
+impl XmlDeserializer {
+
+    pub fn new(input: &str) -> Self {
+        Self {
+            stream: TDFTokenStream::new(),
+            input: input.chars().collect(),
+            cursor: 0,
+        }
+    }
+
+    /// Parse whole document, fields of root element become the root map
+    pub fn des_xml(&mut self) -> Result<()> {
+        self.skip_misc()?;
+        let root = self.parse_element()?;
+        self.skip_misc()?;
+        if self.cursor < self.input.len() {
+            bail!("Unexpected content after root element at {}", self.cursor);
+        }
+        let mut tokens = vec![TDFToken::MapType];
+        des_value(&root, &TDFToken::MapType, &mut tokens)?;
+        for token in tokens {
+            self.stream.push(token);
+        }
+        Ok(())
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.input.get(self.cursor + i) == Some(&c))
+    }
+
+    fn skip_until(&mut self, end: &str) -> Result<()> {
+        while !self.starts_with(end) {
+            if self.cursor >= self.input.len() {
+                bail!("Expected {:?}, found end of XML", end);
+            }
+            self.cursor += 1;
+        }
+        self.cursor += end.chars().count();
+        Ok(())
+    }
+
+    fn skip_space(&mut self) {
+        while self.input.get(self.cursor).is_some_and(|c| c.is_whitespace()) {
+            self.cursor += 1;
+        }
+    }
+
+    /// Whitespace, declarations, comments and doctype
+    fn skip_misc(&mut self) -> Result<()> {
+        loop {
+            self.skip_space();
+            if self.starts_with("<?") {
+                self.skip_until("?>")?;
+            } else if self.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if self.starts_with("<!") {
+                self.skip_until(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_space();
+        match self.input.get(self.cursor) {
+            Some(c) if *c == expected => {
+                self.cursor += 1;
+                Ok(())
+            },
+            Some(c) => bail!("Expected {:?}, found {:?} at {}", expected, c, self.cursor),
+            None => bail!("Expected {:?}, found end of XML", expected),
+        }
+    }
+
+    fn read_name(&mut self) -> Result<String> {
+        self.skip_space();
+        let start = self.cursor;
+        while self.input.get(self.cursor).is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')) {
+            self.cursor += 1;
+        }
+        if start == self.cursor {
+            bail!("Expected name at {}", self.cursor);
+        }
+        Ok(self.input[start..self.cursor].iter().collect())
+    }
+
+    /// Text till the given char, with entities replaced
+    fn read_text(&mut self, end: char) -> Result<String> {
+        let mut text = String::new();
+        while let Some(c) = self.input.get(self.cursor) {
+            if *c == end {
+                return Ok(text);
+            }
+            self.cursor += 1;
+            if *c != '&' {
+                text.push(*c);
+                continue;
+            }
+            let start = self.cursor;
+            self.skip_until(";")?;
+            let entity: String = self.input[start..self.cursor - 1].iter().collect();
+            let decoded = match entity.as_str() {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                e => match e.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => e.strip_prefix('#').and_then(|n| n.parse::<u32>().ok()),
+                }.and_then(char::from_u32),
+            };
+            match decoded {
+                Some(c) => text.push(c),
+                None => bail!("Unknown entity &{};", entity),
+            }
+        }
+        bail!("Expected {:?}, found end of XML", end)
+    }
+
+    fn parse_element(&mut self) -> Result<XmlElement> {
+        self.expect('<')?;
+        let mut element = XmlElement {
+            name: self.read_name()?,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new(),
+        };
+
+        loop {
+            self.skip_space();
+            if self.starts_with("/>") {
+                self.cursor += 2;
+                return Ok(element);
+            }
+            if self.starts_with(">") {
+                self.cursor += 1;
+                break;
+            }
+            let key = self.read_name()?;
+            self.expect('=')?;
+            self.skip_space();
+            let quote = match self.input.get(self.cursor) {
+                Some(c) if *c == '"' || *c == '\'' => *c,
+                _ => bail!("Expected quoted value of attribute {:?}", key),
+            };
+            self.cursor += 1;
+            let value = self.read_text(quote)?;
+            self.cursor += 1;
+            element.attributes.push((key, value));
+        }
+
+        loop {
+            if self.starts_with("</") {
+                self.cursor += 2;
+                let name = self.read_name()?;
+                if name != element.name {
+                    bail!("Expected </{}>, found </{}>", element.name, name);
+                }
+                self.expect('>')?;
+                break;
+            } else if self.starts_with("<!--") {
+                self.skip_until("-->")?;
+            } else if self.starts_with("<![CDATA[") {
+                let start = self.cursor + 9;
+                self.skip_until("]]>")?;
+                element.text.extend(&self.input[start..self.cursor - 3]);
+            } else if self.starts_with("<") {
+                element.children.push(self.parse_element()?);
+            } else {
+                let text = self.read_text('<')?;
+                element.text.push_str(&text);
+            }
+        }
+
+        Ok(element)
+    }
+}
+
+impl TDFDeserializer<String> for XmlDeserializer {
+    fn deserialize(reader: &mut String) -> Result<TDFTokenStream> {
+        let mut des = Self::new(reader);
+        des.des_xml()?;
+        Ok(des.stream)
+    }
+}
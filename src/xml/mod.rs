@@ -0,0 +1,41 @@
+/*
+    XML form of TDF, in the style of Blaze XML responses
+
+    <?xml version="1.0" encoding="UTF-8"?>
+    <tdf>
+        <gid>5</gid>
+        <name>game</name>
+        <pros type="List" item="Map">
+            <entry>
+                <pnam>player</pnam>
+            </entry>
+        </pros>
+        <attr type="PairList" key="String" value="String">
+            <entry key="mode">conquest</entry>
+        </attr>
+        <addr type="Union" member="2">
+            <valu>
+                <ip>2130706433</ip>
+            </valu>
+        </addr>
+    </tdf>
+
+    Map fields are elements named by lowercased labels, spaces become _.
+    Labels with other chars or starting with digit, like 1A_B, are written
+    as <field label="1A_B">. Type of a field is given by type attribute,
+    which is left out for Int, String and non empty Map, and for values
+    whose type is known from their container.
+    Strings that look like numbers are written with type="String",
+    strings XML can't hold are written in hex with encoding="hex".
+    Blob is hex, Float, Time, ObjectType (4/1) and ObjectId (4/1/9) are text.
+    IntList and List have entry elements, PairList entries have key attribute.
+    Union has member attribute with union type, Generic has id attribute
+    unless it is invalid. Field preceded by map union marker has
+    map_union="true" attribute.
+*/
+
+mod ser;
+pub use ser::*;
+
+mod des;
+pub use des::*;
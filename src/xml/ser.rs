@@ -0,0 +1,286 @@
+use crate::token::*;
+use crate::text::type_name;
+use crate::hex::hex;
+use anyhow::{Result, bail};
+
+
+/// Writes token stream as XML document
+pub struct XmlSerializer {
+    stream: TDFTokenStream,
+    root: String,
+    indent: String,
+}
+
+/// XML text with special chars escaped, attributes keep whitespace as char references
+pub fn escape_xml(s: &str, attribute: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\r' => out.push_str("&#13;"),
+            '\n' | '\t' if attribute => out.push_str(&format!("&#{};", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// String as XML text, or None if XML can't hold it
+fn xml_text(bytes: &[u8]) -> Option<&str> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    let valid = s.chars().all(|c| !matches!(c, '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}'));
+    if valid { Some(s) } else { None }
+}
+
+/// Element name of label, None when label has chars XML names can't
+/// hold or that would be ambiguous, like _ or leading digit
+fn element_name(label: &str) -> Option<String> {
+    let label = label.trim_end();
+    let valid = label.starts_with(|c: char| c.is_ascii_uppercase() || c == ' ')
+        && label.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == ' ');
+    if valid { Some(label.replace(' ', "_").to_lowercase()) } else { None }
+}
+
+impl XmlSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream,
+            root: "tdf".to_string(),
+            indent: "    ".to_string(),
+        }
+    }
+
+    /// Name of root element, like the name of response class
+    pub fn with_root<S: Into<String>>(mut self, root: S) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Indentation of nested elements, empty one writes the document in one line
+    pub fn with_indent<S: Into<String>>(mut self, indent: S) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    /// Write whole stream as XML into the writer
+    pub fn write_xml(&mut self, writer: &mut String) -> Result<()> {
+        let token = self.stream.next()?;
+        if token != TDFToken::MapType {
+            bail!("Expected root Map, found {:?}", token);
+        }
+        writer.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        if !self.indent.is_empty() {
+            writer.push('\n');
+        }
+        let root = self.root.clone();
+        self.ser_element(writer, &root, token, true, Vec::new(), 0)?;
+        if !self.indent.is_empty() {
+            writer.push('\n');
+        }
+        Ok(())
+    }
+
+    fn line(&self, level: usize) -> String {
+        if self.indent.is_empty() {
+            return String::new();
+        }
+        format!("\n{}", self.indent.repeat(level))
+    }
+
+    fn read_int(&mut self) -> Result<i64> {
+        match self.stream.next()? {
+            TDFToken::Int(n) => Ok(n),
+            token => bail!("Expected Integer, found {:?}", token),
+        }
+    }
+
+    fn expect_end(&mut self, end: TDFToken) -> Result<()> {
+        let token = self.stream.next()?;
+        if token != end {
+            bail!("Expected {:?}, found {:?}", end, token);
+        }
+        Ok(())
+    }
+
+    /// Text of scalar value and whether it is hex encoded string
+    fn ser_scalar(&mut self, value_type: &TDFToken) -> Result<(String, bool)> {
+        Ok(match value_type {
+            TDFToken::IntType | TDFToken::TimeType => (self.read_int()?.to_string(), false),
+            TDFToken::StringType => match self.stream.next()? {
+                TDFToken::String(bytes) => match xml_text(&bytes) {
+                    Some(s) => (s.to_string(), false),
+                    None => (hex(&bytes, ""), true),
+                },
+                token => bail!("Expected String, found {:?}", token),
+            },
+            TDFToken::BlobType => match self.stream.next()? {
+                TDFToken::Blob(blob) => (hex(&blob, ""), false),
+                token => bail!("Expected Blob, found {:?}", token),
+            },
+            TDFToken::FloatType => match self.stream.next()? {
+                TDFToken::Float(f) => (format!("{:?}", f), false),
+                token => bail!("Expected Float, found {:?}", token),
+            },
+            TDFToken::ObjectTypeType => (format!("{}/{}", self.read_int()?, self.read_int()?), false),
+            TDFToken::ObjectIdType => (format!("{}/{}/{}", self.read_int()?, self.read_int()?, self.read_int()?), false),
+            _ => bail!("Expected scalar type, found {:?}", value_type),
+        })
+    }
+
+    /// Label and value of map field or union/generic member
+    fn ser_field(&mut self, out: &mut String, label: TDFToken, mut attributes: Vec<(&str, String)>, level: usize) -> Result<()> {
+        let name = match label {
+            TDFToken::Label(label) => match element_name(&label) {
+                Some(name) => name,
+                None => {
+                    attributes.insert(0, ("label", label.trim_end().to_string()));
+                    "field".to_string()
+                },
+            },
+            token => bail!("Expected Label, found {:?}", token),
+        };
+        let value_type = self.stream.next()?;
+        out.push_str(&self.line(level));
+        self.ser_element(out, &name, value_type, false, attributes, level)
+    }
+
+    /// Element of the value, known type is not written
+    fn ser_element(&mut self, out: &mut String, name: &str, value_type: TDFToken, known: bool, mut attributes: Vec<(&str, String)>, level: usize) -> Result<()> {
+
+        let mut children = String::new();
+        let mut text = String::new();
+        let mut needs_type = !matches!(value_type, TDFToken::IntType | TDFToken::StringType | TDFToken::MapType);
+
+        match &value_type {
+            TDFToken::MapType => {
+                self.expect_end(TDFToken::MapStart)?;
+                loop {
+                    match self.stream.next()? {
+                        TDFToken::MapEnd => break,
+                        TDFToken::MapUnion => {
+                            let label = self.stream.next()?;
+                            self.ser_field(&mut children, label, vec![("map_union", "true".to_string())], level + 1)?;
+                        },
+                        label => self.ser_field(&mut children, label, Vec::new(), level + 1)?,
+                    }
+                }
+                needs_type = children.is_empty();
+            },
+            TDFToken::ListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::ListStart(size) => size,
+                    token => bail!("Expected List, found {:?}", token),
+                };
+                let item_type = self.stream.next()?;
+                attributes.push(("item", type_name(&item_type)?.to_string()));
+                for _ in 0..size {
+                    children.push_str(&self.line(level + 1));
+                    self.ser_element(&mut children, "entry", item_type.clone(), true, Vec::new(), level + 1)?;
+                }
+                self.expect_end(TDFToken::ListEnd)?;
+            },
+            TDFToken::PairListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::PairListStart(size) => size,
+                    token => bail!("Expected Pair list, found {:?}", token),
+                };
+                let key_type = self.stream.next()?;
+                let item_type = self.stream.next()?;
+                attributes.push(("key", type_name(&key_type)?.to_string()));
+                attributes.push(("value", type_name(&item_type)?.to_string()));
+                for _ in 0..size {
+                    let (key, hex) = self.ser_scalar(&key_type)?;
+                    let mut entry = vec![("key", key)];
+                    if hex {
+                        entry.push(("key_encoding", "hex".to_string()));
+                    }
+                    children.push_str(&self.line(level + 1));
+                    self.ser_element(&mut children, "entry", item_type.clone(), true, entry, level + 1)?;
+                }
+                self.expect_end(TDFToken::PairListEnd)?;
+            },
+            TDFToken::UnionType => {
+                let union_type = match self.stream.next()? {
+                    TDFToken::UnionStart(union_type) => union_type,
+                    token => bail!("Expected Union start, found {:?}", token),
+                };
+                attributes.push(("member", (union_type as u8).to_string()));
+                if union_type != UnionType::Unset {
+                    let label = self.stream.next()?;
+                    self.ser_field(&mut children, label, Vec::new(), level + 1)?;
+                }
+                self.expect_end(TDFToken::UnionEnd)?;
+            },
+            TDFToken::GenericType => {
+                let valid = match self.stream.next()? {
+                    TDFToken::GenericStart(valid) => valid,
+                    token => bail!("Expected Generic start, found {:?}", token),
+                };
+                if valid {
+                    attributes.push(("id", self.read_int()?.to_string()));
+                }
+                match self.stream.next()? {
+                    TDFToken::GenericEnd => {},
+                    label if valid => {
+                        self.ser_field(&mut children, label, Vec::new(), level + 1)?;
+                        self.expect_end(TDFToken::GenericEnd)?;
+                    },
+                    token => bail!("Expected End of Generic, found {:?}", token),
+                }
+            },
+            TDFToken::IntListType => {
+                let size = match self.stream.next()? {
+                    TDFToken::IntListStart(size) => size,
+                    token => bail!("Expected Int List start, found {:?}", token),
+                };
+                for _ in 0..size {
+                    children.push_str(&self.line(level + 1));
+                    children.push_str(&format!("<entry>{}</entry>", self.read_int()?));
+                }
+                self.expect_end(TDFToken::IntListEnd)?;
+            },
+            scalar => {
+                let (value, hex) = self.ser_scalar(scalar)?;
+                if hex {
+                    attributes.push(("encoding", "hex".to_string()));
+                }
+                // Strings that would be read back as Int
+                needs_type |= hex || (*scalar == TDFToken::StringType && value.trim().parse::<i64>().is_ok());
+                text = escape_xml(&value, false);
+            },
+        }
+
+        if needs_type && !known {
+            attributes.insert(0, ("type", type_name(&value_type)?.to_string()));
+        }
+
+        out.push('<');
+        out.push_str(name);
+        for (key, value) in &attributes {
+            out.push_str(&format!(" {}=\"{}\"", key, escape_xml(value, true)));
+        }
+        if children.is_empty() && text.is_empty() {
+            out.push_str("/>");
+            return Ok(());
+        }
+        out.push('>');
+        if children.is_empty() {
+            out.push_str(&text);
+        } else {
+            out.push_str(&children);
+            out.push_str(&self.line(level));
+        }
+        out.push_str(&format!("</{}>", name));
+        Ok(())
+    }
+}
+
+impl TDFSerializer<String> for XmlSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut String) -> Result<()> {
+        Self::new(stream).write_xml(writer)
+    }
+}
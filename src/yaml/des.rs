@@ -0,0 +1,72 @@
+use crate::token::*;
+use crate::value::{TdfValue, Tagged, TAG_STRING};
+use super::{TAG_BLOB, tag_from_name};
+use crate::hex::unhex;
+use anyhow::{Result, bail};
+use serde_yaml::Value;
+
+
+/// Reads YAML document into token stream
+pub struct YamlDeserializer {
+    pub stream: TDFTokenStream,
+    input: String,
+}
+
+fn hex_of(value: Value) -> Result<Vec<u8>> {
+    match value {
+        Value::String(s) => unhex(&s),
+        value => bail!("Expected hex string, found {:?}", value),
+    }
+}
+
+fn from_yaml(value: Value) -> Result<Tagged> {
+    Ok(match value {
+        Value::Null => bail!("TDF has no null value"),
+        Value::Bool(v) => Tagged::Bool(v),
+        Value::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(v), _, _) => Tagged::Int(v),
+            (None, Some(v), _) => bail!("Integer {} doesn't fit into 64 bits", v),
+            (None, None, Some(v)) => Tagged::Float(v as f32),
+            _ => bail!("Invalid number {}", n),
+        },
+        Value::String(v) => Tagged::String(v),
+        Value::Sequence(items) => Tagged::Array(items.into_iter().map(from_yaml).collect::<Result<_>>()?),
+        Value::Mapping(mapping) => Tagged::Map(mapping.into_iter()
+            .map(|(k, v)| Ok((from_yaml(k)?, from_yaml(v)?)))
+            .collect::<Result<_>>()?),
+        Value::Tagged(tagged) => {
+            let name = tagged.tag.to_string();
+            match tag_from_name(name.trim_start_matches('!')) {
+                Some(TAG_BLOB) => Tagged::Bytes(hex_of(tagged.value)?),
+                Some(TAG_STRING) => Tagged::Tag(TAG_STRING, Box::new(Tagged::Bytes(hex_of(tagged.value)?))),
+                Some(tag) => Tagged::Tag(tag, Box::new(from_yaml(tagged.value)?)),
+                None => bail!("Unknown tag {}", name),
+            }
+        },
+    })
+}
+
+impl YamlDeserializer {
+
+    pub fn new(input: &str) -> Self {
+        Self {
+            stream: TDFTokenStream::new(),
+            input: input.to_string(),
+        }
+    }
+
+    /// Parse whole document into the stream
+    pub fn des_yaml(&mut self) -> Result<()> {
+        let value: Value = serde_yaml::from_str(&self.input)?;
+        self.stream = TdfValue::tagged_to_stream(from_yaml(value)?)?;
+        Ok(())
+    }
+}
+
+impl TDFDeserializer<String> for YamlDeserializer {
+    fn deserialize(reader: &mut String) -> Result<TDFTokenStream> {
+        let mut des = Self::new(reader);
+        des.des_yaml()?;
+        Ok(des.stream)
+    }
+}
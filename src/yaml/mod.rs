@@ -0,0 +1,64 @@
+/*
+    YAML form of TDF, enabled by "yaml" feature
+
+    GID: 5
+    NAME: game
+    RATE: 0.5
+    DATA: !blob 00ff
+    PROS:
+    - PNAM: player
+    ATTR: !pair_list
+      mode: conquest
+    ADDR: !union
+    - 2
+    - VALU
+    - PORT: 3659
+    INTS: !int_list
+    - 1
+    - 2
+    OBJI: !object_id
+    - 4
+    - 1
+    - 9
+    TIME: !time 1600000000
+
+    Int, String, Float, Map and List are plain YAML values, labels are
+    written trimmed as they are on the wire and read in any case. Other
+    types are tagged with their names: blobs and strings that aren't
+    UTF-8 are hex, Union is [member, label, value],
+    Generic is [id, label, value], [id] or [] when invalid, Map that
+    starts with union marker is tagged !map. Types of empty List and
+    PairList are not kept and are read back as Int
+*/
+
+mod ser;
+pub use ser::*;
+
+mod des;
+pub use des::*;
+
+use crate::value::{TAG_STRING, TAG_MAP, TAG_PAIR_LIST, TAG_UNION, TAG_INT_LIST, TAG_OBJECT_TYPE, TAG_OBJECT_ID, TAG_TIME, TAG_GENERIC};
+
+/// Blob is tagged only in YAML, which has no binary values
+const TAG_BLOB: u8 = 0x2;
+
+/// Name of YAML tag
+fn tag_name(tag: u8) -> Option<&'static str> {
+    Some(match tag {
+        TAG_STRING => "string",
+        TAG_BLOB => "blob",
+        TAG_MAP => "map",
+        TAG_PAIR_LIST => "pair_list",
+        TAG_UNION => "union",
+        TAG_INT_LIST => "int_list",
+        TAG_OBJECT_TYPE => "object_type",
+        TAG_OBJECT_ID => "object_id",
+        TAG_TIME => "time",
+        TAG_GENERIC => "generic",
+        _ => return None,
+    })
+}
+
+fn tag_from_name(name: &str) -> Option<u8> {
+    (0..=TAG_GENERIC).find(|tag| tag_name(*tag) == Some(name))
+}
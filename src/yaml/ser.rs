@@ -0,0 +1,65 @@
+use crate::token::*;
+use crate::value::{TdfValue, Tagged};
+use super::{TAG_BLOB, tag_name};
+use crate::hex::hex;
+use anyhow::{Result, bail};
+use serde_yaml::Value;
+use serde_yaml::value::{Tag, TaggedValue};
+
+
+/// Writes token stream as YAML document
+pub struct YamlSerializer {
+    stream: TDFTokenStream,
+}
+
+fn tagged(tag: u8, value: Value) -> Result<Value> {
+    match tag_name(tag) {
+        Some(name) => Ok(Value::Tagged(Box::new(TaggedValue { tag: Tag::new(name), value }))),
+        None => bail!("Unknown tag {}", tag),
+    }
+}
+
+/// YAML value, bytes are tagged as blob unless already inside tag
+fn to_yaml(value: Tagged, in_tag: bool) -> Result<Value> {
+    Ok(match value {
+        Tagged::Int(v) => Value::Number(v.into()),
+        Tagged::Bool(v) => Value::Bool(v),
+        // Shortest text of f32, so 0.1 is not written as 0.10000000149011612
+        Tagged::Float(v) => Value::Number(v.to_string().parse::<f64>().unwrap_or(v as f64).into()),
+        Tagged::String(v) => Value::String(v),
+        Tagged::Bytes(v) if in_tag => Value::String(hex(&v, "")),
+        Tagged::Bytes(v) => tagged(TAG_BLOB, Value::String(hex(&v, "")))?,
+        Tagged::Array(items) => Value::Sequence(items.into_iter().map(|v| to_yaml(v, false)).collect::<Result<_>>()?),
+        Tagged::Map(entries) => {
+            let mut mapping = serde_yaml::Mapping::new();
+            for (k, v) in entries {
+                mapping.insert(to_yaml(k, false)?, to_yaml(v, false)?);
+            }
+            Value::Mapping(mapping)
+        },
+        Tagged::Tag(tag, value) => tagged(tag, to_yaml(*value, true)?)?,
+    })
+}
+
+impl YamlSerializer {
+
+    pub fn new(stream: TDFTokenStream) -> Self {
+        Self {
+            stream
+        }
+    }
+
+    /// Write whole stream as YAML into the writer
+    pub fn write_yaml(&mut self, writer: &mut String) -> Result<()> {
+        let stream = std::mem::replace(&mut self.stream, TDFTokenStream::new());
+        let value = to_yaml(TdfValue::stream_to_tagged(stream)?, false)?;
+        writer.push_str(&serde_yaml::to_string(&value)?);
+        Ok(())
+    }
+}
+
+impl TDFSerializer<String> for YamlSerializer {
+    fn serialize(stream: TDFTokenStream, writer: &mut String) -> Result<()> {
+        Self::new(stream).write_yaml(writer)
+    }
+}